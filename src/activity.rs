@@ -0,0 +1,101 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::translation::unix_now;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const SECONDS_PER_DAY: i64 = 86400;
+/// Days of history kept per chat, enough for any realistic streak.
+const MAX_DAYS: usize = 400;
+
+/// Practice answers of one chat on one day (UTC).
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct DayActivity {
+    pub answers: u32,
+    pub correct: u32,
+    pub words: BTreeSet<String>,
+}
+
+type ActivityLog = HashMap<i64, BTreeMap<i64, DayActivity>>;
+
+/// Serializes the read-modify-write of the activity file.
+static ACTIVITY_LOCK: Mutex<()> = Mutex::new(());
+
+/// Defaults to practice_activity.json next to the translations storage file.
+fn get_activity_path() -> String {
+    env::var("PRACTICE_ACTIVITY_FILE").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("practice_activity.json")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+fn read_activity() -> Result<ActivityLog> {
+    let path = get_activity_path();
+    if !Path::new(&path).exists() {
+        return Ok(HashMap::new());
+    }
+    let data = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+pub fn today() -> i64 {
+    unix_now() / SECONDS_PER_DAY
+}
+
+/// Counts a graded practice answer for today.
+pub fn record_answer(chat_id: i64, word: &str, correct: bool) -> Result<()> {
+    let _guard = ACTIVITY_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut log = read_activity()?;
+    let days = log.entry(chat_id).or_default();
+
+    let day = days.entry(today()).or_default();
+    day.answers += 1;
+    if correct {
+        day.correct += 1;
+    }
+    day.words.insert(word.to_string());
+
+    while days.len() > MAX_DAYS {
+        days.pop_first();
+    }
+
+    let path = get_activity_path();
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string(&log)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// Today's activity and the number of consecutive days practiced. A streak
+/// isn't broken until a whole day passes without practice, so it counts up
+/// to yesterday if nothing was answered today yet.
+pub fn activity_summary(chat_id: i64) -> Result<(DayActivity, u32)> {
+    let log = read_activity()?;
+    let Some(days) = log.get(&chat_id) else {
+        return Ok((DayActivity::default(), 0));
+    };
+
+    let today = today();
+    let today_activity = days.get(&today).cloned().unwrap_or_default();
+    let mut day = if today_activity.answers > 0 {
+        today
+    } else {
+        today - 1
+    };
+    let mut streak = 0;
+    while days.get(&day).is_some_and(|activity| activity.answers > 0) {
+        streak += 1;
+        day -= 1;
+    }
+    Ok((today_activity, streak))
+}
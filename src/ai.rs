@@ -1,3 +1,9 @@
+use std::{
+    fmt,
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+
 use serde::{Deserialize, Serialize};
 
 pub const CHATGPT_MODEL: &str = "gpt-4o-latest";
@@ -5,13 +11,13 @@ pub const CHATGPT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
 pub const DEEPSEEK_API_URL: &str = "https://api.deepseek.com/chat/completions";
 pub const DEEPSEEK_MODEL: &str = "deepseek-chat";
 
-pub const RUSSIAN_TO_GERMAN_PROMPT: &str = r#"You are a Russian-German translator.
-Simply translate the given Russian word or phrase to German without any additional information."#;
+pub const TARGET_TO_GERMAN_PROMPT: &str = r#"You are a {language}-German translator.
+Simply translate the given {language} word or phrase to German without any additional information."#;
 
-pub const GERMAN_WORD_PROMPT: &str = r#"You are a German-Russian translator.
+pub const GERMAN_WORD_PROMPT: &str = r#"You are a German-{language} translator.
 For verbs:
 - First line: Original word in German
-- Second line: Russian translation without brackets or decorations
+- Second line: {language} translation without brackets or decorations
 - Third line: Partizip II form
 - Fourth line: Präteritum form
 Then conjugation in Präsens:
@@ -21,30 +27,31 @@ Then conjugation in Präsens:
 - wir form
 - ihr form
 - sie/Sie form
-Then provide 2 simple example sentences in format:
-1. German sentence - Russian translation
-2. German sentence - Russian translation
+Then provide {example_count} simple example sentences in format:
+{example_lines}
 
 For nouns:
 - First line: Original word in German
-- Second line: Russian translation without brackets or decorations
+- Second line: {language} translation without brackets or decorations
 - Third line: German article in nominative case
-- Then provide 2 simple example sentences in format:
-1. German sentence - Russian translation
-2. German sentence - Russian translation
+- Then provide {example_count} simple example sentences in format:
+{example_lines}
 
 For other word types:
 - First line: Original word in German
-- Second line: Russian translation without brackets or decorations
-- Then provide 2 simple example sentences in format:
-1. German sentence - Russian translation
-2. German sentence - Russian translation
+- Second line: {language} translation without brackets or decorations
+- Then provide {example_count} simple example sentences in format:
+{example_lines}
+
+For German words with three or more syllables, add a line "Silben: Ver·ant·wor·tung" (the German word split into syllables with ·) directly before the example sentences.
+
+Separate each example sentence from its translation with exactly " - " (space, hyphen, space).
 
 If there are spelling mistakes in the input, please correct them without any comments and write the corrected version instead of the original word."#;
 
-pub const RUSSIAN_WORD_PROMPT: &str = r#"You are a Russian-German translator.
+pub const TARGET_WORD_PROMPT: &str = r#"You are a {language}-German translator.
 For verbs:
-- First line: Original word in Russian
+- First line: Original word in {language}
 - Second line: German translation without brackets or decorations
 - Third line: Partizip II form
 - Fourth line: Präteritum form
@@ -55,45 +62,69 @@ Then conjugation in Präsens:
 - wir form
 - ihr form
 - sie/Sie form
-Then provide 2 simple example sentences in format:
-1. Russian sentence - German translation
-2. Russian sentence - German translation
+Then provide {example_count} simple example sentences in format:
+{example_lines}
 
 For nouns:
-- First line: Original word in Russian
+- First line: Original word in {language}
 - Second line: German translation without brackets or decorations
 - Third line: German article in nominative case
-- Then provide 2 simple example sentences in format:
-1. Russian sentence - German translation
-2. Russian sentence - German translation
+- Then provide {example_count} simple example sentences in format:
+{example_lines}
 
 For other word types:
-- First line: Original word in Russian
+- First line: Original word in {language}
 - Second line: German translation without brackets or decorations
-- Then provide 2 simple example sentences in format:
-1. Russian sentence - German translation
-2. Russian sentence - German translation"#;
+- Then provide {example_count} simple example sentences in format:
+{example_lines}
+
+For German words with three or more syllables, add a line "Silben: Ver·ant·wor·tung" (the German word split into syllables with ·) directly before the example sentences.
+
+Separate each example sentence from its translation with exactly " - " (space, hyphen, space)."#;
+
+/// Appended to the word prompt when the first reply couldn't be parsed.
+pub const STRICT_FORMAT_PROMPT: &str = r#"IMPORTANT: Your previous reply could not be read. Follow the line format above exactly.
+The first line must be the German word and the second line its {language} translation.
+Do not add any introduction, comments, headings, markdown or empty lines."#;
 
-pub const GERMAN_SENTENCE_PROMPT: &str = r#"You are a German-Russian translator.
-Simply translate the given German sentence to Russian without any additional information."#;
+pub const EXAMPLES_PROMPT: &str = r#"You are a German-{language} teacher.
+Write {example_count} new simple example sentences with the German word "{word}" ({translation}).
+Use everyday situations and vary the grammatical forms of the word.
+Reply only with the sentences in format:
+{example_lines}"#;
+
+pub const GERMAN_SENTENCE_PROMPT: &str = r#"You are a German-{language} translator.
+Simply translate the given German sentence to {language} without any additional information."#;
+
+/// Appended to the sentence prompts when the text has several sentences, so
+/// each translation can be shown next to its original.
+pub const SENTENCE_ALIGNMENT_PROMPT: &str = r#"The text is split into numbered sentences.
+Translate each sentence separately and reply with exactly one line per sentence, in the same order and numbered the same way, containing only the translation.
+
+{sentences}"#;
 
 pub const EXPLANATION_PROMPT: &str = r#"You are a German language teacher.
 Explain the grammar and meaning of each word in the given German text.
-Provide your explanation in Russian. Try to be concise and short. Focus on
+Provide your explanation in {language}. Try to be concise and short. Focus on
 - Why is the sentence structured this way?
 - Grammar forms
 - Usage rules
 - Any special considerations or common mistakes"#;
 
+pub const MISTAKE_EXPLANATION_PROMPT: &str = r#"You are a German language teacher.
+A student practicing vocabulary answered "{answer}" but the correct answer is "{expected}".
+In one or two short sentences in {language}, explain the mistake: the article, ending, spelling or meaning that is wrong and the rule behind the correct form.
+Reply with the explanation only."#;
+
 pub const GRAMMAR_CHECK_PROMPT: &str = r#"You are a German language grammar checker.
 Check the given German text for grammar mistakes and explain any issues found.
 Be concise and short. Don't list mistakes. Don't give an explanation for correct text.
-Provide your response in Russian in the following format:
+Provide your response in {language} in the following format:
 - First line: Original text with mistakes marked in bold (using *word* format)
 - Second line: Corrected version (if there are mistakes)"#;
 
 pub const FREEFORM_PROMPT: &str = r#"You are a German language expert.
-Please answer the following question about German language in Russian."#;
+Please answer the following question about German language in {language}."#;
 
 pub const SIMPLIFY_PROMPT: &str = r#"You are a German language teacher.
 Simplify the given German sentence while preserving its main meaning.
@@ -105,11 +136,19 @@ Make it easier to understand for beginners by:
 Provide your response in the following format:
 - First line: Original sentence
 - Second line: Simplified version
-- Third line: Russian translation of the simplified version"#;
+- Third line: {language} translation of the simplified version"#;
+
+pub const MONOLINGUAL_PROMPT: &str = r#"You are a German language teacher for advanced learners.
+Explain the given German word in simple German (einsprachig), without any other language.
+Provide your response in the following format:
+- First line: the word with its article if it is a noun
+- Second line: a short definition in simple German
+- Third line: Synonym: one synonym
+- Fourth line: Beispiel: one example sentence"#;
 
 pub const CONTEXT_PROMPT: &str = r#"You are a German language expert.
 The following query is about this word/phrase: {context}
-Please answer the query in Russian, providing relevant information about the context word/phrase."#;
+Please answer the query in {language}, providing relevant information about the context word/phrase."#;
 
 pub const STORY_PROMPT: &str = r#"You are a creative storyteller writing modern German short stories in the style of Éric Rohmer.
 
@@ -157,18 +196,30 @@ If the user makes any grammar mistakes:
 1. Start your response with "Kleine Korrektur:" and show the corrected version
 2. Then continue the conversation naturally, responding to their message
 
-DO NOT translate the user's message to Russian. Instead, maintain a natural conversation in German.
+DO NOT translate the user's message to {language}. Instead, maintain a natural conversation in German.
 Always respond in German, except for the grammar corrections which should be brief and clear.
-
+{quiz}
 Previous conversation:
 {context}
 
 User message: {message}"#;
 
+/// Filled into `{quiz}` of the talk prompt on quiz turns of `/talk quiz`.
+pub const TALK_QUIZ_INSTRUCTION: &str = r#"
+In this reply, naturally use the German word "{word}" and ask the user a short question that invites them to use it too.
+"#;
+
 const MAX_RETRIES: u32 = 5;
 const INITIAL_BACKOFF_MS: u64 = 1000;
 const MAX_BACKOFF_MS: u64 = 32000;
 
+const BREAKER_FAILURE_THRESHOLD: u32 = 3;
+const BREAKER_WINDOW: Duration = Duration::from_secs(120);
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
+
+pub const SERVICE_UNAVAILABLE_MESSAGE: &str =
+    "⚠️ Сервис временно недоступен, попробуйте через минуту.";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClaudeRequest {
     pub model: String,
@@ -185,43 +236,251 @@ pub struct ClaudeMessage {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClaudeResponse {
     pub content: Vec<ClaudeContent>,
+    #[serde(default)]
+    pub usage: Option<ClaudeUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct ClaudeUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+// Default prices in USD per million tokens (Claude Sonnet)
+const DEFAULT_INPUT_PRICE_PER_MTOK: f64 = 3.0;
+const DEFAULT_OUTPUT_PRICE_PER_MTOK: f64 = 15.0;
+
+/// Token usage accumulated over the lifetime of the process.
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    const fn new() -> Self {
+        Self {
+            requests: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// Estimated cost in USD, using CLAUDE_INPUT_PRICE_PER_MTOK and
+    /// CLAUDE_OUTPUT_PRICE_PER_MTOK when set.
+    pub fn estimated_cost(&self) -> f64 {
+        let input_price =
+            price_from_env("CLAUDE_INPUT_PRICE_PER_MTOK", DEFAULT_INPUT_PRICE_PER_MTOK);
+        let output_price = price_from_env(
+            "CLAUDE_OUTPUT_PRICE_PER_MTOK",
+            DEFAULT_OUTPUT_PRICE_PER_MTOK,
+        );
+        (self.input_tokens as f64 * input_price + self.output_tokens as f64 * output_price)
+            / 1_000_000.0
+    }
+}
+
+fn price_from_env(name: &str, default: f64) -> f64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .unwrap_or(default)
+}
+
+static USAGE: Mutex<Usage> = Mutex::new(Usage::new());
+
+pub fn record_usage(usage: Option<ClaudeUsage>) {
+    let mut total = USAGE.lock().unwrap_or_else(PoisonError::into_inner);
+    total.requests += 1;
+    if let Some(usage) = usage {
+        total.input_tokens += usage.input_tokens;
+        total.output_tokens += usage.output_tokens;
+    }
+}
+
+pub fn get_usage() -> Usage {
+    *USAGE.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClaudeContent {
+    // Non-text blocks (e.g. tool_use) have no text
+    #[serde(default)]
     pub text: String,
     pub r#type: String,
 }
 
+/// Returns the first text block of a Claude response, or an error if the
+/// response contains none (e.g. an empty refusal or only tool-use blocks).
+pub fn first_text(
+    response: &ClaudeResponse,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    response
+        .content
+        .iter()
+        .find(|block| block.r#type == "text")
+        .map(|block| block.text.clone())
+        .ok_or_else(|| "Claude response contained no text content".into())
+}
+
+/// Returned instead of calling the API while the circuit breaker is open.
+#[derive(Debug)]
+pub struct ServiceUnavailable;
+
+impl fmt::Display for ServiceUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AI service is temporarily unavailable")
+    }
+}
+
+impl std::error::Error for ServiceUnavailable {}
+
+/// Shared across all requests so an outage trips the breaker once instead of
+/// every message spending the full retry budget.
+struct BreakerState {
+    consecutive_failures: u32,
+    first_failure_at: Option<Instant>,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl BreakerState {
+    const fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            first_failure_at: None,
+            open_until: None,
+            trial_in_flight: false,
+        }
+    }
+
+    /// Closed: allow. Open: reject until the cooldown ends, then let a single
+    /// trial request through (half-open) to test recovery.
+    fn allow_request(&mut self) -> bool {
+        let Some(open_until) = self.open_until else {
+            return true;
+        };
+        if Instant::now() < open_until || self.trial_in_flight {
+            return false;
+        }
+        self.trial_in_flight = true;
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        self.open_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    fn record_success(&mut self) {
+        *self = Self::new();
+    }
+
+    fn record_failure(&mut self) {
+        let now = Instant::now();
+        if self.trial_in_flight {
+            self.trial_in_flight = false;
+            self.open_until = Some(now + BREAKER_COOLDOWN);
+            return;
+        }
+
+        let window_expired = self
+            .first_failure_at
+            .is_none_or(|first| now.duration_since(first) > BREAKER_WINDOW);
+        if window_expired {
+            self.consecutive_failures = 0;
+            self.first_failure_at = Some(now);
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= BREAKER_FAILURE_THRESHOLD {
+            log::warn!(
+                "Claude API failed {} times in a row, pausing requests for {:?}",
+                self.consecutive_failures,
+                BREAKER_COOLDOWN
+            );
+            self.open_until = Some(now + BREAKER_COOLDOWN);
+        }
+    }
+}
+
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Long word cards and stories can take the models a while to write.
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+const HTTP_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// One client for all outgoing API calls so connections and TLS sessions are
+/// reused between requests.
+pub fn http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .timeout(HTTP_REQUEST_TIMEOUT)
+            .pool_idle_timeout(HTTP_POOL_IDLE_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+static BREAKER: Mutex<BreakerState> = Mutex::new(BreakerState::new());
 
+fn with_breaker<T>(f: impl FnOnce(&mut BreakerState) -> T) -> T {
+    f(&mut BREAKER.lock().unwrap_or_else(PoisonError::into_inner))
+}
 
 pub async fn make_claude_request(
     request: &ClaudeRequest,
 ) -> Result<ClaudeResponse, Box<dyn std::error::Error + Send + Sync>> {
-    let client = reqwest::Client::new();
+    let client = http_client();
     let anthropic_api_key = std::env::var("ANTHROPIC_API_KEY")?;
 
+    if !with_breaker(BreakerState::allow_request) {
+        return Err(ServiceUnavailable.into());
+    }
+
     let mut current_retry = 0;
     let mut backoff_ms = INITIAL_BACKOFF_MS;
 
     loop {
-        let response = client
+        let response = match client
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &anthropic_api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
             .json(request)
             .send()
-            .await?;
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                with_breaker(BreakerState::record_failure);
+                return Err(e.into());
+            }
+        };
 
         let status = response.status();
 
         if status.is_success() {
-            return Ok(response.json::<ClaudeResponse>().await?);
+            with_breaker(BreakerState::record_success);
+            let response = response.json::<ClaudeResponse>().await?;
+            record_usage(response.usage);
+            return Ok(response);
         }
 
         // If we get a 529 (or other 5xx) error
         if status.is_server_error() {
+            // Every failed attempt counts, so a long outage opens the breaker
+            // without waiting for the retries to run out
+            with_breaker(BreakerState::record_failure);
+            if with_breaker(|breaker| breaker.is_open()) {
+                return Err(ServiceUnavailable.into());
+            }
             if current_retry >= MAX_RETRIES {
                 return Err(format!("Max retries ({}) exceeded", MAX_RETRIES).into());
             }
@@ -245,7 +504,8 @@ pub async fn make_claude_request(
             continue;
         }
 
-        // For other errors, return immediately
+        // For other errors, return immediately. The service itself is reachable.
+        with_breaker(BreakerState::record_success);
         return Err(format!("Claude API request failed with status: {}", status).into());
     }
 }
@@ -254,6 +514,53 @@ pub async fn make_claude_request(
 pub struct ChatGPTRequest {
     pub model: String,
     pub messages: Vec<ChatGPTMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+}
+
+impl ChatGPTRequest {
+    pub fn new(model: &str, messages: Vec<ChatGPTMessage>) -> Self {
+        Self {
+            model: model.to_string(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            max_completion_tokens: None,
+        }
+    }
+
+    /// Builds an OpenAI request, honoring OPENAI_MODEL, OPENAI_TEMPERATURE and
+    /// OPENAI_MAX_TOKENS. Reasoning models (o-series, gpt-5) only accept
+    /// `max_completion_tokens`, so the limit is sent in that field for them.
+    pub fn openai(messages: Vec<ChatGPTMessage>) -> Self {
+        let model = std::env::var("OPENAI_MODEL").unwrap_or_else(|_| CHATGPT_MODEL.to_string());
+        let mut request = Self::new(&model, messages);
+
+        request.temperature = std::env::var("OPENAI_TEMPERATURE")
+            .ok()
+            .and_then(|value| value.trim().parse::<f32>().ok());
+
+        let token_limit = std::env::var("OPENAI_MAX_TOKENS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u32>().ok());
+        if uses_max_completion_tokens(&model) {
+            request.max_completion_tokens = token_limit;
+        } else {
+            request.max_tokens = token_limit;
+        }
+
+        request
+    }
+}
+
+fn uses_max_completion_tokens(model: &str) -> bool {
+    let mut chars = model.chars();
+    let is_o_series = chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit());
+    is_o_series || model.starts_with("gpt-5")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -271,3 +578,15 @@ pub struct ChatGPTResponse {
 pub struct ChatGPTChoice {
     pub message: ChatGPTMessage,
 }
+
+/// Returns the message content of the first choice of a ChatGPT-compatible
+/// response, or an error if there are no choices.
+pub fn first_choice(
+    response: &ChatGPTResponse,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    response
+        .choices
+        .first()
+        .map(|choice| choice.message.content.clone())
+        .ok_or_else(|| "Response contained no choices".into())
+}
@@ -0,0 +1,144 @@
+use strsim::jaro_winkler;
+
+use crate::translation::{canonical_key, Translation};
+
+/// Keys at least this similar (but not equal) are reported as near duplicates.
+const NEAR_DUPLICATE_SIMILARITY: f64 = 0.95;
+/// Offending entries listed per check; the rest are only counted.
+const MAX_LISTED: usize = 5;
+
+struct Finding {
+    title: &'static str,
+    entries: Vec<String>,
+}
+
+fn is_verb(translation: &Translation) -> bool {
+    translation
+        .conjugations
+        .as_ref()
+        .is_some_and(|conjugations| !conjugations.is_empty())
+}
+
+/// Capitalized single-word headwords are nouns in German, so they should
+/// carry der/die/das as their first grammar form.
+fn nouns_without_article(translations: &[Translation]) -> Vec<String> {
+    translations
+        .iter()
+        .filter(|t| {
+            t.original.chars().next().is_some_and(char::is_uppercase)
+                && !t.original.contains(' ')
+                && !is_verb(t)
+                && !t.is_noun()
+        })
+        .map(|t| match t.grammar_forms.first() {
+            Some(form) => format!("{} (first form: '{}')", t.original, form.trim()),
+            None => format!("{} (no grammar forms)", t.original),
+        })
+        .collect()
+}
+
+fn invalid_entries(translations: &[Translation]) -> Vec<String> {
+    translations
+        .iter()
+        .filter_map(|t| {
+            t.validate()
+                .err()
+                .map(|e| format!("'{}': {}", t.original, e))
+        })
+        .collect()
+}
+
+/// Pairs of headwords that differ only slightly, e.g. a typo saved twice.
+/// Only keys with the same first letter are compared to keep this fast.
+fn near_duplicates(translations: &[Translation]) -> Vec<String> {
+    let mut keys: Vec<(String, &str)> = translations
+        .iter()
+        .map(|t| (t.key(), t.original.as_str()))
+        .collect();
+    keys.sort();
+
+    let mut pairs = Vec::new();
+    for (i, (key, original)) in keys.iter().enumerate() {
+        let first = key.chars().next();
+        for (other_key, other) in keys[i + 1..]
+            .iter()
+            .take_while(|(other_key, _)| other_key.chars().next() == first)
+        {
+            if key == other_key || jaro_winkler(key, other_key) >= NEAR_DUPLICATE_SIMILARITY {
+                pairs.push(format!("{} / {}", original, other));
+            }
+        }
+    }
+    pairs
+}
+
+/// Whether the example mentions the headword, allowing for endings and
+/// conjugated forms.
+fn example_mentions(translation: &Translation, german: &str) -> bool {
+    let sentence = canonical_key(german);
+    let key = translation.key();
+    let headword = key.split_whitespace().last().unwrap_or(&key);
+    let stem_len = (headword.chars().count() * 2 / 3).max(3);
+    let stem: String = headword.chars().take(stem_len).collect();
+
+    sentence.contains(&stem)
+        || translation.conjugations.iter().flatten().any(|form| {
+            form.split_whitespace()
+                .last()
+                .is_some_and(|verb| sentence.contains(&canonical_key(verb)))
+        })
+}
+
+fn examples_without_headword(translations: &[Translation]) -> Vec<String> {
+    translations
+        .iter()
+        .flat_map(|t| {
+            t.examples
+                .iter()
+                .filter(|example| !example_mentions(t, &example.german))
+                .map(move |example| format!("{}: \"{}\"", t.original, example.german))
+        })
+        .collect()
+}
+
+/// Scans the vocabulary for likely data problems and summarizes them with
+/// counts and the first few offending entries.
+pub fn audit_translations(translations: &[Translation]) -> String {
+    let findings = [
+        Finding {
+            title: "Nouns without a valid article",
+            entries: nouns_without_article(translations),
+        },
+        Finding {
+            title: "Invalid entries",
+            entries: invalid_entries(translations),
+        },
+        Finding {
+            title: "Near duplicates",
+            entries: near_duplicates(translations),
+        },
+        Finding {
+            title: "Examples without the headword",
+            entries: examples_without_headword(translations),
+        },
+    ];
+
+    if findings.iter().all(|f| f.entries.is_empty()) {
+        return format!("✅ No problems found in {} entries", translations.len());
+    }
+
+    let mut report = format!("🔍 Audit of {} entries\n", translations.len());
+    for finding in findings.iter().filter(|f| !f.entries.is_empty()) {
+        report.push_str(&format!("\n{}: {}\n", finding.title, finding.entries.len()));
+        for entry in finding.entries.iter().take(MAX_LISTED) {
+            report.push_str(&format!("• {}\n", entry));
+        }
+        if finding.entries.len() > MAX_LISTED {
+            report.push_str(&format!(
+                "...and {} more\n",
+                finding.entries.len() - MAX_LISTED
+            ));
+        }
+    }
+    report
+}
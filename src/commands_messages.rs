@@ -1,34 +1,144 @@
-use std::{collections::HashSet, env, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{Arc, Mutex as StdMutex, OnceLock, PoisonError},
+    time::Instant,
+};
 
 use teloxide::{
-    macros::BotCommands,
     net::Download,
-    payloads::SendDocumentSetters,
+    payloads::{EditMessageTextSetters, SendDocumentSetters, SendMessageSetters, SendVoiceSetters},
     prelude::Requester,
-    types::{InputFile, Message},
-    Bot,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message,
+        MessageId, ParseMode,
+    },
+    utils::command::BotCommands,
+    ApiError, Bot, RequestError,
 };
 use tokio::sync::{broadcast, Mutex};
 
 use crate::{
-    consts::{HELP_MESSAGE, SHUTDOWN_MESSAGE},
-    input::{analyze_input, InputType},
+    activity::activity_summary,
+    ai::{get_usage, ServiceUnavailable, SERVICE_UNAVAILABLE_MESSAGE},
+    audit::audit_translations,
+    consts::{SHUTDOWN_MESSAGE, USAGE_MESSAGE},
+    csv_import::import_csv,
+    daily_set::todays_set,
+    daily_story::{get_send_time, read_daily_story_chats, set_daily_story},
+    flashcards::render_flashcards_html,
+    input::{analyze_input, fold_ascii_digraphs, InputType},
+    language::{is_target_language, TargetLanguage},
+    new_words::{learn_word, LEARN_CALLBACK_PREFIX},
     picture::{
         handle_picture_message, start_picture_session, stop_picture_session, PictureSessions,
     },
-    practice::{check_practice_answer, start_practice_session, stop_practice_session},
+    practice::{
+        check_choice_answer, check_practice_answer, skip_practice_question, start_practice_session,
+        stop_practice_session, PracticeOptions, CHOICE_CALLBACK_PREFIX, LEECH_CALLBACK_PREFIX,
+    },
+    retry::SendRetrying,
+    sentence_log::{
+        is_sentence_log_enabled, log_sentence, read_logged_sentences, search_logged_sentences,
+    },
     story::generate_story,
+    stt::transcribe_voice,
     talk::{handle_talk_message, start_talk_session, stop_talk_session, TalkSessions},
     translation::{
-        add_translation, clear_translations, delete_translation, find_translation,
-        format_translation_response, get_storage_path, import_translations,
-        parse_translation_response, read_translations, translate_text,
+        add_translation, canonical_key, clear_translations, delete_translation,
+        export_translations, find_translation, format_aligned_sentences, format_grammar_check,
+        format_translation_response, get_by_original, get_by_translation, get_example_count,
+        get_random_translation, get_translation, import_translations, mastery, merge_translation,
+        normalize_tag, parse_word_reply, read_translations, regenerate_examples,
+        search_translations, set_archived, set_example_count, set_leech, set_note, similar_words,
+        storage_last_modified, tag_translation, translate_text, unix_now, Mastery, Translation,
+        WordLimitReached, MAX_EXAMPLE_COUNT, MIN_EXAMPLE_COUNT,
     },
+    tts::synthesize_speech,
+    typing::TypingIndicator,
+    user_config::{chat_config, read_user_config, write_user_config, PromptKind, WordKind},
     PracticeSessions,
 };
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 pub type DeleteMode = Arc<tokio::sync::Mutex<HashSet<i64>>>;
+pub type PendingActions = Arc<Mutex<HashMap<i64, PendingAction>>>;
+/// Maps a user's (chat id, message id) to the id of the bot's reply so the
+/// reply can be updated when the user edits their message.
+pub type ReplyMap = Arc<Mutex<HashMap<(i64, MessageId), MessageId>>>;
+/// Cards shown with `~:`, or while auto-save is off, that wait for a save
+/// confirmation, one per chat.
+pub type PendingPreviews = Arc<Mutex<HashMap<i64, Translation>>>;
+/// Words being translated right now, per chat, so sending the same word again
+/// before the first reply doesn't start a second API call. Behind a std mutex
+/// so the marker can clear itself on drop.
+pub type InFlightWords = Arc<StdMutex<HashSet<(i64, String)>>>;
+
+const MAX_TRACKED_REPLIES: usize = 1000;
+const TTS_CALLBACK_PREFIX: &str = "tts:";
+const EXAMPLES_CALLBACK_PREFIX: &str = "examples:";
+const ONBOARDING_CALLBACK_PREFIX: &str = "start:";
+/// The `/neverpracticed` button, which starts practice on the listed words.
+const NEVER_PRACTICED_CALLBACK: &str = "neverpracticed";
+const PREVIEW_CALLBACK_PREFIX: &str = "preview:";
+/// Telegram rejects callback data longer than this many bytes.
+pub(crate) const MAX_CALLBACK_DATA_LEN: usize = 64;
+const MAX_SIMILAR_WORDS: usize = 3;
+const RECENT_SENTENCES_COUNT: usize = 10;
+const MAX_SEARCH_RESULTS: usize = 20;
+const MAX_NEVER_PRACTICED_LISTED: usize = 30;
+const DEFAULT_MAX_IMPORT_SIZE_MB: u64 = 5;
+const DEFAULT_MAX_INPUT_CHARS: usize = 2000;
+const IMPORT_ERROR_PREVIEW_CHARS: usize = 40;
+const MAX_REPLY_CONTEXT_CHARS: usize = 300;
+const OFF_FORMAT_MESSAGE: &str =
+    "⚠️ Не удалось получить перевод в нужном формате, слово не сохранено. Попробуйте переформулировать запрос.";
+/// Keys reported by /health, only as present or missing.
+const HEALTH_API_KEYS: [&str; 4] = [
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "DEEPSEEK_API_KEY",
+    "PIXABAY_API_KEY",
+];
+
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+/// Marks a word as being translated until dropped.
+struct InFlightWord {
+    words: InFlightWords,
+    chat_id: i64,
+    key: String,
+}
+
+impl InFlightWord {
+    /// Returns `None` if the word is already being translated for this chat.
+    fn start(words: &InFlightWords, chat_id: ChatId, word: &str) -> Option<Self> {
+        let key = canonical_key(word);
+        let inserted = words
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((chat_id.0, key.clone()));
+        inserted.then_some(Self {
+            words: words.clone(),
+            chat_id: chat_id.0,
+            key,
+        })
+    }
+}
+
+impl Drop for InFlightWord {
+    fn drop(&mut self) {
+        self.words
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(self.chat_id, std::mem::take(&mut self.key)));
+    }
+}
+
+/// Remembers when the bot started, for the uptime in /health.
+pub fn record_start_time() {
+    STARTED_AT.get_or_init(Instant::now);
+}
 
 #[derive(BotCommands, Clone)]
 #[command(
@@ -36,8 +146,8 @@ pub type DeleteMode = Arc<tokio::sync::Mutex<HashSet<i64>>>;
     description = "These commands are supported:"
 )]
 pub enum Command {
-    #[command(description = "start the bot")]
-    Start,
+    #[command(description = "start the bot, or a mode from a t.me/<bot>?start=<mode> link")]
+    Start(String),
     #[command(description = "show help information")]
     Help,
     #[command(description = "shutdown the bot")]
@@ -46,17 +156,43 @@ pub enum Command {
     Export,
     #[command(description = "clear translations database")]
     Clear,
-    #[command(description = "start practice mode")]
-    Practice,
-    #[command(description = "import translations database from JSON file")]
+    #[command(description = "start practice mode, optionally with ru|de, #tag, recent or stale")]
+    Practice(String),
+    #[command(description = "add a tag to a word: /tag <word> <tag>")]
+    Tag(String),
+    #[command(
+        description = "hide a word from practice and stories, keeping its stats: /forget <word>"
+    )]
+    Forget(String),
+    #[command(description = "bring back a word hidden with /forget: /unforget <word>")]
+    Unforget(String),
+    #[command(description = "list words answered wrong so often that practice skips them")]
+    Leeches,
+    #[command(description = "practice today's fixed set of weak words")]
+    Today,
+    #[command(description = "list words that were never practiced")]
+    NeverPracticed,
+    #[command(description = "practice a single word until you get it right: /drill <word>")]
+    Drill(String),
+    #[command(description = "export printable HTML flashcards")]
+    Flashcards,
+    #[command(description = "turn the daily morning story on or off: /dailystory on|off")]
+    DailyStory(String),
+    #[command(
+        description = "import translations from a JSON, CSV or Anki text file (caption 'merge' or 'replace' to choose; JSON replaces and CSV merges by default)"
+    )]
     Import,
+    #[command(description = "skip the current practice question and show the answer")]
+    Skip,
     #[command(description = "stop practice mode")]
     Stop,
+    #[command(description = "exit whatever mode is active")]
+    Cancel,
     #[command(description = "enter delete mode")]
     Delete,
     #[command(description = "exit delete mode")]
     StopDelete,
-    #[command(description = "show word statistics")]
+    #[command(description = "show word statistics, optionally for one word")]
     Stats(String),
     #[command(description = "generate a short story in German")]
     Story,
@@ -66,14 +202,708 @@ pub enum Command {
     UseClaude,
     #[command(description = "switch to DeepSeek")]
     UseDeepSeek,
-    #[command(description = "start talk mode")]
-    Talk,
+    #[command(description = "start talk mode, /talk quiz also brings up your weak words")]
+    Talk(String),
     #[command(description = "stop talk mode")]
     StopTalk,
-    #[command(description = "start picture description mode")]
-    Pic,
+    #[command(
+        description = "start picture description mode, /pic curated brings back images you described well"
+    )]
+    Pic(String),
     #[command(description = "stop picture description mode")]
     Stoppic,
+    #[command(description = "show a random saved word")]
+    Random,
+    #[command(
+        description = "show a word's examples (/examples <word>) or set their number (/examples <1-5>)"
+    )]
+    Examples(String),
+    #[command(description = "re-query the AI to fill in missing details: /enrich <word>")]
+    Enrich(String),
+    #[command(description = "show Claude token usage and estimated cost")]
+    Usage,
+    #[command(description = "list recently translated sentences")]
+    Sentences,
+    #[command(description = "search saved words and logged sentences: /search <text>")]
+    Search(String),
+    #[command(description = "show or change practice settings: /settings key=value")]
+    Settings(String),
+    #[command(
+        description = "replace an AI prompt for this chat: /setprompt <word|sentence|explain|grammar|freeform|simplify> <text>"
+    )]
+    SetPrompt(String),
+    #[command(description = "restore the built-in AI prompts: /resetprompt [kind]")]
+    ResetPrompt(String),
+    #[command(description = "show or change the translation language: /targetlang <ru|uk|en>")]
+    TargetLang(String),
+    #[command(description = "check saved words for missing articles, duplicates and bad examples")]
+    Audit,
+    #[command(description = "show the bot version")]
+    Version,
+    #[command(description = "show uptime, active sessions and configured API keys")]
+    Health,
+}
+
+/// Entry count, a noun/verb/other breakdown and how long ago the storage
+/// was last written.
+fn export_caption(translations: &[Translation]) -> String {
+    let count_kind = |kind: WordKind| translations.iter().filter(|t| kind.matches(t)).count();
+    let mut caption = format!(
+        "Translation database with {} entries\nNouns: {}, verbs: {}, other: {}",
+        translations.len(),
+        count_kind(WordKind::Noun),
+        count_kind(WordKind::Verb),
+        count_kind(WordKind::Other)
+    );
+    if let Some(age) = storage_last_modified().and_then(|t| t.elapsed().ok()) {
+        let secs = age.as_secs();
+        caption.push_str(&format!(
+            "\nLast updated {}d {}h {}m ago",
+            secs / 86400,
+            secs % 86400 / 3600,
+            secs % 3600 / 60
+        ));
+    }
+    caption
+}
+
+/// "1 день назад", "3 дня назад", "11 дней назад": Russian picks the noun
+/// form from the last one or two digits.
+fn days_ago(days: i64) -> String {
+    let word = match (days % 10, days % 100) {
+        (1, n) if n != 11 => "день",
+        (2..=4, n) if !(12..=14).contains(&n) => "дня",
+        _ => "дней",
+    };
+    format!("{} {} назад", days, word)
+}
+
+/// The command list is derived from `Command` so it can't drift out of sync.
+fn help_message() -> String {
+    format!("{}\n\n{}", Command::descriptions(), USAGE_MESSAGE)
+}
+
+/// Replies with just the numbered example sentences of a stored word.
+async fn send_word_examples(bot: &Bot, msg: &Message, word: &str) -> Result<()> {
+    let Some(translation) = get_translation(word)? else {
+        bot.send_message(msg.chat.id, "Word not found in database.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    };
+
+    if translation.examples.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "No examples for '{}' yet. Use /enrich {} to generate some.",
+                translation.original, translation.original
+            ),
+        )
+        .send_retrying()
+        .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("📝 Examples for '{}':\n", translation.original);
+    for (i, example) in translation.examples.iter().enumerate() {
+        response.push_str(&format!(
+            "\n{}. {}\n   {}",
+            i + 1,
+            example.german,
+            example.russian
+        ));
+    }
+    bot.send_message(msg.chat.id, response)
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+/// Handles /forget (archive = true) and /unforget.
+async fn archive_word(bot: &Bot, msg: &Message, word: &str, archive: bool) -> Result<()> {
+    let message = if word.is_empty() {
+        if archive {
+            "Usage: /forget <word>".to_string()
+        } else {
+            "Usage: /unforget <word>".to_string()
+        }
+    } else if !set_archived(word, archive)? {
+        "Word not found in database.".to_string()
+    } else if archive {
+        format!(
+            "🗄 '{}' won't come up in practice anymore. Use /unforget to bring it back.",
+            word
+        )
+    } else {
+        format!("✅ '{}' is back in practice.", word)
+    };
+    bot.send_message(msg.chat.id, message)
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+/// Today's practice and the current streak for `/stats` without a word.
+fn format_activity(chat_id: i64) -> Result<String> {
+    let (today, streak) = activity_summary(chat_id)?;
+    let accuracy = if today.answers > 0 {
+        format!(
+            "{:.1}%",
+            today.correct as f64 / today.answers as f64 * 100.0
+        )
+    } else {
+        "-".to_string()
+    };
+    Ok(format!(
+        "📅 Today\n\nWords practiced: {}\nAnswers: {}\nAccuracy: {}\nStreak: {} {}",
+        today.words.len(),
+        today.answers,
+        accuracy,
+        streak,
+        if streak == 1 { "day" } else { "days" }
+    ))
+}
+
+/// Vocabulary-wide progress for `/stats` without a word: how many words are
+/// mastered, still being learned or have too few answers to tell.
+fn format_progress(translations: &[Translation], min_attempts: u32) -> String {
+    let (mut mastered, mut learning, mut unknown) = (0, 0, 0);
+    let (mut correct, mut answered) = (0, 0);
+    for translation in translations {
+        match mastery(translation, min_attempts) {
+            Mastery::Mastered => mastered += 1,
+            Mastery::Learning => learning += 1,
+            Mastery::NotEnoughData => {
+                unknown += 1;
+                continue;
+            }
+        }
+        correct += translation.correct_answers;
+        answered += translation.correct_answers + translation.wrong_answers;
+    }
+
+    let accuracy = if answered > 0 {
+        format!("{:.1}%", correct as f64 / answered as f64 * 100.0)
+    } else {
+        "недостаточно данных".to_string()
+    };
+    format!(
+        "📊 Progress\n\nWords: {}\nMastered: {}\nLearning: {}\nNot enough data (under {} answers): {}\nAccuracy of practiced words: {}",
+        translations.len(),
+        mastered,
+        learning,
+        min_attempts,
+        unknown,
+        accuracy
+    )
+}
+
+/// Lists words without any practice answers, with a button to practice
+/// just those.
+async fn send_never_practiced(bot: &Bot, msg: &Message) -> Result<()> {
+    let words: Vec<Translation> = read_translations()?
+        .into_iter()
+        .filter(|t| !t.archived && t.correct_answers + t.wrong_answers == 0)
+        .collect();
+    if words.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Every saved word has been practiced at least once.",
+        )
+        .send_retrying()
+        .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("🆕 Never practiced ({}):\n", words.len());
+    for word in words.iter().take(MAX_NEVER_PRACTICED_LISTED) {
+        response.push_str(&format!(
+            "\n• {} — {}",
+            word.with_article(),
+            word.translation
+        ));
+    }
+    if words.len() > MAX_NEVER_PRACTICED_LISTED {
+        response.push_str(&format!(
+            "\n… and {} more",
+            words.len() - MAX_NEVER_PRACTICED_LISTED
+        ));
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "▶️ Практиковать эти слова",
+        NEVER_PRACTICED_CALLBACK,
+    )]]);
+    bot.send_message(msg.chat.id, response)
+        .reply_markup(keyboard)
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+/// Lists leeches with a button each to put them back into practice.
+async fn send_leeches(bot: &Bot, msg: &Message) -> Result<()> {
+    let leeches: Vec<Translation> = read_translations()?
+        .into_iter()
+        .filter(|t| t.leech)
+        .collect();
+    if leeches.is_empty() {
+        bot.send_message(msg.chat.id, "🐌 No leeches, every word is in practice.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("🐌 Leeches ({}):\n", leeches.len());
+    for leech in &leeches {
+        response.push_str(&format!(
+            "\n• {} — {} (✅ {} / ❌ {})",
+            leech.with_article(),
+            leech.translation,
+            leech.correct_answers,
+            leech.wrong_answers
+        ));
+    }
+    response.push_str("\n\nPress a word to put it back into practice.");
+
+    let buttons: Vec<Vec<InlineKeyboardButton>> = leeches
+        .iter()
+        .map(|leech| format!("{}{}", LEECH_CALLBACK_PREFIX, leech.original))
+        .zip(&leeches)
+        .filter(|(data, _)| data.len() <= MAX_CALLBACK_DATA_LEN)
+        .map(|(data, leech)| vec![InlineKeyboardButton::callback(leech.original.clone(), data)])
+        .collect();
+    bot.send_message(msg.chat.id, response)
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+async fn start_practice(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &PracticeSessions,
+    args: &str,
+) -> Result<()> {
+    match PracticeOptions::parse(args) {
+        Ok(options) => start_practice_session(bot, msg, sessions, options).await,
+        Err(e) => {
+            bot.send_message(
+                msg.chat.id,
+                format!("{}. Usage: /practice [ru|de] [#tag] [recent|stale]", e),
+            )
+            .send_retrying()
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+async fn send_story(
+    bot: &Bot,
+    msg: &Message,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    bot.send_message(msg.chat.id, "Generating a story...")
+        .send_retrying()
+        .await?;
+    let use_chatgpt = *use_chatgpt.lock().await;
+    let use_deepseek = *use_deepseek.lock().await;
+    let typing = TypingIndicator::start(bot, msg.chat.id);
+    let story = generate_story(use_chatgpt, use_deepseek).await;
+    drop(typing);
+    match story {
+        Ok(story) => {
+            bot.send_message(msg.chat.id, story).send_retrying().await?;
+        }
+        Err(e) if e.is::<ServiceUnavailable>() => {
+            bot.send_message(msg.chat.id, SERVICE_UNAVAILABLE_MESSAGE)
+                .send_retrying()
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("Failed to generate story: {}", e))
+                .send_retrying()
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Greets the user and, if their vocabulary is still empty, walks them through
+/// adding a first word and practicing it.
+async fn send_onboarding(bot: &Bot, msg: &Message) -> Result<()> {
+    let word_count = read_translations()?.len();
+    let text = if word_count == 0 {
+        "👋 Willkommen! Я помогу вам учить немецкий.\n\n\
+         1️⃣ Отправьте мне любое немецкое или русское слово, например «Wald» — \
+         я переведу его и сохраню в ваш словарь.\n\
+         2️⃣ Добавьте несколько слов и запустите /practice, чтобы их потренировать.\n\n\
+         Или попробуйте сразу поговорить на немецком или описать картинку:"
+            .to_string()
+    } else {
+        format!(
+            "👋 Willkommen zurück! В вашем словаре {} слов.\n\n\
+             Запустите /practice, чтобы потренироваться, или /help, чтобы увидеть все команды.",
+            word_count
+        )
+    };
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🗣 Разговор", format!("{}talk", ONBOARDING_CALLBACK_PREFIX)),
+        InlineKeyboardButton::callback(
+            "🖼 Описать картинку",
+            format!("{}pic", ONBOARDING_CALLBACK_PREFIX),
+        ),
+    ]]);
+
+    bot.send_message(msg.chat.id, text)
+        .reply_markup(keyboard)
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+/// The modes that take over a chat's plain messages until they're stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Picture,
+    Talk,
+    Practice,
+    Delete,
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Picture => "picture mode",
+            Mode::Talk => "talk mode",
+            Mode::Practice => "practice",
+            Mode::Delete => "delete mode",
+        }
+    }
+}
+
+impl Command {
+    /// The mode this command enters, if any.
+    fn mode_started(&self) -> Option<Mode> {
+        match self {
+            Command::Practice(_) | Command::Drill(_) | Command::Today => Some(Mode::Practice),
+            Command::Talk(_) => Some(Mode::Talk),
+            Command::Pic(_) => Some(Mode::Picture),
+            Command::Delete => Some(Mode::Delete),
+            Command::Start(payload) => {
+                let payload = payload.trim().to_lowercase();
+                match payload.split('_').next() {
+                    Some("practice") => Some(Mode::Practice),
+                    Some("talk") => Some(Mode::Talk),
+                    Some("pic") => Some(Mode::Picture),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The mode the chat is in, checked in the order `handle_message` routes
+/// messages.
+async fn active_mode(
+    chat_id: ChatId,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+) -> Option<Mode> {
+    if picture_sessions.lock().await.contains_key(&chat_id.0) {
+        Some(Mode::Picture)
+    } else if talk_sessions.lock().await.contains_key(&chat_id.0) {
+        Some(Mode::Talk)
+    } else if sessions.lock().await.contains_key(&chat_id.0) {
+        Some(Mode::Practice)
+    } else if delete_mode.lock().await.contains(&chat_id.0) {
+        Some(Mode::Delete)
+    } else {
+        None
+    }
+}
+
+/// Refuses to start `mode` while the chat is in a different one, since
+/// `handle_message` would route the messages to only one of them. Returns
+/// whether another mode is active.
+async fn ensure_no_other_mode(
+    bot: &Bot,
+    chat_id: ChatId,
+    mode: Mode,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+) -> Result<bool> {
+    let active = active_mode(
+        chat_id,
+        sessions,
+        talk_sessions,
+        picture_sessions,
+        delete_mode,
+    )
+    .await;
+    if active.is_none_or(|active| active == mode) {
+        return Ok(false);
+    }
+    bot.send_message(chat_id, "Сначала завершите текущий режим (/cancel).")
+        .send_retrying()
+        .await?;
+    Ok(true)
+}
+
+/// Stops whatever modes the chat is in, with each mode's usual goodbye.
+async fn cancel_modes(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+) -> Result<()> {
+    let mut cancelled = Vec::new();
+    while let Some(mode) = active_mode(
+        msg.chat.id,
+        sessions,
+        talk_sessions,
+        picture_sessions,
+        delete_mode,
+    )
+    .await
+    {
+        match mode {
+            Mode::Picture => stop_picture_session(bot, msg, picture_sessions).await?,
+            Mode::Talk => stop_talk_session(bot, msg, talk_sessions).await?,
+            Mode::Practice => stop_practice_session(bot, msg, sessions).await?,
+            Mode::Delete => {
+                delete_mode.lock().await.remove(&msg.chat.id.0);
+            }
+        }
+        cancelled.push(mode.name());
+    }
+
+    let reply = if cancelled.is_empty() {
+        "Nothing to cancel.".to_string()
+    } else {
+        format!("Cancelled {}.", cancelled.join(", "))
+    };
+    bot.send_message(msg.chat.id, reply).send_retrying().await?;
+    Ok(())
+}
+
+/// Destructive commands that wait for an inline-button confirmation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PendingAction {
+    Clear,
+    Exit,
+}
+
+impl PendingAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PendingAction::Clear => "clear",
+            PendingAction::Exit => "exit",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "clear" => Some(PendingAction::Clear),
+            "exit" => Some(PendingAction::Exit),
+            _ => None,
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self {
+            PendingAction::Clear => "⚠️ Delete all translations from the database?",
+            PendingAction::Exit => "⚠️ Shut down the bot?",
+        }
+    }
+}
+
+async fn request_confirmation(
+    bot: &Bot,
+    msg: &Message,
+    pending_actions: &PendingActions,
+    action: PendingAction,
+) -> Result<()> {
+    pending_actions.lock().await.insert(msg.chat.id.0, action);
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Подтвердить", format!("confirm:{}", action.as_str())),
+        InlineKeyboardButton::callback("Отмена", format!("cancel:{}", action.as_str())),
+    ]]);
+    bot.send_message(msg.chat.id, action.prompt())
+        .reply_markup(keyboard)
+        .send_retrying()
+        .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_callback_query(
+    bot: &Bot,
+    query: &CallbackQuery,
+    shutdown: &broadcast::Sender<()>,
+    pending_actions: &PendingActions,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+    previews: &PendingPreviews,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    bot.answer_callback_query(query.id.clone()).await?;
+
+    let user_id = i64::try_from(query.from.id.0).unwrap_or(0);
+    if !is_user_id_authorized(user_id) {
+        return Ok(());
+    }
+
+    let (Some(data), Some(message)) = (query.data.as_deref(), query.message.as_ref()) else {
+        return Ok(());
+    };
+
+    if let Some(word) = data.strip_prefix(TTS_CALLBACK_PREFIX) {
+        return send_card_audio(bot, message.chat.id, word).await;
+    }
+
+    if let Some(word) = data.strip_prefix(EXAMPLES_CALLBACK_PREFIX) {
+        let use_chatgpt = *use_chatgpt.lock().await;
+        let use_deepseek = *use_deepseek.lock().await;
+        return send_new_examples(bot, message.chat.id, word, use_chatgpt, use_deepseek).await;
+    }
+
+    if let Some(choice) = data.strip_prefix(CHOICE_CALLBACK_PREFIX) {
+        return check_choice_answer(bot, message, choice, sessions).await;
+    }
+
+    if let Some(word) = data.strip_prefix(LEECH_CALLBACK_PREFIX) {
+        bot.edit_message_reply_markup(message.chat.id, message.id)
+            .send_retrying()
+            .await?;
+        let reply = if set_leech(word, false)? {
+            format!("✅ '{}' снова в практике.", word)
+        } else {
+            "Word not found in database.".to_string()
+        };
+        bot.send_message(message.chat.id, reply)
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(word) = data.strip_prefix(LEARN_CALLBACK_PREFIX) {
+        let use_chatgpt = *use_chatgpt.lock().await;
+        let use_deepseek = *use_deepseek.lock().await;
+        return learn_word(bot, message.chat.id, word, use_chatgpt, use_deepseek).await;
+    }
+
+    if let Some(decision) = data.strip_prefix(PREVIEW_CALLBACK_PREFIX) {
+        return confirm_preview(bot, message, decision, previews).await;
+    }
+
+    if data == NEVER_PRACTICED_CALLBACK {
+        if ensure_no_other_mode(
+            bot,
+            message.chat.id,
+            Mode::Practice,
+            sessions,
+            talk_sessions,
+            picture_sessions,
+            delete_mode,
+        )
+        .await?
+        {
+            return Ok(());
+        }
+        let options = PracticeOptions {
+            never_practiced: true,
+            ..PracticeOptions::default()
+        };
+        return start_practice_session(bot, message, sessions, options).await;
+    }
+
+    if let Some(mode) = data.strip_prefix(ONBOARDING_CALLBACK_PREFIX) {
+        let started = match mode {
+            "talk" => Mode::Talk,
+            "pic" => Mode::Picture,
+            _ => return Ok(()),
+        };
+        if ensure_no_other_mode(
+            bot,
+            message.chat.id,
+            started,
+            sessions,
+            talk_sessions,
+            picture_sessions,
+            delete_mode,
+        )
+        .await?
+        {
+            return Ok(());
+        }
+        return match mode {
+            "talk" => start_talk_session(bot, message, talk_sessions, false).await,
+            "pic" => start_picture_session(bot, message, picture_sessions, false).await,
+            _ => Ok(()),
+        };
+    }
+
+    let Some((decision, action)) = data.split_once(':') else {
+        return Ok(());
+    };
+    let Some(action) = PendingAction::parse(action) else {
+        return Ok(());
+    };
+
+    let chat_id = message.chat.id;
+    // Only act on the confirmation that is still pending, so stale buttons do nothing
+    let pending = pending_actions.lock().await.remove(&chat_id.0);
+    if pending != Some(action) {
+        bot.edit_message_text(chat_id, message.id, "This confirmation has expired.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
+
+    if decision != "confirm" {
+        bot.edit_message_text(chat_id, message.id, "Cancelled.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
+
+    match action {
+        PendingAction::Clear => {
+            clear_translations()?;
+            bot.edit_message_text(
+                chat_id,
+                message.id,
+                "Translations database has been cleared.",
+            )
+            .send_retrying()
+            .await?;
+        }
+        PendingAction::Exit => {
+            bot.edit_message_text(chat_id, message.id, SHUTDOWN_MESSAGE)
+                .send_retrying()
+                .await?;
+            shutdown.send(()).ok();
+        }
+    }
+
+    Ok(())
 }
 
 fn get_allowed_users() -> Vec<i64> {
@@ -94,12 +924,16 @@ fn get_allowed_users() -> Vec<i64> {
 }
 
 async fn is_user_authorized(msg: &Message) -> bool {
-    let allowed_users = get_allowed_users();
     let user_id = msg
         .clone()
         .from()
         .map(|u| i64::try_from(u.id.0).unwrap_or(0))
         .unwrap_or(0);
+    is_user_id_authorized(user_id)
+}
+
+fn is_user_id_authorized(user_id: i64) -> bool {
+    let allowed_users = get_allowed_users();
     let is_authorized = allowed_users.contains(&user_id);
     log::info!(
         "Authorization check - User ID: {}, Authorized: {}, Allowed users: {:?}",
@@ -110,15 +944,16 @@ async fn is_user_authorized(msg: &Message) -> bool {
     is_authorized
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_command(
     bot: &Bot,
     msg: &Message,
     cmd: Command,
-    shutdown: &broadcast::Sender<()>,
     sessions: &PracticeSessions,
     talk_sessions: &TalkSessions,
     picture_sessions: &PictureSessions,
     delete_mode: &DeleteMode,
+    pending_actions: &PendingActions,
     use_chatgpt: &Arc<Mutex<bool>>,
     use_deepseek: &Arc<Mutex<bool>>,
 ) -> Result<()> {
@@ -127,46 +962,173 @@ pub async fn handle_command(
             msg.chat.id,
             "Sorry, you are not authorized to use this bot.",
         )
+        .send_retrying()
         .await?;
         return Ok(());
     }
-    match cmd {
-        Command::Practice => {
-            start_practice_session(bot, msg, sessions).await?;
-        }
-        Command::Stop => {
-            stop_practice_session(bot, msg, sessions).await?;
-        }
-        Command::Start => {
-            bot.send_message(msg.chat.id, HELP_MESSAGE).await?;
+    if let Some(mode) = cmd.mode_started() {
+        let other_mode_active = ensure_no_other_mode(
+            bot,
+            msg.chat.id,
+            mode,
+            sessions,
+            talk_sessions,
+            picture_sessions,
+            delete_mode,
+        )
+        .await?;
+        if other_mode_active {
+            return Ok(());
         }
-        Command::Help => {
-            bot.send_message(msg.chat.id, HELP_MESSAGE).await?;
+    }
+    match cmd {
+        Command::Practice(args) => {
+            start_practice(bot, msg, sessions, &args).await?;
+        }
+        Command::Today => {
+            let set = todays_set(msg.chat.id.0, &read_translations()?)?;
+            let remaining = set.remaining();
+            if set.words.is_empty() {
+                bot.send_message(msg.chat.id, "No words available for practice!")
+                    .send_retrying()
+                    .await?;
+            } else if remaining.is_empty() {
+                bot.send_message(
+                    msg.chat.id,
+                    "🎉 Все слова на сегодня пройдены! Завтра будет новый набор.",
+                )
+                .send_retrying()
+                .await?;
+            } else {
+                let options = PracticeOptions {
+                    daily_words: Some(remaining),
+                    ..PracticeOptions::default()
+                };
+                start_practice_session(bot, msg, sessions, options).await?;
+            }
+        }
+        Command::Drill(word) => {
+            let translations = read_translations()?;
+            match find_translation(word.trim(), &translations) {
+                Some(translation) => {
+                    let options = PracticeOptions {
+                        drill_word: Some(translation.original.clone()),
+                        ..PracticeOptions::default()
+                    };
+                    start_practice_session(bot, msg, sessions, options).await?;
+                }
+                None => {
+                    bot.send_message(msg.chat.id, "слово не найдено")
+                        .send_retrying()
+                        .await?;
+                }
+            }
+        }
+        Command::Tag(args) => {
+            let mut parts = args.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(word), Some(tag), None) if !normalize_tag(tag).is_empty() => {
+                    if tag_translation(word, tag)? {
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("🏷 Tagged '{}' with #{}", word, normalize_tag(tag)),
+                        )
+                        .send_retrying()
+                        .await?;
+                    } else {
+                        bot.send_message(msg.chat.id, "Word not found in database.")
+                            .send_retrying()
+                            .await?;
+                    }
+                }
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /tag <word> <tag>")
+                        .send_retrying()
+                        .await?;
+                }
+            }
+        }
+        Command::Forget(word) => {
+            archive_word(bot, msg, word.trim(), true).await?;
+        }
+        Command::Unforget(word) => {
+            archive_word(bot, msg, word.trim(), false).await?;
+        }
+        Command::NeverPracticed => {
+            send_never_practiced(bot, msg).await?;
+        }
+        Command::Leeches => {
+            send_leeches(bot, msg).await?;
+        }
+        Command::Skip => {
+            skip_practice_question(bot, msg, sessions).await?;
+        }
+        Command::Stop => {
+            stop_practice_session(bot, msg, sessions).await?;
+        }
+        Command::Start(payload) => {
+            // Deep link payloads only allow [A-Za-z0-9_-], so "practice_de"
+            // stands for "/practice de"
+            let payload = payload.trim().to_lowercase();
+            let (mode, args) = payload.split_once('_').unwrap_or((&payload, ""));
+            match mode {
+                "practice" => start_practice(bot, msg, sessions, &args.replace('_', " ")).await?,
+                "talk" => start_talk_session(bot, msg, talk_sessions, args == "quiz").await?,
+                "pic" => start_picture_session(bot, msg, picture_sessions, false).await?,
+                "story" => send_story(bot, msg, use_chatgpt, use_deepseek).await?,
+                _ => send_onboarding(bot, msg).await?,
+            }
+        }
+        Command::Help => {
+            bot.send_message(msg.chat.id, help_message())
+                .send_retrying()
+                .await?;
         }
         Command::Exit => {
-            bot.send_message(msg.chat.id, SHUTDOWN_MESSAGE).await?;
-            shutdown.send(()).ok();
+            request_confirmation(bot, msg, pending_actions, PendingAction::Exit).await?;
         }
         Command::Export => {
+            // Serialized here rather than sending the file so every storage backend exports JSON
             let translations = read_translations()?;
-            let file_path = get_storage_path();
+            let caption = export_caption(&translations);
+            let input_file = InputFile::memory(export_translations(translations)?)
+                .file_name("translations_storage.json");
+            bot.send_document(msg.chat.id, input_file)
+                .caption(caption)
+                .send_retrying()
+                .await?;
+        }
+        Command::Flashcards => {
+            let translations = read_translations()?;
+            if translations.is_empty() {
+                bot.send_message(msg.chat.id, "The database is empty, nothing to print.")
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            }
 
-            let input_file = InputFile::file(file_path);
+            let html = render_flashcards_html(&translations);
+            let input_file = InputFile::memory(html.into_bytes()).file_name("flashcards.html");
             bot.send_document(msg.chat.id, input_file)
                 .caption(format!(
-                    "Translation database with {} entries",
+                    "Printable flashcards for {} words",
                     translations.len()
                 ))
+                .send_retrying()
                 .await?;
         }
         Command::Clear => {
-            clear_translations()?;
-            bot.send_message(msg.chat.id, "Translations database has been cleared.")
-                .await?;
+            request_confirmation(bot, msg, pending_actions, PendingAction::Clear).await?;
         }
         Command::Import => {
-            bot.send_message(msg.chat.id, "Please send me a JSON file with translations.")
-                .await?;
+            bot.send_message(
+                msg.chat.id,
+                "Please send me a JSON export or a CSV/Anki text file with translations.\n\
+                 A JSON export replaces your words and a CSV/Anki file is added to them; \
+                 caption the file 'merge' or 'replace' to choose.",
+            )
+            .send_retrying()
+            .await?;
         }
         Command::Delete => {
             let mut delete_mode = delete_mode.lock().await;
@@ -175,85 +1137,499 @@ pub async fn handle_command(
                        msg.chat.id,
                        "Delete mode activated. Send any word to delete it from the database. Use /stopdelete to exit delete mode.",
                    )
-                   .await?;
+                   .send_retrying().await?;
+        }
+        Command::Cancel => {
+            cancel_modes(
+                bot,
+                msg,
+                sessions,
+                talk_sessions,
+                picture_sessions,
+                delete_mode,
+            )
+            .await?;
         }
         Command::StopDelete => {
             let mut delete_mode = delete_mode.lock().await;
             delete_mode.remove(&msg.chat.id.0);
             bot.send_message(msg.chat.id, "Delete mode deactivated.")
+                .send_retrying()
+                .await?;
+        }
+        Command::Stats(word) if word.trim().is_empty() => {
+            let min_attempts = read_user_config(msg.chat.id.0)?.min_attempts_for_mastery;
+            let response = format!(
+                "{}\n\n{}",
+                format_activity(msg.chat.id.0)?,
+                format_progress(&read_translations()?, min_attempts)
+            );
+            bot.send_message(msg.chat.id, response)
+                .send_retrying()
                 .await?;
         }
         Command::Stats(word) => {
-            if let Some(translation) = find_translation(&word, &read_translations()?) {
+            if let Some(translation) = get_translation(&word)? {
                 let total = translation.correct_answers + translation.wrong_answers;
-                let accuracy = if total > 0 {
-                    (translation.correct_answers as f64 / total as f64) * 100.0
-                } else {
-                    0.0
+                let min_attempts = read_user_config(msg.chat.id.0)?.min_attempts_for_mastery;
+                let accuracy = match mastery(&translation, min_attempts) {
+                    Mastery::NotEnoughData => format!(
+                        "недостаточно данных ({} of {} attempts needed)",
+                        total, min_attempts
+                    ),
+                    level => format!(
+                        "{:.1}%{}",
+                        (translation.correct_answers as f64 / total as f64) * 100.0,
+                        if matches!(level, Mastery::Mastered) {
+                            " (mastered)"
+                        } else {
+                            ""
+                        }
+                    ),
                 };
 
+                let last_practiced = match translation.last_practiced {
+                    Some(at) => match (unix_now() - at) / 86400 {
+                        0 => "сегодня".to_string(),
+                        days => days_ago(days),
+                    },
+                    None => "ещё не было".to_string(),
+                };
                 let stats_message = format!(
-                    "📊 Statistics for '{}'\n\nTotal attempts: {}\nCorrect: {}\nWrong: {}\nAccuracy: {:.1}%",
-                    word, total, translation.correct_answers, translation.wrong_answers, accuracy
+                    "📊 Statistics for '{}'\n\nTotal attempts: {}\nCorrect: {}\nWrong: {}\nAccuracy: {}\nПоследняя практика: {}",
+                    word,
+                    total,
+                    translation.correct_answers,
+                    translation.wrong_answers,
+                    accuracy,
+                    last_practiced
                 );
 
-                bot.send_message(msg.chat.id, stats_message).await?;
+                bot.send_message(msg.chat.id, stats_message)
+                    .send_retrying()
+                    .await?;
             } else {
                 bot.send_message(msg.chat.id, "Word not found in database.")
+                    .send_retrying()
                     .await?;
             }
         }
         Command::Story => {
-            bot.send_message(msg.chat.id, "Generating a story...")
-                .await?;
-            let use_chatgpt = *use_chatgpt.lock().await;
-            let use_deepseek = *use_deepseek.lock().await;
-            match generate_story(use_chatgpt, use_deepseek).await {
-                Ok(story) => {
-                    bot.send_message(msg.chat.id, story).await?;
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("Failed to generate story: {}", e))
-                        .await?;
-                }
-            }
+            send_story(bot, msg, use_chatgpt, use_deepseek).await?;
         }
         Command::UseChatGPT => {
             let mut use_chatgpt = use_chatgpt.lock().await;
             *use_chatgpt = true;
             *use_deepseek.lock().await = false;
             bot.send_message(msg.chat.id, "Switched to ChatGPT.")
+                .send_retrying()
                 .await?;
         }
         Command::UseClaude => {
             let mut use_chatgpt = use_chatgpt.lock().await;
             *use_chatgpt = false;
             *use_deepseek.lock().await = false;
-            bot.send_message(msg.chat.id, "Switched to Claude.").await?;
+            bot.send_message(msg.chat.id, "Switched to Claude.")
+                .send_retrying()
+                .await?;
         }
         Command::UseDeepSeek => {
             let mut use_deepseek = use_deepseek.lock().await;
             *use_deepseek = true;
             *use_chatgpt.lock().await = false;
             bot.send_message(msg.chat.id, "Switched to DeepSeek.")
+                .send_retrying()
                 .await?;
         }
-        Command::Talk => {
-            start_talk_session(bot, msg, talk_sessions).await?;
+        Command::DailyStory(arg) => {
+            let enabled = match arg.trim().to_lowercase().as_str() {
+                "on" => true,
+                "off" => false,
+                _ => {
+                    let subscribed = read_daily_story_chats()?.contains(&msg.chat.id.0);
+                    bot.send_message(
+                        msg.chat.id,
+                        format!(
+                            "Daily story is {}. Use /dailystory on or /dailystory off.",
+                            if subscribed { "on" } else { "off" }
+                        ),
+                    )
+                    .send_retrying()
+                    .await?;
+                    return Ok(());
+                }
+            };
+
+            set_daily_story(msg.chat.id.0, enabled)?;
+            let message = if enabled {
+                let (hour, minute) = get_send_time();
+                format!(
+                    "☀️ Daily story enabled. It will arrive every day at {:02}:{:02} UTC.",
+                    hour, minute
+                )
+            } else {
+                "Daily story disabled.".to_string()
+            };
+            bot.send_message(msg.chat.id, message)
+                .send_retrying()
+                .await?;
         }
+        Command::Talk(mode) => match mode.trim().to_lowercase().as_str() {
+            "" => start_talk_session(bot, msg, talk_sessions, false).await?,
+            "quiz" => start_talk_session(bot, msg, talk_sessions, true).await?,
+            _ => {
+                bot.send_message(msg.chat.id, "Usage: /talk [quiz]")
+                    .send_retrying()
+                    .await?;
+            }
+        },
         Command::StopTalk => {
             stop_talk_session(bot, msg, talk_sessions).await?;
         }
-        Command::Pic => {
-            start_picture_session(bot, msg, picture_sessions).await?;
-        }
+        Command::Pic(mode) => match mode.trim().to_lowercase().as_str() {
+            "" => start_picture_session(bot, msg, picture_sessions, false).await?,
+            "curated" => start_picture_session(bot, msg, picture_sessions, true).await?,
+            _ => {
+                bot.send_message(msg.chat.id, "Usage: /pic [curated]")
+                    .send_retrying()
+                    .await?;
+            }
+        },
         Command::Stoppic => {
             stop_picture_session(bot, msg, picture_sessions).await?;
         }
+        Command::Examples(arg) => {
+            let count = arg.trim();
+            if count.is_empty() {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Currently requesting {} example sentences. Use /examples <n> to change it ({}-{}).",
+                        get_example_count(),
+                        MIN_EXAMPLE_COUNT,
+                        MAX_EXAMPLE_COUNT
+                    ),
+                )
+                .send_retrying().await?;
+            } else if !count.chars().all(|c| c.is_ascii_digit()) {
+                send_word_examples(bot, msg, count).await?;
+            } else {
+                let result = count
+                    .parse::<usize>()
+                    .map_err(|e| e.into())
+                    .and_then(set_example_count);
+                match result {
+                    Ok(()) => {
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("Now requesting {} example sentences.", get_example_count()),
+                        )
+                        .send_retrying()
+                        .await?;
+                    }
+                    Err(e) => {
+                        bot.send_message(
+                            msg.chat.id,
+                            format!(
+                                "❌ Invalid example count '{}': {}. Use a number from {} to {}.",
+                                count, e, MIN_EXAMPLE_COUNT, MAX_EXAMPLE_COUNT
+                            ),
+                        )
+                        .send_retrying()
+                        .await?;
+                    }
+                }
+            }
+        }
+        Command::Enrich(word) => {
+            let word = word.trim();
+            let translations = read_translations()?;
+            let Some(mut existing) = find_translation(word, &translations).cloned() else {
+                bot.send_message(msg.chat.id, "Word not found in database.")
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            };
+
+            let use_chatgpt = *use_chatgpt.lock().await;
+            let use_deepseek = *use_deepseek.lock().await;
+            let claude_response = translate_text(
+                &existing.original,
+                Some(msg.chat.id.0),
+                use_chatgpt,
+                use_deepseek,
+            )
+            .await?;
+            let fresh = match parse_word_reply(
+                &existing.original,
+                &claude_response,
+                Some(msg.chat.id.0),
+                use_chatgpt,
+                use_deepseek,
+            )
+            .await?
+            {
+                Ok(fresh) => fresh,
+                Err(e) => {
+                    log::warn!("Not enriching '{}': {}", existing.original, e);
+                    bot.send_message(msg.chat.id, OFF_FORMAT_MESSAGE)
+                        .send_retrying()
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            let added = merge_translation(&mut existing, fresh);
+            if added.is_empty() {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Nothing new to add for '{}'.", existing.original),
+                )
+                .send_retrying()
+                .await?;
+                return Ok(());
+            }
+
+            add_translation(existing.clone())?;
+
+            let mut response = format!("✨ Added to '{}':\n", existing.original);
+            for item in &added {
+                response.push_str(&format!("• {}\n", item));
+            }
+            response.push('\n');
+            response.push_str(&format_translation_response(&existing));
+            bot.send_message(msg.chat.id, response)
+                .send_retrying()
+                .await?;
+        }
+        Command::Settings(args) => {
+            let chat_id = msg.chat.id.0;
+            let mut config = read_user_config(chat_id)?;
+            for update in args.split_whitespace() {
+                let result = update
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected key=value, got '{}'", update))
+                    .and_then(|(key, value)| config.set(key, value));
+                if let Err(e) = result {
+                    bot.send_message(msg.chat.id, format!("❌ {}", e))
+                        .send_retrying()
+                        .await?;
+                    return Ok(());
+                }
+            }
+            if !args.trim().is_empty() {
+                write_user_config(chat_id, &config)?;
+            }
+            bot.send_message(msg.chat.id, config.describe())
+                .send_retrying()
+                .await?;
+        }
+        Command::SetPrompt(args) => {
+            let args = args.trim();
+            let (kind, prompt) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let reply = match PromptKind::parse(kind) {
+                Some(kind) if !prompt.trim().is_empty() => {
+                    let mut config = read_user_config(msg.chat.id.0)?;
+                    config.prompts.insert(kind, prompt.trim().to_string());
+                    write_user_config(msg.chat.id.0, &config)?;
+                    format!(
+                        "✅ Custom {} prompt saved. Use /resetprompt {} to restore the default.",
+                        kind, kind
+                    )
+                }
+                _ => format!(
+                    "Usage: /setprompt <kind> <text>\nKinds: {}",
+                    PromptKind::NAMES
+                ),
+            };
+            bot.send_message(msg.chat.id, reply).send_retrying().await?;
+        }
+        Command::ResetPrompt(kind) => {
+            let kind = kind.trim();
+            let mut config = read_user_config(msg.chat.id.0)?;
+            let reply = if kind.is_empty() {
+                config.prompts.clear();
+                write_user_config(msg.chat.id.0, &config)?;
+                "✅ All prompts restored to the defaults.".to_string()
+            } else if let Some(kind) = PromptKind::parse(kind) {
+                config.prompts.remove(&kind);
+                write_user_config(msg.chat.id.0, &config)?;
+                format!("✅ Default {} prompt restored.", kind)
+            } else {
+                format!("Usage: /resetprompt [kind]\nKinds: {}", PromptKind::NAMES)
+            };
+            bot.send_message(msg.chat.id, reply).send_retrying().await?;
+        }
+        Command::TargetLang(code) => {
+            let code = code.trim();
+            let mut config = read_user_config(msg.chat.id.0)?;
+            let reply = if code.is_empty() {
+                format!(
+                    "Translating between German and {}. Use /targetlang <code> to change it ({}).",
+                    config.target_language().name(),
+                    TargetLanguage::CODES
+                )
+            } else if let Some(language) = TargetLanguage::parse(code) {
+                config.target_language = Some(language);
+                write_user_config(msg.chat.id.0, &config)?;
+                format!(
+                    "Now translating between German and {} ({}).",
+                    language.name(),
+                    language.code()
+                )
+            } else {
+                format!(
+                    "❌ Unknown language '{}'. Use one of: {}",
+                    code,
+                    TargetLanguage::CODES
+                )
+            };
+            bot.send_message(msg.chat.id, reply).send_retrying().await?;
+        }
+        Command::Version => {
+            bot.send_message(
+                msg.chat.id,
+                format!("zungenrede-bot v{}", env!("CARGO_PKG_VERSION")),
+            )
+            .send_retrying()
+            .await?;
+        }
+        Command::Audit => {
+            let report = audit_translations(&read_translations()?);
+            bot.send_message(msg.chat.id, report)
+                .send_retrying()
+                .await?;
+        }
+        Command::Health => {
+            let uptime = STARTED_AT.get().map(Instant::elapsed).unwrap_or_default();
+            let uptime_secs = uptime.as_secs();
+            let mut response = format!(
+                "🩺 zungenrede-bot v{}\n\nUptime: {}d {}h {}m\nActive sessions: {} practice, {} talk, {} picture\n\nAPI keys:",
+                env!("CARGO_PKG_VERSION"),
+                uptime_secs / 86400,
+                uptime_secs % 86400 / 3600,
+                uptime_secs % 3600 / 60,
+                sessions.lock().await.len(),
+                talk_sessions.lock().await.len(),
+                picture_sessions.lock().await.len(),
+            );
+            for key in HEALTH_API_KEYS {
+                let present = env::var(key).is_ok_and(|value| !value.trim().is_empty());
+                response.push_str(&format!("\n{} {}", if present { "✅" } else { "❌" }, key));
+            }
+            bot.send_message(msg.chat.id, response)
+                .send_retrying()
+                .await?;
+        }
+        Command::Usage => {
+            let usage = get_usage();
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "📈 Claude usage since start\n\nRequests: {}\nInput tokens: {}\nOutput tokens: {}\nTotal tokens: {}\nEstimated cost: ${:.4}",
+                    usage.requests,
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    usage.total_tokens(),
+                    usage.estimated_cost()
+                ),
+            )
+            .send_retrying().await?;
+        }
+        Command::Sentences => {
+            if !is_sentence_log_enabled() {
+                bot.send_message(
+                    msg.chat.id,
+                    "Sentence log is disabled. Set LOG_SENTENCES=true to enable it.",
+                )
+                .send_retrying()
+                .await?;
+                return Ok(());
+            }
+
+            let sentences = read_logged_sentences()?;
+            if sentences.is_empty() {
+                bot.send_message(msg.chat.id, "No sentences logged yet.")
+                    .send_retrying()
+                    .await?;
+            } else {
+                let mut response = String::from("📝 Recent sentences:\n\n");
+                for sentence in sentences.iter().rev().take(RECENT_SENTENCES_COUNT) {
+                    response.push_str(&format!(
+                        "{} ➜ {}\n\n",
+                        sentence.original, sentence.translation
+                    ));
+                }
+                bot.send_message(msg.chat.id, response.trim_end())
+                    .send_retrying()
+                    .await?;
+            }
+        }
+        Command::Search(query) => {
+            let query = query.trim();
+            if query.is_empty() {
+                bot.send_message(msg.chat.id, "Usage: /search <text>")
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            }
+
+            let translations = read_translations()?;
+            let words = search_translations(query, &translations);
+            let sentences = if is_sentence_log_enabled() {
+                search_logged_sentences(query)?
+            } else {
+                Vec::new()
+            };
+
+            if words.is_empty() && sentences.is_empty() {
+                bot.send_message(msg.chat.id, format!("Nothing found for '{}'.", query))
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            }
+
+            let mut response = format!("🔍 Results for '{}':\n", query);
+            if !words.is_empty() {
+                response.push_str("\nWords:\n");
+                for t in words.iter().take(MAX_SEARCH_RESULTS) {
+                    response.push_str(&format!("• {} — {}\n", t.original, t.translation));
+                }
+            }
+            if !sentences.is_empty() {
+                response.push_str("\nSentences:\n");
+                for s in sentences.iter().rev().take(MAX_SEARCH_RESULTS) {
+                    response.push_str(&format!("• {} ➜ {}\n", s.original, s.translation));
+                }
+            }
+            bot.send_message(msg.chat.id, response)
+                .send_retrying()
+                .await?;
+        }
+        Command::Random => {
+            let translations = read_translations()?;
+            match get_random_translation(&translations) {
+                Some(translation) => {
+                    let response = format_translation_response(translation);
+                    bot.send_message(msg.chat.id, response)
+                        .send_retrying()
+                        .await?;
+                }
+                None => {
+                    bot.send_message(
+                        msg.chat.id,
+                        "The database is empty. Send me a word to add your first translation!",
+                    )
+                    .send_retrying()
+                    .await?;
+                }
+            }
+        }
     }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_message(
     bot: &Bot,
     msg: &Message,
@@ -261,6 +1637,53 @@ pub async fn handle_message(
     talk_sessions: &TalkSessions,
     picture_sessions: &PictureSessions,
     delete_mode: &DeleteMode,
+    replies: &ReplyMap,
+    previews: &PendingPreviews,
+    in_flight: &InFlightWords,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    if !is_user_authorized(msg).await {
+        bot.send_message(
+            msg.chat.id,
+            "Sorry, you are not authorized to use this bot.",
+        )
+        .send_retrying()
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(text) = msg.text().or(msg.caption()) {
+        handle_text(
+            bot,
+            msg,
+            text,
+            sessions,
+            talk_sessions,
+            picture_sessions,
+            delete_mode,
+            replies,
+            previews,
+            in_flight,
+            use_chatgpt,
+            use_deepseek,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_voice(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+    replies: &ReplyMap,
+    previews: &PendingPreviews,
+    in_flight: &InFlightWords,
     use_chatgpt: &Arc<Mutex<bool>>,
     use_deepseek: &Arc<Mutex<bool>>,
 ) -> Result<()> {
@@ -269,10 +1692,76 @@ pub async fn handle_message(
             msg.chat.id,
             "Sorry, you are not authorized to use this bot.",
         )
+        .send_retrying()
         .await?;
         return Ok(());
     }
 
+    if let Some(voice) = msg.voice() {
+        let file = bot.get_file(&voice.file.id).await?;
+        let mut bytes = Vec::new();
+        bot.download_file(&file.path, &mut bytes).await?;
+
+        match transcribe_voice(bytes).await {
+            Ok(text) if !text.is_empty() => {
+                bot.send_message(msg.chat.id, format!("🎤 {}", text))
+                    .send_retrying()
+                    .await?;
+                handle_text(
+                    bot,
+                    msg,
+                    &text,
+                    sessions,
+                    talk_sessions,
+                    picture_sessions,
+                    delete_mode,
+                    replies,
+                    previews,
+                    in_flight,
+                    use_chatgpt,
+                    use_deepseek,
+                )
+                .await?;
+            }
+            Ok(_) => {
+                bot.send_message(msg.chat.id, "❌ Could not recognize any speech.")
+                    .send_retrying()
+                    .await?;
+            }
+            Err(e) => {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("❌ Error transcribing voice message: {}", e),
+                )
+                .send_retrying()
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Routes user text (typed or transcribed from voice) to the active mode or
+/// to a translation lookup.
+#[allow(clippy::too_many_arguments)]
+async fn handle_text(
+    bot: &Bot,
+    msg: &Message,
+    text: &str,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    delete_mode: &DeleteMode,
+    replies: &ReplyMap,
+    previews: &PendingPreviews,
+    in_flight: &InFlightWords,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    if reject_long_input(bot, msg, text).await? {
+        return Ok(());
+    }
+
     let chat_id = msg.chat.id;
 
     // Check if user is in picture mode
@@ -280,7 +1769,7 @@ pub async fn handle_message(
         let picture_lock = picture_sessions.lock().await;
         if picture_lock.contains_key(&chat_id.0) {
             drop(picture_lock);
-            handle_picture_message(bot, msg, picture_sessions).await?;
+            handle_picture_message(bot, msg, text, picture_sessions).await?;
             return Ok(());
         }
     }
@@ -292,120 +1781,810 @@ pub async fn handle_message(
         drop(talk_lock);
 
         if is_talking {
-            handle_talk_message(bot, msg, talk_sessions, use_chatgpt).await?;
+            // The model can't see the image, but should know there was one
+            let text = if msg.photo().is_some() {
+                format!("(Foto) {}", text)
+            } else {
+                text.to_string()
+            };
+            handle_talk_message(bot, msg, &text, talk_sessions, use_chatgpt).await?;
             return Ok(());
         }
     }
 
-    if let Some(text) = msg.text() {
-        let is_practicing = sessions.lock().await.contains_key(&chat_id.0);
-        let is_deleting = delete_mode.lock().await.contains(&chat_id.0);
+    let is_practicing = sessions.lock().await.contains_key(&chat_id.0);
+    let is_deleting = delete_mode.lock().await.contains(&chat_id.0);
 
-        if is_practicing {
-            check_practice_answer(bot, msg, sessions).await?;
-        } else if is_deleting {
-            match delete_translation(text) {
-                Ok(true) => {
-                    bot.send_message(msg.chat.id, "✅ Word deleted successfully.")
-                        .await?;
-                }
-                Ok(false) => {
-                    bot.send_message(msg.chat.id, "❌ Word not found.").await?;
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("❌ Error: {}", e))
-                        .await?;
+    if is_practicing {
+        let use_chatgpt = *use_chatgpt.lock().await;
+        let use_deepseek = *use_deepseek.lock().await;
+        check_practice_answer(bot, msg, text, sessions, use_chatgpt, use_deepseek).await?;
+    } else if is_deleting {
+        match delete_translation(text) {
+            Ok(true) => {
+                bot.send_message(msg.chat.id, "✅ Word deleted successfully.")
+                    .send_retrying()
+                    .await?;
+            }
+            Ok(false) => {
+                bot.send_message(msg.chat.id, "❌ Word not found.")
+                    .send_retrying()
+                    .await?;
+            }
+            Err(e) => {
+                bot.send_message(msg.chat.id, format!("❌ Error: {}", e))
+                    .send_retrying()
+                    .await?;
+            }
+        }
+    } else {
+        let use_chatgpt = *use_chatgpt.lock().await;
+        let use_deepseek = *use_deepseek.lock().await;
+        let typing = TypingIndicator::start(bot, msg.chat.id);
+        let reply = lookup_text(msg, text, previews, in_flight, use_chatgpt, use_deepseek).await;
+        drop(typing);
+        let reply = match reply {
+            Err(e) if e.is::<ServiceUnavailable>() => {
+                bot.send_message(msg.chat.id, SERVICE_UNAVAILABLE_MESSAGE)
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            }
+            result => result?,
+        };
+        let reply = reply.send(bot, msg.chat.id).await?;
+        track_reply(replies, msg, &reply).await;
+    }
+    Ok(())
+}
+
+/// Inline 🔊 button for a word card. Telegram limits callback data to 64
+/// bytes, so very long entries get no button.
+fn card_keyboard(word: &str) -> Option<InlineKeyboardMarkup> {
+    let buttons: Vec<InlineKeyboardButton> = [
+        ("🔊 Озвучить", TTS_CALLBACK_PREFIX),
+        ("🔁 Новые примеры", EXAMPLES_CALLBACK_PREFIX),
+    ]
+    .into_iter()
+    .map(|(label, prefix)| (label, format!("{}{}", prefix, word)))
+    .filter(|(_, data)| data.len() <= MAX_CALLBACK_DATA_LEN)
+    .map(|(label, data)| InlineKeyboardButton::callback(label, data))
+    .collect();
+    (!buttons.is_empty()).then(|| InlineKeyboardMarkup::new(vec![buttons]))
+}
+
+/// Replaces the examples of a saved word with freshly generated ones and
+/// sends the updated card.
+async fn send_new_examples(
+    bot: &Bot,
+    chat_id: ChatId,
+    word: &str,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<()> {
+    let Some(translation) = get_translation(word)? else {
+        bot.send_message(chat_id, "Word not found in database.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    };
+
+    let language = chat_config(Some(chat_id.0)).target_language();
+    let reply = match regenerate_examples(&translation, language, use_chatgpt, use_deepseek).await {
+        Ok(updated) => format_translation_response(&updated),
+        Err(e) => {
+            log::error!("Failed to regenerate examples for '{}': {}", word, e);
+            format!("❌ Could not generate new examples for '{}'.", word)
+        }
+    };
+    match card_keyboard(&translation.original) {
+        Some(keyboard) => {
+            bot.send_message(chat_id, reply)
+                .reply_markup(keyboard)
+                .send_retrying()
+                .await?
+        }
+        None => bot.send_message(chat_id, reply).send_retrying().await?,
+    };
+    Ok(())
+}
+
+/// Sends voice clips of a saved word and each of its example sentences.
+async fn send_card_audio(bot: &Bot, chat_id: ChatId, word: &str) -> Result<()> {
+    let translations = read_translations()?;
+    let Some(translation) = find_translation(word, &translations) else {
+        bot.send_message(chat_id, "Word not found in database.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    };
+
+    let headword = match translation.grammar_forms.first() {
+        Some(article) if ["der", "die", "das"].contains(&article.trim()) => {
+            format!("{} {}", article.trim(), translation.original)
+        }
+        _ => translation.original.clone(),
+    };
+    let texts =
+        std::iter::once(headword).chain(translation.examples.iter().map(|e| e.german.clone()));
+
+    for text in texts {
+        match synthesize_speech(&text).await {
+            Ok(audio) => {
+                bot.send_voice(chat_id, InputFile::memory(audio))
+                    .caption(text)
+                    .send_retrying()
+                    .await?;
+            }
+            Err(e) => {
+                bot.send_message(chat_id, format!("❌ Error generating audio: {}", e))
+                    .send_retrying()
+                    .await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn track_reply(replies: &ReplyMap, msg: &Message, reply: &Message) {
+    let mut replies = replies.lock().await;
+    if replies.len() >= MAX_TRACKED_REPLIES {
+        replies.clear();
+    }
+    replies.insert((msg.chat.id.0, msg.id), reply.id);
+}
+
+/// Re-runs the lookup for an edited message and updates the bot's original
+/// reply in place, or sends a fresh reply if the original can't be found.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_edited_message(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &PracticeSessions,
+    talk_sessions: &TalkSessions,
+    picture_sessions: &PictureSessions,
+    replies: &ReplyMap,
+    previews: &PendingPreviews,
+    in_flight: &InFlightWords,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    if !is_user_authorized(msg).await {
+        return Ok(());
+    }
+
+    let Some(text) = msg.text() else {
+        return Ok(());
+    };
+    if reject_long_input(bot, msg, text).await? {
+        return Ok(());
+    }
+
+    // Edits to practice answers or conversation turns are ignored, the
+    // session has already moved on
+    let chat_id = msg.chat.id.0;
+    if sessions.lock().await.contains_key(&chat_id)
+        || talk_sessions.lock().await.contains_key(&chat_id)
+        || picture_sessions.lock().await.contains_key(&chat_id)
+    {
+        return Ok(());
+    }
+
+    let use_chatgpt = *use_chatgpt.lock().await;
+    let use_deepseek = *use_deepseek.lock().await;
+    let typing = TypingIndicator::start(bot, msg.chat.id);
+    let reply = lookup_text(msg, text, previews, in_flight, use_chatgpt, use_deepseek).await;
+    drop(typing);
+    let reply = match reply {
+        Err(e) if e.is::<ServiceUnavailable>() => {
+            bot.send_message(msg.chat.id, SERVICE_UNAVAILABLE_MESSAGE)
+                .send_retrying()
+                .await?;
+            return Ok(());
+        }
+        result => result?,
+    };
+    let original_reply = replies.lock().await.get(&(chat_id, msg.id)).copied();
+    match original_reply {
+        Some(reply_id) => {
+            if let Err(e) = reply.edit(bot, msg.chat.id, reply_id).await {
+                log::warn!("Failed to edit reply {}: {}", reply_id.0, e);
+                reply.send(bot, msg.chat.id).await?;
+            }
+        }
+        None => {
+            let sent = reply.send(bot, msg.chat.id).await?;
+            track_reply(replies, msg, &sent).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reply to a free-text query.
+struct LookupReply {
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+    /// MarkdownV2 version of `text`, sent in its place when set.
+    markdown: Option<String>,
+}
+
+/// Telegram rejects the whole message over a single malformed entity.
+fn is_formatting_error(error: &RequestError) -> bool {
+    match error {
+        RequestError::Api(ApiError::CantParseEntities) => true,
+        RequestError::Api(ApiError::Unknown(text)) => text.contains("can't parse entities"),
+        _ => false,
+    }
+}
+
+impl LookupReply {
+    /// Sends the formatted version if there is one, falling back to the plain
+    /// text when Telegram can't parse the formatting.
+    async fn send(&self, bot: &Bot, chat_id: ChatId) -> Result<Message> {
+        if let Some(markdown) = &self.markdown {
+            let mut request = bot
+                .send_message(chat_id, markdown)
+                .parse_mode(ParseMode::MarkdownV2);
+            if let Some(keyboard) = self.keyboard.clone() {
+                request = request.reply_markup(keyboard);
+            }
+            match request.send_retrying().await {
+                Err(e) if is_formatting_error(&e) => {
+                    log::warn!("Reply formatting was rejected, sending plain text: {}", e)
                 }
+                result => return Ok(result?),
             }
-        } else {
-            let input_type = analyze_input(text);
-
-            // Check local database first for single words
-            if matches!(input_type, InputType::GermanWord | InputType::RussianWord) {
-                let translations = read_translations()?;
-                if let Some(existing_translation) = find_translation(text, &translations) {
-                    let response = format_translation_response(existing_translation);
-                    bot.send_message(msg.chat.id, response).await?;
-                    return Ok(());
+        }
+
+        let mut request = bot.send_message(chat_id, &self.text);
+        if let Some(keyboard) = self.keyboard.clone() {
+            request = request.reply_markup(keyboard);
+        }
+        Ok(request.send_retrying().await?)
+    }
+
+    /// Replaces the text of an earlier reply, with the same fallback as `send`.
+    async fn edit(&self, bot: &Bot, chat_id: ChatId, message_id: MessageId) -> Result<Message> {
+        if let Some(markdown) = &self.markdown {
+            let mut request = bot
+                .edit_message_text(chat_id, message_id, markdown)
+                .parse_mode(ParseMode::MarkdownV2);
+            if let Some(keyboard) = self.keyboard.clone() {
+                request = request.reply_markup(keyboard);
+            }
+            match request.send_retrying().await {
+                Err(e) if is_formatting_error(&e) => {
+                    log::warn!("Reply formatting was rejected, sending plain text: {}", e)
                 }
+                result => return Ok(result?),
             }
+        }
 
-            // Continue with existing logic for API calls
-            let context = if let Some(reply) = msg.reply_to_message() {
-                reply.text().map(|original_text| {
-                    if let Some(first_line) = original_text.lines().next() {
-                        if first_line.starts_with("➡️ ") {
-                            first_line.trim_start_matches("➡️ ").trim().to_string()
-                        } else {
-                            first_line.trim().to_string()
-                        }
-                    } else {
-                        String::new()
-                    }
-                })
-            } else {
-                None
-            };
+        let mut request = bot.edit_message_text(chat_id, message_id, &self.text);
+        if let Some(keyboard) = self.keyboard.clone() {
+            request = request.reply_markup(keyboard);
+        }
+        Ok(request.send_retrying().await?)
+    }
+}
 
-            let use_chatgpt = *use_chatgpt.lock().await;
-            let use_deepseek = *use_deepseek.lock().await;
-            let claude_response = if let Some(context) = context {
-                let combined_text = format!("Context: {}\nQuery: {}", context, text);
-                translate_text(&combined_text, use_chatgpt, use_deepseek).await?
-            } else {
-                translate_text(text, use_chatgpt, use_deepseek).await?
-            };
+/// Translates or answers a free-text query, checking the local database first
+/// for single words.
+async fn lookup_text(
+    msg: &Message,
+    text: &str,
+    previews: &PendingPreviews,
+    in_flight: &InFlightWords,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<LookupReply> {
+    let language = chat_config(Some(msg.chat.id.0)).target_language();
+    let input_type = analyze_input(text, language);
+
+    if let InputType::Preview = input_type {
+        return preview_translation(msg.chat.id, text, previews, use_chatgpt, use_deepseek).await;
+    }
+
+    // Check local database first for single words. The in-flight marker is
+    // held until the reply is ready, whether the lookup succeeds or fails
+    let _in_flight = if matches!(input_type, InputType::GermanWord | InputType::TargetWord) {
+        if let Some(existing_translation) = find_saved_word(msg.chat.id, text, &input_type)? {
+            return Ok(LookupReply {
+                text: with_similar_words(
+                    format_translation_response(&existing_translation),
+                    &existing_translation,
+                ),
+                keyboard: card_keyboard(&existing_translation.original),
+                markdown: None,
+            });
+        }
+
+        let Some(marker) = InFlightWord::start(in_flight, msg.chat.id, text) else {
+            return Ok(LookupReply {
+                text: "⏳ Это слово уже обрабатывается.".to_string(),
+                keyboard: None,
+                markdown: None,
+            });
+        };
+        Some(marker)
+    } else {
+        None
+    };
+
+    // Continue with existing logic for API calls
+    let context = msg
+        .reply_to_message()
+        .and_then(|reply| reply.text().or(reply.caption()))
+        .and_then(|text| reply_context(text, language));
+
+    let claude_response = if let Some(context) = context {
+        let combined_text = format!("Context: {}\nQuery: {}", context, text);
+        translate_text(
+            &combined_text,
+            Some(msg.chat.id.0),
+            use_chatgpt,
+            use_deepseek,
+        )
+        .await?
+    } else {
+        translate_text(text, Some(msg.chat.id.0), use_chatgpt, use_deepseek).await?
+    };
 
-            let response = match input_type {
-                InputType::Explanation
-                | InputType::GrammarCheck
-                | InputType::Freeform
-                | InputType::Simplify => claude_response.trim().to_string(),
-                InputType::GermanWord | InputType::RussianWord => {
-                    let translation = parse_translation_response(text, &claude_response);
-                    if let Err(e) = add_translation(translation.clone()) {
-                        log::error!("Failed to add translation: {}", e);
+    let mut keyboard = None;
+    let mut markdown = None;
+    let response = match input_type {
+        InputType::GrammarCheck => {
+            markdown = Some(format_grammar_check(claude_response.trim()));
+            claude_response.trim().to_string()
+        }
+        InputType::Explanation => {
+            let explanation = claude_response.trim().to_string();
+            match replied_card_word(msg) {
+                Some(word) => match set_note(word, &explanation) {
+                    Ok(true) => format!(
+                        "{}\n\n📝 Сохранено как заметка к слову «{}».",
+                        explanation, word
+                    ),
+                    Ok(false) => explanation,
+                    Err(e) => {
+                        log::error!("Failed to save note for '{}': {}", word, e);
+                        explanation
                     }
-                    format_translation_response(&translation)
-                }
-                InputType::RussianSentence | InputType::GermanSentence => {
-                    format!("{} ➜ {}", text, claude_response.trim())
+                },
+                None => explanation,
+            }
+        }
+        InputType::Freeform | InputType::Simplify | InputType::Monolingual => {
+            claude_response.trim().to_string()
+        }
+        InputType::Preview => unreachable!(),
+        InputType::GermanWord | InputType::TargetWord => {
+            let translation = match parse_word_reply(
+                text,
+                &claude_response,
+                Some(msg.chat.id.0),
+                use_chatgpt,
+                use_deepseek,
+            )
+            .await?
+            {
+                Ok(translation) => translation,
+                Err(e) => {
+                    log::warn!("Not saving reply for '{}': {}", text, e);
+                    return Ok(LookupReply {
+                        text: OFF_FORMAT_MESSAGE.to_string(),
+                        keyboard: None,
+                        markdown: None,
+                    });
                 }
             };
+            let auto_save = read_user_config(msg.chat.id.0)
+                .map(|config| config.auto_save)
+                .unwrap_or(true);
+            let mut card =
+                with_similar_words(format_translation_response(&translation), &translation);
+            if auto_save {
+                match add_translation(translation.clone()) {
+                    Err(e) if e.is::<WordLimitReached>() => {
+                        card.push_str(&format!("\n\n⚠️ {}. Слово не сохранено.", e));
+                    }
+                    Err(e) => log::error!("Failed to add translation: {}", e),
+                    Ok(()) => {}
+                }
+                keyboard = card_keyboard(&translation.original);
+                card
+            } else {
+                keyboard = Some(InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback(
+                        "💾 Сохранить",
+                        preview_callback_data("save", &translation.original),
+                    ),
+                ]]));
+                previews.lock().await.insert(msg.chat.id.0, translation);
+                card
+            }
+        }
+        InputType::TargetSentence | InputType::GermanSentence => {
+            if is_sentence_log_enabled() {
+                if let Err(e) = log_sentence(text, claude_response.trim()) {
+                    log::error!("Failed to log sentence: {}", e);
+                }
+            }
+            format_aligned_sentences(text, &claude_response)
+                .unwrap_or_else(|| format!("{} ➜ {}", text, claude_response.trim()))
+        }
+    };
+
+    Ok(LookupReply {
+        text: response,
+        keyboard,
+        markdown,
+    })
+}
+
+/// Appends up to `MAX_SIMILAR_WORDS` stored words spelled like the card's
+/// word, so related vocabulary comes up for review.
+fn with_similar_words(mut card: String, translation: &Translation) -> String {
+    let translations = match read_translations() {
+        Ok(translations) => translations,
+        Err(e) => {
+            log::warn!("Failed to read translations for similar words: {}", e);
+            return card;
+        }
+    };
+    let similar: Vec<String> =
+        similar_words(&translation.original, &translations, MAX_SIMILAR_WORDS)
+            .iter()
+            .map(|t| format!("{} ({})", t.with_article(), t.translation))
+            .collect();
+    if !similar.is_empty() {
+        card.push_str(&format!(
+            "\n\n🔗 Похожие слова из вашего словаря: {}",
+            similar.join(", ")
+        ));
+    }
+    card
+}
+
+/// Picks the phrase a reply is about from the replied-to message: the word of
+/// a card ("➡️ der Wald"), the German side of a sentence translation
+/// ("Ich gehe ➜ Я иду"), or the opening line of anything else.
+fn reply_context(text: &str, language: TargetLanguage) -> Option<String> {
+    let text = text.trim();
+
+    if let Some(word) = text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("➡️ "))
+    {
+        return Some(word.trim().to_string());
+    }
+
+    if let Some((source, target)) = text.split_once(" ➜ ") {
+        let german = if is_target_language(source, language) {
+            target
+        } else {
+            source
+        };
+        return Some(truncate_chars(german.trim(), MAX_REPLY_CONTEXT_CHARS));
+    }
+
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| truncate_chars(line, MAX_REPLY_CONTEXT_CHARS))
+}
+
+/// The saved word of a replied-to word card, without its article.
+fn replied_card_word(msg: &Message) -> Option<&str> {
+    let headword = msg
+        .reply_to_message()?
+        .text()?
+        .lines()
+        .next()?
+        .strip_prefix("➡️ ")?
+        .trim();
+    let word = ["der ", "die ", "das "]
+        .iter()
+        .find_map(|article| headword.strip_prefix(article))
+        .unwrap_or(headword);
+    Some(word.trim())
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text.to_string(),
+    }
+}
+
+/// Looks the word up as typed and, with the digraphs setting on, as
+/// "Fußball" when typed "Fussball".
+fn find_saved_word(
+    chat_id: ChatId,
+    text: &str,
+    input_type: &InputType,
+) -> Result<Option<Translation>> {
+    let saved = match input_type {
+        InputType::GermanWord => get_by_original(text)?,
+        InputType::TargetWord => get_by_translation(text)?,
+        _ => get_translation(text)?,
+    };
+    if saved.is_some() {
+        return Ok(saved);
+    }
+    if !matches!(input_type, InputType::GermanWord)
+        || !read_user_config(chat_id.0).is_ok_and(|config| config.ascii_digraphs)
+    {
+        return Ok(None);
+    }
+    let folded = fold_ascii_digraphs(text);
+    if folded == text {
+        return Ok(None);
+    }
+    get_by_original(&folded)
+}
+
+/// Translates a `~:` word without saving it and asks whether to keep the card.
+async fn preview_translation(
+    chat_id: ChatId,
+    text: &str,
+    previews: &PendingPreviews,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<LookupReply> {
+    let word = text.trim_start_matches("~:").trim();
+    if word.is_empty() {
+        return Ok(LookupReply {
+            text: "Usage: ~: <word>".to_string(),
+            keyboard: None,
+            markdown: None,
+        });
+    }
+
+    if let Some(existing_translation) = get_translation(word)? {
+        return Ok(LookupReply {
+            text: format!(
+                "{}\n\nУже сохранено.",
+                format_translation_response(&existing_translation)
+            ),
+            keyboard: card_keyboard(&existing_translation.original),
+            markdown: None,
+        });
+    }
+
+    let response = translate_text(text, Some(chat_id.0), use_chatgpt, use_deepseek).await?;
+    let translation = match parse_word_reply(
+        word,
+        &response,
+        Some(chat_id.0),
+        use_chatgpt,
+        use_deepseek,
+    )
+    .await?
+    {
+        Ok(translation) => translation,
+        Err(e) => {
+            log::warn!("Not previewing reply for '{}': {}", word, e);
+            return Ok(LookupReply {
+                text: OFF_FORMAT_MESSAGE.to_string(),
+                keyboard: None,
+                markdown: None,
+            });
+        }
+    };
+    let card = format_translation_response(&translation);
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Да", preview_callback_data("save", &translation.original)),
+        InlineKeyboardButton::callback("Нет", preview_callback_data("skip", &translation.original)),
+    ]]);
+
+    previews.lock().await.insert(chat_id.0, translation);
+
+    Ok(LookupReply {
+        text: format!("{}\n\nСохранить?", card),
+        keyboard: Some(keyboard),
+        markdown: None,
+    })
+}
+
+/// The word goes into the button data so an older preview's buttons can't
+/// save a newer card; words too long for callback data go without the check.
+fn preview_callback_data(decision: &str, word: &str) -> String {
+    let data = format!("{}{}:{}", PREVIEW_CALLBACK_PREFIX, decision, word);
+    if data.len() <= MAX_CALLBACK_DATA_LEN {
+        data
+    } else {
+        format!("{}{}", PREVIEW_CALLBACK_PREFIX, decision)
+    }
+}
+
+/// Handles the Да/Нет buttons under a `~:` preview and the save button shown
+/// when auto-save is off.
+async fn confirm_preview(
+    bot: &Bot,
+    message: &Message,
+    data: &str,
+    previews: &PendingPreviews,
+) -> Result<()> {
+    let chat_id = message.chat.id;
+    let (decision, word) = match data.split_once(':') {
+        Some((decision, word)) => (decision, Some(word)),
+        None => (data, None),
+    };
+
+    let pending = {
+        let mut previews = previews.lock().await;
+        let is_current = previews
+            .get(&chat_id.0)
+            .is_some_and(|t| word.is_none_or(|word| t.original == word));
+        if is_current {
+            previews.remove(&chat_id.0)
+        } else {
+            None
+        }
+    };
+    let Some(translation) = pending else {
+        bot.edit_message_reply_markup(chat_id, message.id)
+            .send_retrying()
+            .await?;
+        bot.send_message(chat_id, "This preview has expired.")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    };
+
+    let card = format_translation_response(&translation);
+    if decision != "save" {
+        bot.edit_message_text(chat_id, message.id, format!("{}\n\nНе сохранено.", card))
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
 
-            bot.send_message(msg.chat.id, response).await?;
+    let original = translation.original.clone();
+    match add_translation(translation) {
+        Ok(()) => {
+            let mut request =
+                bot.edit_message_text(chat_id, message.id, format!("{}\n\n✅ Сохранено.", card));
+            if let Some(keyboard) = card_keyboard(&original) {
+                request = request.reply_markup(keyboard);
+            }
+            request.send_retrying().await?;
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ Error saving translation: {}", e))
+                .send_retrying()
+                .await?;
         }
     }
     Ok(())
 }
 
+fn get_max_input_chars() -> usize {
+    env::var("MAX_INPUT_CHARS")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|chars| *chars > 0)
+        .unwrap_or(DEFAULT_MAX_INPUT_CHARS)
+}
+
+/// The text's length in characters if it's over the limit.
+fn over_input_limit(text: &str, max_chars: usize) -> Option<usize> {
+    let chars = text.chars().count();
+    (chars > max_chars).then_some(chars)
+}
+
+/// Refuses text longer than MAX_INPUT_CHARS before it becomes a prompt, so a
+/// pasted document doesn't run into the model's token limits. Returns `true`
+/// if the text was rejected.
+async fn reject_long_input(bot: &Bot, msg: &Message, text: &str) -> Result<bool> {
+    let max_chars = get_max_input_chars();
+    let Some(chars) = over_input_limit(text, max_chars) else {
+        return Ok(false);
+    };
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "⚠️ Текст слишком длинный ({} символов, максимум {}). Разбейте его на части и отправьте по очереди.",
+            chars, max_chars
+        ),
+    )
+    .send_retrying()
+    .await?;
+    Ok(true)
+}
+
+fn get_max_import_size_mb() -> u64 {
+    env::var("MAX_IMPORT_SIZE_MB")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MAX_IMPORT_SIZE_MB)
+}
+
+/// Whether an uploaded file is added to the vocabulary rather than replacing
+/// it. A JSON export is a full backup and replaces by default; a CSV/Anki
+/// deck is usually a partial list, so it is merged unless the caption says
+/// "replace".
+fn import_merges(caption: Option<&str>, is_json: bool) -> bool {
+    match caption
+        .map(|caption| caption.trim().to_lowercase())
+        .as_deref()
+    {
+        Some("merge") => true,
+        Some("replace") => false,
+        _ => !is_json,
+    }
+}
+
+fn import_file(data: &str, is_json: bool, merge: bool) -> Result<usize> {
+    if is_json {
+        import_translations(data, merge)
+    } else {
+        import_csv(data, merge)
+    }
+}
+
 pub async fn handle_document(bot: &Bot, msg: &Message) -> Result<()> {
     if !is_user_authorized(msg).await {
         bot.send_message(
             msg.chat.id,
             "Sorry, you are not authorized to use this bot.",
         )
+        .send_retrying()
         .await?;
         return Ok(());
     }
 
     if let Some(document) = msg.document() {
-        if document
+        let file_name = document
             .file_name
-            .as_ref()
-            .map_or(false, |name| name.ends_with(".json"))
-        {
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+        let is_json = file_name.ends_with(".json");
+        if is_json || file_name.ends_with(".csv") || file_name.ends_with(".txt") {
+            let max_size_mb = get_max_import_size_mb();
+            if document.file.size as u64 > max_size_mb * 1024 * 1024 {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "❌ File is too large ({:.1} MB). The maximum import size is {} MB.",
+                        document.file.size as f64 / (1024.0 * 1024.0),
+                        max_size_mb
+                    ),
+                )
+                .send_retrying()
+                .await?;
+                return Ok(());
+            }
+
+            let merge = import_merges(msg.caption(), is_json);
             let file = bot.get_file(&document.file.id).await?;
             let mut bytes = Vec::new();
             bot.download_file(&file.path, &mut bytes).await?;
 
             match String::from_utf8(bytes) {
-                Ok(json_str) => match import_translations(&json_str) {
+                Ok(data) => match import_file(&data, is_json, merge) {
                     Ok(count) => {
                         bot.send_message(
                             msg.chat.id,
-                            format!("✅ Successfully imported {} translations", count),
+                            format!(
+                                "✅ Successfully imported {} translations ({})",
+                                count,
+                                if merge {
+                                    "added to your words"
+                                } else {
+                                    "replaced your words"
+                                }
+                            ),
                         )
+                        .send_retrying()
                         .await?;
                     }
                     Err(e) => {
@@ -413,18 +2592,124 @@ pub async fn handle_document(bot: &Bot, msg: &Message) -> Result<()> {
                             msg.chat.id,
                             format!("❌ Error importing translations: {}", e),
                         )
+                        .send_retrying()
                         .await?;
                     }
                 },
                 Err(e) => {
-                    bot.send_message(msg.chat.id, format!("❌ Error reading file: {}", e))
-                        .await?;
+                    let preview: String = String::from_utf8_lossy(e.as_bytes())
+                        .chars()
+                        .take(IMPORT_ERROR_PREVIEW_CHARS)
+                        .collect();
+                    bot.send_message(
+                        msg.chat.id,
+                        format!(
+                            "❌ The file is not valid UTF-8 text ({}). Is it really a JSON or CSV export?\nStarts with: {}",
+                            e.utf8_error(),
+                            preview
+                        ),
+                    )
+                    .send_retrying().await?;
                 }
             }
         } else {
-            bot.send_message(msg.chat.id, "❌ Please send a JSON file")
-                .await?;
+            bot.send_message(
+                msg.chat.id,
+                "❌ Please send a JSON export or a CSV/Anki text file",
+            )
+            .send_retrying()
+            .await?;
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestStorage;
+
+    #[test]
+    fn reply_context_of_a_word_card_is_its_headword() {
+        let card = "➡️ der Wald\nлес\n\n📝 Grammar forms:\nder Wald, die Wälder";
+        assert_eq!(
+            reply_context(card, TargetLanguage::Russian).as_deref(),
+            Some("der Wald")
+        );
+    }
+
+    #[test]
+    fn reply_context_of_a_sentence_is_its_german_side() {
+        let from_german = "Ich gehe nach Hause ➜ Я иду домой";
+        let to_german = "Я иду домой ➜ Ich gehe nach Hause";
+        assert_eq!(
+            reply_context(from_german, TargetLanguage::Russian).as_deref(),
+            Some("Ich gehe nach Hause")
+        );
+        assert_eq!(
+            reply_context(to_german, TargetLanguage::Russian).as_deref(),
+            Some("Ich gehe nach Hause")
+        );
+    }
+
+    #[test]
+    fn reply_context_of_plain_text_is_its_first_line() {
+        let explanation = "\n  «Doch» отвечает на отрицательный вопрос.\nНапример: …";
+        assert_eq!(
+            reply_context(explanation, TargetLanguage::Russian).as_deref(),
+            Some("«Doch» отвечает на отрицательный вопрос.")
+        );
+    }
+
+    #[test]
+    fn input_limit_counts_characters_up_to_the_boundary() {
+        let mut env = TestStorage::new("input-limit");
+        env.env("MAX_INPUT_CHARS", "10");
+        let max_chars = get_max_input_chars();
+        assert_eq!(max_chars, 10);
+
+        // Umlauts take two bytes but count as one character
+        assert_eq!(over_input_limit(&"ä".repeat(10), max_chars), None);
+        assert_eq!(over_input_limit(&"ä".repeat(11), max_chars), Some(11));
+    }
+
+    #[test]
+    fn entity_errors_fall_back_to_plain_text() {
+        assert!(is_formatting_error(&RequestError::Api(
+            ApiError::CantParseEntities
+        )));
+        assert!(is_formatting_error(&RequestError::Api(ApiError::Unknown(
+            "Bad Request: can't parse entities: Can't find end of Bold entity at byte offset 4"
+                .to_string()
+        ))));
+        assert!(!is_formatting_error(&RequestError::Api(
+            ApiError::MessageTextIsEmpty
+        )));
+    }
+
+    #[test]
+    fn csv_imports_merge_unless_told_to_replace() {
+        assert!(import_merges(None, false));
+        assert!(import_merges(Some("vocab"), false));
+        assert!(!import_merges(Some(" Replace "), false));
+        assert!(!import_merges(None, true));
+        assert!(import_merges(Some("MERGE"), true));
+    }
+
+    #[test]
+    fn days_ago_uses_the_russian_plural_forms() {
+        for (days, expected) in [
+            (1, "1 день назад"),
+            (2, "2 дня назад"),
+            (4, "4 дня назад"),
+            (5, "5 дней назад"),
+            (11, "11 дней назад"),
+            (12, "12 дней назад"),
+            (21, "21 день назад"),
+            (22, "22 дня назад"),
+            (111, "111 дней назад"),
+        ] {
+            assert_eq!(days_ago(days), expected);
+        }
+    }
+}
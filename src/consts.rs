@@ -1,28 +1,22 @@
 pub const SHUTDOWN_MESSAGE: &str = "Shutting down...";
 
-pub const HELP_MESSAGE: &str = r#"Доступные комманды:
-/start - Запустить бота
-/help - Показать это сообщение
-/export - Экспортировать базу данных переводов
-/practice - Начать практику
-/stop - Остановить практику
-/talk - Начать разговор на немецком (уровень B1)
-/stoptalk - Закончить разговор
-/exit - Остановить бота
-/story — Создать историю на основе слов из базы
-
-Специальные префиксы для запросов:
+/// Usage notes appended to the command list generated from `Command`.
+pub const USAGE_MESSAGE: &str = r#"Специальные префиксы для запросов:
 !: [запрос] - Проверить грамматику немецкого текста
 -: [запрос] - Упростить немецкое предложение
 ?: [запрос]  - Объяснить грамматику немецкого текста
 ??: [запрос] - Задать вопрос о немецком языке в свободной форме
+~: [слово] - Показать карточку слова и сохранить только после подтверждения
+=: [слово] - Объяснение слова на простом немецком, без перевода
 
 Как пользоваться:
 • Отправьте немецкое или русское слово для перевода и грамматической справки
 • Отправьте немецкое или русское предложение для перевода
 • Ответьте на любой перевод вопросом, чтобы получить ответ, учитывающий контекст
+• Ответьте на карточку слова запросом ?: — объяснение сохранится как заметка к слову
+• Отправьте голосовое сообщение — оно будет распознано и обработано как текст
 • Используйте специальные префиксы перед запросом для расширенного функционала
-• В режиме разговора (/talk) бот будет вести с вами диалог на немецком языке, исправляя ошибки
+• В режиме разговора (/talk) бот будет вести с вами диалог на немецком языке, исправляя ошибки. С /talk quiz собеседник будет иногда вставлять в разговор ваши трудные слова
 
 Примеры:
 Wald
@@ -31,5 +25,7 @@ Wald
 ?: Der Mann isst einen Apfel
 !: Ich habe gestern nach Berlin gefahren
 -: Ich würde gerne wissen, ob Sie morgen Zeit haben
+~: Wald
+=: Gelassenheit
 
 Бот автоматически определяет язык ввода и тип запроса."#;
@@ -0,0 +1,149 @@
+use crate::translation::{import_entries, split_example, unix_now, Example, Translation};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const ARTICLES: [&str; 3] = ["der", "die", "das"];
+/// First-column values that mark a header row rather than a word.
+const HEADER_FIELDS: [&str; 4] = ["german", "deutsch", "front", "word"];
+/// Several examples in one field are separated like this, Anki uses `<br>`.
+const EXAMPLE_SEPARATORS: [&str; 3] = ["<br>", "<br/>", "|"];
+/// Parse errors listed in the reply before the rest are only counted.
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Anki exports notes as tab-separated text, spreadsheets mostly use commas
+/// or semicolons. Picked from the first data line.
+fn detect_delimiter(line: &str) -> char {
+    if line.contains('\t') {
+        '\t'
+    } else if line.contains(';') && !line.contains(',') {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Splits one line into fields, honoring double quotes and `""` escapes.
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+fn parse_examples(field: &str) -> std::result::Result<Vec<Example>, String> {
+    let mut parts = vec![field];
+    for separator in EXAMPLE_SEPARATORS {
+        parts = parts
+            .iter()
+            .flat_map(|part| part.split(separator))
+            .collect();
+    }
+
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| match split_example(part) {
+            (german, russian) if !german.is_empty() && !russian.is_empty() => Ok(Example {
+                german: german.to_string(),
+                russian: russian.to_string(),
+            }),
+            _ => Err(format!(
+                "example '{}' is not in \"German - translation\" form",
+                part
+            )),
+        })
+        .collect()
+}
+
+fn parse_row(fields: &[String]) -> std::result::Result<Translation, String> {
+    let (front, back) = match fields {
+        [front, back, ..] if !front.is_empty() && !back.is_empty() => (front, back),
+        [_, _, ..] => return Err("German or translation column is empty".to_string()),
+        _ => return Err("expected at least two columns".to_string()),
+    };
+
+    // "der Hund" keeps the article the way word cards store it
+    let (original, grammar_forms) = match front.split_once(' ') {
+        Some((article, noun)) if ARTICLES.contains(&article.to_lowercase().as_str()) => {
+            (noun.trim().to_string(), vec![article.to_lowercase()])
+        }
+        _ => (front.clone(), Vec::new()),
+    };
+    let examples = match fields.get(2) {
+        Some(field) => parse_examples(field)?,
+        None => Vec::new(),
+    };
+
+    Ok(Translation {
+        original,
+        translation: back.clone(),
+        grammar_forms,
+        examples,
+        added_at: Some(unix_now()),
+        ..Default::default()
+    })
+}
+
+/// Parses a CSV or Anki text export: German word (optionally with its
+/// article), translation and an optional examples column. Every broken row
+/// is reported with its line number.
+fn parse_csv(data: &str) -> Result<Vec<Translation>> {
+    let mut delimiter = None;
+    let mut translations = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}');
+        // Anki puts "#separator:tab" style headers at the top
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let delimiter = *delimiter.get_or_insert_with(|| detect_delimiter(line));
+        let fields = split_fields(line, delimiter);
+        if translations.is_empty()
+            && errors.is_empty()
+            && HEADER_FIELDS.contains(&fields[0].to_lowercase().as_str())
+        {
+            continue;
+        }
+
+        match parse_row(&fields) {
+            Ok(translation) => translations.push(translation),
+            Err(e) => errors.push(format!("row {}: {}", i + 1, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        let mut message = format!("{} invalid rows:\n", errors.len());
+        message.push_str(&errors[..errors.len().min(MAX_REPORTED_ERRORS)].join("\n"));
+        if errors.len() > MAX_REPORTED_ERRORS {
+            message.push_str(&format!(
+                "\n...and {} more",
+                errors.len() - MAX_REPORTED_ERRORS
+            ));
+        }
+        return Err(message.into());
+    }
+    Ok(translations)
+}
+
+/// Imports a CSV/Anki deck the same way as a JSON export: replacing the
+/// database, or adding to it when `merge` is set.
+pub fn import_csv(data: &str, merge: bool) -> Result<usize> {
+    import_entries(parse_csv(data)?, merge)
+}
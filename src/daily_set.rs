@@ -0,0 +1,146 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    activity::today,
+    translation::{canonical_key, practice_weight, Translation},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_DAILY_SET_SIZE: usize = 10;
+
+/// The words picked for one chat's `/today` review and which of them were
+/// already answered correctly.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct DailySet {
+    pub day: i64,
+    /// Canonical keys of the day's words.
+    pub words: Vec<String>,
+    pub done: BTreeSet<String>,
+}
+
+impl DailySet {
+    pub fn remaining(&self) -> BTreeSet<String> {
+        self.words
+            .iter()
+            .filter(|word| !self.done.contains(*word))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Serializes the read-modify-write of the daily sets file.
+static DAILY_SET_LOCK: Mutex<()> = Mutex::new(());
+
+/// Defaults to daily_sets.json next to the translations storage file.
+fn get_daily_sets_path() -> String {
+    env::var("DAILY_SETS_FILE").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("daily_sets.json")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+fn get_daily_set_size() -> usize {
+    env::var("DAILY_SET_SIZE")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_DAILY_SET_SIZE)
+}
+
+fn read_daily_sets() -> Result<HashMap<i64, DailySet>> {
+    let path = get_daily_sets_path();
+    if !Path::new(&path).exists() {
+        return Ok(HashMap::new());
+    }
+    let data = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn write_daily_sets(sets: &HashMap<i64, DailySet>) -> Result<()> {
+    let path = get_daily_sets_path();
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string(sets)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// Weighted pick of the day's words, favoring weak ones. The generator is
+/// seeded with the date and chat so the same vocabulary gives the same set.
+fn pick_daily_words(chat_id: i64, day: i64, translations: &[Translation]) -> Vec<String> {
+    let mut candidates: Vec<&Translation> = translations
+        .iter()
+        .filter(|t| !t.archived && !t.leech)
+        .collect();
+    candidates.sort_by_key(|t| t.key());
+    candidates.dedup_by_key(|t| t.key());
+
+    let mut rng = StdRng::seed_from_u64((day as u64) ^ (chat_id as u64).rotate_left(32));
+    match candidates
+        .choose_multiple_weighted(&mut rng, get_daily_set_size(), |t| practice_weight(t))
+    {
+        Ok(picked) => picked.map(|t| t.key()).collect(),
+        Err(e) => {
+            log::warn!("Weighted daily pick failed, using the first words: {}", e);
+            candidates
+                .iter()
+                .take(get_daily_set_size())
+                .map(|t| t.key())
+                .collect()
+        }
+    }
+}
+
+/// Today's set for the chat, picked and saved on the first call of the day.
+pub fn todays_set(chat_id: i64, translations: &[Translation]) -> Result<DailySet> {
+    let _guard = DAILY_SET_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut sets = read_daily_sets()?;
+    let day = today();
+
+    if let Some(set) = sets.get(&chat_id).filter(|set| set.day == day) {
+        return Ok(set.clone());
+    }
+
+    let set = DailySet {
+        day,
+        words: pick_daily_words(chat_id, day, translations),
+        done: BTreeSet::new(),
+    };
+    sets.insert(chat_id, set.clone());
+    write_daily_sets(&sets)?;
+    Ok(set)
+}
+
+/// Marks a word of today's set as done and returns how many are left, or
+/// `None` if the word isn't in the set.
+pub fn mark_done(chat_id: i64, word: &str) -> Result<Option<usize>> {
+    let _guard = DAILY_SET_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut sets = read_daily_sets()?;
+    let key = canonical_key(word);
+
+    let Some(set) = sets
+        .get_mut(&chat_id)
+        .filter(|set| set.day == today() && set.words.contains(&key))
+    else {
+        return Ok(None);
+    };
+    set.done.insert(key);
+    let remaining = set.remaining().len();
+    write_daily_sets(&sets)?;
+    Ok(Some(remaining))
+}
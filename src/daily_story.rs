@@ -0,0 +1,146 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use teloxide::{prelude::Requester, types::ChatId, Bot};
+use tokio::sync::Mutex;
+
+use crate::retry::SendRetrying;
+use crate::story::{generate_story, get_german_words};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_SEND_TIME: (u64, u64) = (8, 0);
+const CHECK_INTERVAL_SECS: u64 = 60;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Defaults to daily_story_chats.json next to the translations storage file.
+fn get_daily_story_path() -> String {
+    env::var("DAILY_STORY_FILE").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("daily_story_chats.json")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+pub fn read_daily_story_chats() -> Result<HashSet<i64>> {
+    let path = get_daily_story_path();
+    if !Path::new(&path).exists() {
+        return Ok(HashSet::new());
+    }
+    let data = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+pub fn set_daily_story(chat_id: i64, enabled: bool) -> Result<()> {
+    let mut chats = read_daily_story_chats()?;
+    if enabled {
+        chats.insert(chat_id);
+    } else {
+        chats.remove(&chat_id);
+    }
+
+    let path = get_daily_story_path();
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string(&chats)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// Send time in UTC as (hour, minute), read from DAILY_STORY_TIME ("HH:MM").
+pub fn get_send_time() -> (u64, u64) {
+    env::var("DAILY_STORY_TIME")
+        .ok()
+        .and_then(|value| {
+            let (hour, minute) = value.trim().split_once(':')?;
+            let hour = hour.parse::<u64>().ok().filter(|h| *h < 24)?;
+            let minute = minute.parse::<u64>().ok().filter(|m| *m < 60)?;
+            Some((hour, minute))
+        })
+        .unwrap_or(DEFAULT_SEND_TIME)
+}
+
+/// Returns (now, today, send time today) as seconds/days since the epoch.
+fn schedule_for_now() -> (u64, u64, u64) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let today = now / SECONDS_PER_DAY;
+    let (hour, minute) = get_send_time();
+    (
+        now,
+        today,
+        today * SECONDS_PER_DAY + hour * 3600 + minute * 60,
+    )
+}
+
+/// Checks once a minute whether the configured send time has passed today
+/// and, if so, sends a fresh story to every opted-in chat.
+pub async fn run_daily_story_task(
+    bot: Bot,
+    use_chatgpt: Arc<Mutex<bool>>,
+    use_deepseek: Arc<Mutex<bool>>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+
+    // Don't send right after a restart if today's story time has already passed
+    let (now, today, send_at) = schedule_for_now();
+    let mut last_sent_day = (now >= send_at).then_some(today);
+
+    loop {
+        interval.tick().await;
+
+        let (now, today, send_at) = schedule_for_now();
+        if now < send_at || last_sent_day == Some(today) {
+            continue;
+        }
+        last_sent_day = Some(today);
+
+        if let Err(e) = send_daily_stories(&bot, &use_chatgpt, &use_deepseek).await {
+            log::error!("Failed to send daily stories: {}", e);
+        }
+    }
+}
+
+async fn send_daily_stories(
+    bot: &Bot,
+    use_chatgpt: &Arc<Mutex<bool>>,
+    use_deepseek: &Arc<Mutex<bool>>,
+) -> Result<()> {
+    let chats = read_daily_story_chats()?;
+    if chats.is_empty() {
+        return Ok(());
+    }
+    if get_german_words()?.is_empty() {
+        log::info!("Skipping daily story, the word list is empty");
+        return Ok(());
+    }
+
+    let use_chatgpt = *use_chatgpt.lock().await;
+    let use_deepseek = *use_deepseek.lock().await;
+
+    for chat_id in chats {
+        match generate_story(use_chatgpt, use_deepseek).await {
+            Ok(story) => {
+                // One chat that blocked the bot shouldn't stop the others
+                if let Err(e) = bot
+                    .send_message(ChatId(chat_id), format!("☀️ Guten Morgen!\n\n{}", story))
+                    .send_retrying()
+                    .await
+                {
+                    log::error!("Failed to send daily story to {}: {}", chat_id, e);
+                }
+            }
+            Err(e) => log::error!("Failed to generate daily story for {}: {}", chat_id, e),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::translation::Translation;
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html lang="de">
+<head>
+<meta charset="utf-8">
+<title>Zungenrede Flashcards</title>
+<style>
+body { font-family: sans-serif; margin: 1cm; }
+.card { display: flex; border: 1px dashed #888; margin-bottom: 0.5cm; page-break-inside: avoid; }
+.side { flex: 1; padding: 0.6cm; min-height: 3cm; }
+.front { border-right: 1px dashed #888; font-size: 1.6em; display: flex; align-items: center; justify-content: center; }
+.back .translation { font-size: 1.3em; margin-bottom: 0.3cm; }
+.back .example { color: #444; font-size: 0.9em; }
+</style>
+</head>
+<body>
+"#;
+
+const HTML_FOOTER: &str = "</body>\n</html>\n";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the vocabulary as a self-contained printable HTML page with the
+/// German word on the front and the Russian translation plus one example on
+/// the back of each card.
+pub fn render_flashcards_html(translations: &[Translation]) -> String {
+    let mut html = String::from(HTML_HEADER);
+
+    for translation in translations {
+        html.push_str("<div class=\"card\">\n");
+        html.push_str(&format!(
+            "<div class=\"side front\">{}</div>\n",
+            escape_html(&translation.with_article())
+        ));
+        html.push_str("<div class=\"side back\">\n");
+        html.push_str(&format!(
+            "<div class=\"translation\">{}</div>\n",
+            escape_html(&translation.translation)
+        ));
+        if let Some(example) = translation.examples.first() {
+            html.push_str(&format!(
+                "<div class=\"example\">{}<br>{}</div>\n",
+                escape_html(&example.german),
+                escape_html(&example.russian)
+            ));
+        }
+        html.push_str("</div>\n</div>\n");
+    }
+
+    html.push_str(HTML_FOOTER);
+    html
+}
@@ -1,16 +1,107 @@
+use crate::language::{is_target_language, TargetLanguage};
+
 #[derive(Debug)]
 pub enum InputType {
-    RussianWord,
-    RussianSentence,
+    TargetWord,
+    TargetSentence,
     GermanWord,
     GermanSentence,
     Explanation,
     GrammarCheck,
     Freeform,
     Simplify,
+    Preview,
+    /// `=:` asks for a definition in simple German, nothing is saved.
+    Monolingual,
+}
+
+const SEPARABLE_PREFIXES: [&str; 20] = [
+    "ab", "an", "auf", "aus", "bei", "dar", "ein", "fest", "fort", "her", "hin", "los", "mit",
+    "nach", "vor", "weg", "weiter", "zu", "zurück", "zusammen",
+];
+
+/// Recognizes infinitive phrases that should be saved as vocabulary rather
+/// than translated as sentences: "auf stehen", "sich freuen", "sich an ziehen".
+fn is_german_verb_phrase(words: &[&str]) -> bool {
+    let is_prefix = |w: &str| SEPARABLE_PREFIXES.contains(&w.to_lowercase().as_str());
+    let is_sich = |w: &str| w.to_lowercase() == "sich";
+
+    let phrase_start = match words {
+        [first, _] => is_sich(first) || is_prefix(first),
+        [first, second, _] => is_sich(first) && is_prefix(second),
+        _ => false,
+    };
+    // The last word has to be the verb itself, which keeps "nach Hause" or
+    // "mit dir" out
+    phrase_start && words.last().is_some_and(|verb| looks_like_infinitive(verb))
+}
+
+/// Lowercase and ending like an infinitive: "stehen", "klingeln", "wandern".
+fn looks_like_infinitive(word: &str) -> bool {
+    word.chars().all(char::is_lowercase)
+        && ["en", "eln", "ern"]
+            .iter()
+            .any(|ending| word.len() > ending.len() && word.ends_with(ending))
+}
+
+const ASCII_DIGRAPHS: [(&str, &str); 12] = [
+    ("ae", "ä"),
+    ("oe", "ö"),
+    ("ue", "ü"),
+    ("Ae", "Ä"),
+    ("Oe", "Ö"),
+    ("Ue", "Ü"),
+    ("AE", "Ä"),
+    ("OE", "Ö"),
+    ("UE", "Ü"),
+    ("ss", "ß"),
+    ("SS", "ß"),
+    ("Ss", "ß"),
+];
+
+/// Rewrites the ASCII spellings used on keyboards without umlauts ("Fussball",
+/// "schoen") to German letters. This also changes words that really are
+/// spelled that way ("Wasser", "Feuer"), so callers should only use it as a
+/// second attempt after the text as typed didn't match.
+pub fn fold_ascii_digraphs(text: &str) -> String {
+    ASCII_DIGRAPHS
+        .iter()
+        .fold(text.to_string(), |text, (digraph, letter)| {
+            text.replace(digraph, letter)
+        })
+}
+
+/// Words ending in a period that don't end a sentence.
+const ABBREVIATIONS: [&str; 10] = [
+    "z.", "b.", "d.", "h.", "dr.", "nr.", "bzw.", "usw.", "ca.", "etc.",
+];
+
+/// Splits a paragraph into sentences after `.`, `!`, `?` and `…`. Common
+/// abbreviations ("z. B.", "Dr.") and ordinals ("3. Mai") don't end a
+/// sentence.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for word in text.split_whitespace() {
+        current.push(word);
+        let ends_sentence = word.ends_with(['.', '!', '?', '…'])
+            && !ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+            && !word
+                .strip_suffix('.')
+                .is_some_and(|number| number.chars().all(|c| c.is_ascii_digit()));
+        if ends_sentence {
+            sentences.push(current.join(" "));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+    sentences
 }
 
-pub fn analyze_input(text: &str) -> InputType {
+pub fn analyze_input(text: &str, language: TargetLanguage) -> InputType {
     if text.starts_with("??:") {
         InputType::Freeform
     } else if text.starts_with("?:") {
@@ -19,22 +110,28 @@ pub fn analyze_input(text: &str) -> InputType {
         InputType::GrammarCheck
     } else if text.starts_with("-:") {
         InputType::Simplify
+    } else if text.starts_with("~:") {
+        InputType::Preview
+    } else if text.starts_with("=:") {
+        InputType::Monolingual
     } else {
-        let has_cyrillic = text
-            .chars()
-            .any(|c| matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}'));
+        if is_target_language(text, language) {
+            // English words are often sent with their article or "to"
+            let words: Vec<_> = text.split_whitespace().collect();
+            let is_english_phrase = language == TargetLanguage::English
+                && words.len() == 2
+                && ["the", "a", "an", "to"].contains(&words[0].to_lowercase().as_str());
 
-        if has_cyrillic {
-            if !text.contains(' ') {
-                InputType::RussianWord
+            if !text.contains(' ') || is_english_phrase {
+                InputType::TargetWord
             } else {
-                InputType::RussianSentence
+                InputType::TargetSentence
             }
         } else {
             let words: Vec<_> = text.split_whitespace().collect();
             let is_german_noun = words.len() == 2 && ["der", "die", "das"].contains(&words[0]);
 
-            if !text.contains(' ') || is_german_noun {
+            if !text.contains(' ') || is_german_noun || is_german_verb_phrase(&words) {
                 InputType::GermanWord
             } else {
                 InputType::GermanSentence
@@ -42,3 +139,54 @@ pub fn analyze_input(text: &str) -> InputType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verb_phrases_are_words() {
+        for text in ["aufstehen", "auf stehen", "sich freuen", "an rufen"] {
+            assert!(
+                matches!(
+                    analyze_input(text, TargetLanguage::Russian),
+                    InputType::GermanWord
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn single_english_words_are_target_words() {
+        assert!(matches!(
+            analyze_input("window", TargetLanguage::English),
+            InputType::TargetWord
+        ));
+        assert!(matches!(
+            analyze_input("Haus", TargetLanguage::English),
+            InputType::GermanWord
+        ));
+    }
+
+    #[test]
+    fn prepositional_phrases_and_sentences_are_sentences() {
+        for text in [
+            "Ich stehe auf",
+            "nach Hause",
+            "mit dir",
+            "zu Hause",
+            "an dem",
+        ] {
+            assert!(
+                matches!(
+                    analyze_input(text, TargetLanguage::Russian),
+                    InputType::GermanSentence
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+}
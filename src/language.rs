@@ -0,0 +1,171 @@
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+/// The language German is translated to and from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TargetLanguage {
+    #[serde(rename = "ru")]
+    Russian,
+    #[serde(rename = "uk")]
+    Ukrainian,
+    #[serde(rename = "en")]
+    English,
+}
+
+impl TargetLanguage {
+    pub const CODES: &'static str = "ru, uk, en";
+
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().as_str() {
+            "ru" => Some(Self::Russian),
+            "uk" | "ua" => Some(Self::Ukrainian),
+            "en" => Some(Self::English),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Russian => "ru",
+            Self::Ukrainian => "uk",
+            Self::English => "en",
+        }
+    }
+
+    /// English name, as templated into the prompts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Russian => "Russian",
+            Self::Ukrainian => "Ukrainian",
+            Self::English => "English",
+        }
+    }
+}
+
+/// TARGET_LANG, used by chats that haven't picked a language with /targetlang
+/// and for work not tied to a chat.
+pub fn default_target_language() -> TargetLanguage {
+    env::var("TARGET_LANG")
+        .ok()
+        .and_then(|code| TargetLanguage::parse(&code))
+        .unwrap_or(TargetLanguage::Russian)
+}
+
+pub fn contains_cyrillic(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}'))
+}
+
+const GERMAN_MARKERS: [&str; 12] = [
+    "der", "die", "das", "ein", "eine", "ich", "du", "ist", "und", "nicht", "sich", "zu",
+];
+const ENGLISH_MARKERS: [&str; 12] = [
+    "the", "a", "an", "is", "are", "i", "you", "and", "not", "to", "of", "it",
+];
+
+/// Letter groups that practically only occur in English spelling. Weaker
+/// hints such as "th", "oo", "ph" or "y" are left out because common German
+/// words have them too (Theater, Boot, Physik, Typ).
+const ENGLISH_PATTERNS: [&str; 6] = ["wh", "igh", "ght", "aw", "ew", "ow"];
+const ENGLISH_SUFFIXES: [&str; 6] = ["ing", "ly", "ful", "ness", "less", "ous"];
+/// Any of these makes a word look German, whatever else it contains.
+const GERMAN_PATTERNS: [&str; 12] = [
+    "sch", "ei", "ie", "tz", "pf", "z", "ung", "heit", "keit", "chen", "lich", "ling",
+];
+
+/// Whether a single word's spelling is clearly English: an English-only
+/// letter group or suffix and nothing typically German.
+fn looks_english(word: &str) -> bool {
+    let english = ENGLISH_PATTERNS
+        .iter()
+        .any(|pattern| word.contains(pattern))
+        || ENGLISH_SUFFIXES
+            .iter()
+            .any(|suffix| word.len() > suffix.len() + 1 && word.ends_with(suffix));
+    english && !GERMAN_PATTERNS.iter().any(|pattern| word.contains(pattern))
+}
+
+/// Whether the text is in the target language rather than German. Cyrillic
+/// languages are told apart by their script. English shares the Latin
+/// alphabet, so it's recognized by common function words and, failing those,
+/// by spelling that only English has; anything still ambiguous counts as
+/// German.
+pub fn is_target_language(text: &str, language: TargetLanguage) -> bool {
+    match language {
+        TargetLanguage::Russian | TargetLanguage::Ukrainian => contains_cyrillic(text),
+        TargetLanguage::English => {
+            if text.chars().any(|c| "äöüÄÖÜß".contains(c)) {
+                return false;
+            }
+            let words: Vec<String> = text
+                .split_whitespace()
+                .map(|w| w.trim_matches(|c: char| !c.is_alphabetic()).to_lowercase())
+                .collect();
+            let count = |markers: &[&str]| {
+                words
+                    .iter()
+                    .filter(|w| markers.contains(&w.as_str()))
+                    .count()
+            };
+            let (english, german) = (count(&ENGLISH_MARKERS), count(&GERMAN_MARKERS));
+            if english != german {
+                return english > german;
+            }
+            words.iter().any(|w| looks_english(w))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_english_words_are_recognized() {
+        for word in [
+            "night",
+            "where",
+            "window",
+            "beautiful",
+            "running",
+            "quickly",
+        ] {
+            assert!(
+                is_target_language(word, TargetLanguage::English),
+                "{}",
+                word
+            );
+        }
+
+        // German words with spelling that is common in English
+        for word in [
+            "Theater", "Boot", "Physik", "Typ", "System", "Party", "Hobby", "Liebling",
+        ] {
+            assert!(
+                !is_target_language(word, TargetLanguage::English),
+                "{}",
+                word
+            );
+        }
+    }
+
+    #[test]
+    fn single_german_words_stay_german() {
+        for word in [
+            "Haus", "Schule", "Zeitung", "Mädchen", "Freiheit", "gehen", "Schnee",
+        ] {
+            assert!(
+                !is_target_language(word, TargetLanguage::English),
+                "{}",
+                word
+            );
+        }
+    }
+
+    #[test]
+    fn cyrillic_targets_go_by_script() {
+        assert!(is_target_language("дом", TargetLanguage::Russian));
+        assert!(!is_target_language("Haus", TargetLanguage::Ukrainian));
+    }
+}
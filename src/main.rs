@@ -1,19 +1,41 @@
+mod activity;
 mod ai;
+mod audit;
 mod commands_messages;
 mod consts;
+mod csv_import;
+mod daily_set;
+mod daily_story;
+mod flashcards;
 mod input;
+mod language;
+mod new_words;
 mod picture;
 mod practice;
+mod retry;
+mod sentence_log;
+mod storage;
 mod story;
+mod stt;
 mod talk;
+#[cfg(test)]
+mod test_support;
 mod translation;
+mod tts;
+mod typing;
+mod user_config;
 
-use commands_messages::{handle_command, handle_document, handle_message, Command, DeleteMode};
+use commands_messages::{
+    handle_callback_query, handle_command, handle_document, handle_edited_message, handle_message,
+    handle_voice, record_start_time, Command, DeleteMode, InFlightWords, PendingActions,
+    PendingPreviews, ReplyMap,
+};
 use picture::PictureSession;
 use practice::PracticeSession;
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::Duration,
 };
 use talk::TalkSession;
 use teloxide::prelude::*;
@@ -24,6 +46,8 @@ type PracticeSessions = Arc<Mutex<HashMap<i64, PracticeSession>>>;
 type TalkSessions = Arc<Mutex<HashMap<i64, TalkSession>>>;
 type PictureSessions = Arc<Mutex<HashMap<i64, PictureSession>>>;
 
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
@@ -32,6 +56,11 @@ async fn main() {
     if let Some(parent) = std::path::Path::new(&get_storage_path()).parent() {
         std::fs::create_dir_all(parent).expect("Failed to create storage directory");
     }
+    record_start_time();
+    storage::init_storage().expect("Failed to open translations storage");
+    if let Err(e) = talk::load_talk_prompts() {
+        log::error!("Failed to load talk prompts, using built-in ones: {}", e);
+    }
 
     let bot = Bot::from_env();
     let (shutdown_tx, _) = broadcast::channel(1);
@@ -39,25 +68,58 @@ async fn main() {
     let talk_sessions: TalkSessions = Arc::new(Mutex::new(HashMap::new()));
     let picture_sessions: PictureSessions = Arc::new(Mutex::new(HashMap::new()));
     let delete_mode: DeleteMode = Arc::new(Mutex::new(HashSet::new()));
+    let replies: ReplyMap = Arc::new(Mutex::new(HashMap::new()));
+    let pending_actions: PendingActions = Arc::new(Mutex::new(HashMap::new()));
+    let previews: PendingPreviews = Arc::new(Mutex::new(HashMap::new()));
+    let in_flight: InFlightWords = Arc::new(std::sync::Mutex::new(HashSet::new()));
     let use_chatgpt = Arc::new(Mutex::new(false));
     let use_deepseek = Arc::new(Mutex::new(false));
+    let use_chatgpt_story = use_chatgpt.clone();
+    let use_deepseek_story = use_deepseek.clone();
 
-    let shutdown_tx_clone = shutdown_tx.clone();
     let sessions_clone = sessions.clone();
     let talk_sessions_clone = talk_sessions.clone();
     let picture_sessions_clone = picture_sessions.clone();
     let delete_mode_clone = delete_mode.clone();
+    let pending_actions_clone = pending_actions.clone();
     let use_chatgpt_clone = use_chatgpt.clone();
     let use_deepseek_clone = use_deepseek.clone();
 
+    let voice_sessions = sessions.clone();
+    let voice_talk_sessions = talk_sessions.clone();
+    let voice_picture_sessions = picture_sessions.clone();
+    let voice_delete_mode = delete_mode.clone();
+    let voice_replies = replies.clone();
+    let voice_previews = previews.clone();
+    let voice_in_flight = in_flight.clone();
+    let voice_use_chatgpt = use_chatgpt.clone();
+    let voice_use_deepseek = use_deepseek.clone();
+
+    let edit_sessions = sessions.clone();
+    let edit_talk_sessions = talk_sessions.clone();
+    let edit_picture_sessions = picture_sessions.clone();
+    let edit_replies = replies.clone();
+    let edit_previews = previews.clone();
+    let edit_in_flight = in_flight.clone();
+    let edit_use_chatgpt = use_chatgpt.clone();
+    let edit_use_deepseek = use_deepseek.clone();
+
+    let callback_sessions = sessions.clone();
+    let callback_talk_sessions = talk_sessions.clone();
+    let callback_picture_sessions = picture_sessions.clone();
+    let callback_delete_mode = delete_mode.clone();
+    let callback_previews = previews.clone();
+    let callback_use_chatgpt = use_chatgpt.clone();
+    let callback_use_deepseek = use_deepseek.clone();
+
     let message_handler = Update::filter_message()
         .branch(dptree::entry().filter_command::<Command>().endpoint(
             move |bot: Bot, msg: Message, cmd: Command| {
-                let shutdown = shutdown_tx_clone.clone();
                 let sessions = sessions_clone.clone();
                 let talk_sessions = talk_sessions_clone.clone();
                 let picture_sessions = picture_sessions_clone.clone();
                 let delete_mode = delete_mode_clone.clone();
+                let pending_actions = pending_actions_clone.clone();
                 let use_chatgpt = use_chatgpt_clone.clone();
                 let use_deepseek = use_deepseek_clone.clone();
                 async move {
@@ -65,11 +127,11 @@ async fn main() {
                         &bot,
                         &msg,
                         cmd,
-                        &shutdown,
                         &sessions,
                         &talk_sessions,
                         &picture_sessions,
                         &delete_mode,
+                        &pending_actions,
                         &use_chatgpt,
                         &use_deepseek,
                     )
@@ -92,12 +154,51 @@ async fn main() {
             ),
         )
         .branch(
-            dptree::filter(|msg: Message| msg.text().is_some()).endpoint(
+            dptree::filter(|msg: Message| msg.voice().is_some()).endpoint(
+                move |bot: Bot, msg: Message| {
+                    let sessions = voice_sessions.clone();
+                    let talk_sessions = voice_talk_sessions.clone();
+                    let picture_sessions = voice_picture_sessions.clone();
+                    let delete_mode = voice_delete_mode.clone();
+                    let replies = voice_replies.clone();
+                    let previews = voice_previews.clone();
+                    let in_flight = voice_in_flight.clone();
+                    let use_chatgpt = voice_use_chatgpt.clone();
+                    let use_deepseek = voice_use_deepseek.clone();
+                    async move {
+                        if let Err(e) = handle_voice(
+                            &bot,
+                            &msg,
+                            &sessions,
+                            &talk_sessions,
+                            &picture_sessions,
+                            &delete_mode,
+                            &replies,
+                            &previews,
+                            &in_flight,
+                            &use_chatgpt,
+                            &use_deepseek,
+                        )
+                        .await
+                        {
+                            log::error!("Error: {:?}", e);
+                        }
+                        ResponseResult::Ok(())
+                    }
+                },
+            ),
+        )
+        .branch(
+            // Captions count as text so a captioned photo isn't silently dropped
+            dptree::filter(|msg: Message| msg.text().or(msg.caption()).is_some()).endpoint(
                 move |bot: Bot, msg: Message| {
                     let sessions = sessions.clone();
                     let talk_sessions = talk_sessions.clone();
                     let picture_sessions = picture_sessions.clone();
                     let delete_mode = delete_mode.clone();
+                    let replies = replies.clone();
+                    let previews = previews.clone();
+                    let in_flight = in_flight.clone();
                     let use_chatgpt = use_chatgpt.clone();
                     let use_deepseek = use_deepseek.clone();
                     async move {
@@ -108,6 +209,9 @@ async fn main() {
                             &talk_sessions,
                             &picture_sessions,
                             &delete_mode,
+                            &replies,
+                            &previews,
+                            &in_flight,
                             &use_chatgpt,
                             &use_deepseek,
                         )
@@ -121,16 +225,115 @@ async fn main() {
             ),
         );
 
-    let mut dispatcher = Dispatcher::builder(bot, message_handler)
+    let edited_message_handler = Update::filter_edited_message().branch(
+        dptree::filter(|msg: Message| msg.text().is_some()).endpoint(
+            move |bot: Bot, msg: Message| {
+                let sessions = edit_sessions.clone();
+                let talk_sessions = edit_talk_sessions.clone();
+                let picture_sessions = edit_picture_sessions.clone();
+                let replies = edit_replies.clone();
+                let previews = edit_previews.clone();
+                let in_flight = edit_in_flight.clone();
+                let use_chatgpt = edit_use_chatgpt.clone();
+                let use_deepseek = edit_use_deepseek.clone();
+                async move {
+                    if let Err(e) = handle_edited_message(
+                        &bot,
+                        &msg,
+                        &sessions,
+                        &talk_sessions,
+                        &picture_sessions,
+                        &replies,
+                        &previews,
+                        &in_flight,
+                        &use_chatgpt,
+                        &use_deepseek,
+                    )
+                    .await
+                    {
+                        log::error!("Error: {:?}", e);
+                    }
+                    ResponseResult::Ok(())
+                }
+            },
+        ),
+    );
+
+    let callback_shutdown_tx = shutdown_tx.clone();
+    let callback_query_handler =
+        Update::filter_callback_query().endpoint(move |bot: Bot, query: CallbackQuery| {
+            let shutdown = callback_shutdown_tx.clone();
+            let pending_actions = pending_actions.clone();
+            let sessions = callback_sessions.clone();
+            let talk_sessions = callback_talk_sessions.clone();
+            let picture_sessions = callback_picture_sessions.clone();
+            let delete_mode = callback_delete_mode.clone();
+            let previews = callback_previews.clone();
+            let use_chatgpt = callback_use_chatgpt.clone();
+            let use_deepseek = callback_use_deepseek.clone();
+            async move {
+                if let Err(e) = handle_callback_query(
+                    &bot,
+                    &query,
+                    &shutdown,
+                    &pending_actions,
+                    &sessions,
+                    &talk_sessions,
+                    &picture_sessions,
+                    &delete_mode,
+                    &previews,
+                    &use_chatgpt,
+                    &use_deepseek,
+                )
+                .await
+                {
+                    log::error!("Error: {:?}", e);
+                }
+                ResponseResult::Ok(())
+            }
+        });
+
+    let handler = dptree::entry()
+        .branch(message_handler)
+        .branch(edited_message_handler)
+        .branch(callback_query_handler);
+
+    tokio::spawn(daily_story::run_daily_story_task(
+        bot.clone(),
+        use_chatgpt_story,
+        use_deepseek_story,
+    ));
+
+    let mut dispatcher = Dispatcher::builder(bot, handler)
         .enable_ctrlc_handler()
         .build();
 
     let mut rx = shutdown_tx.subscribe();
+    let shutdown_token = dispatcher.shutdown_token();
+
+    // On /exit stop polling and let in-flight handlers finish, but don't wait
+    // forever on a stuck one. Ctrl-C goes through the same dispatcher shutdown.
+    let drain_deadline = async move {
+        if rx.recv().await.is_err() {
+            return std::future::pending().await;
+        }
+        log::info!("Shutdown signal received, waiting for in-flight updates");
+        if shutdown_token.shutdown().is_err() {
+            log::warn!("Dispatcher was not running when shutdown was requested");
+        }
+        tokio::time::sleep(SHUTDOWN_DRAIN_TIMEOUT).await;
+    };
 
     tokio::select! {
-        _ = dispatcher.dispatch() => log::info!("Bot stopped normally"),
-        _ = rx.recv() => log::info!("Shutdown signal received"),
+        _ = dispatcher.dispatch() => log::info!("Bot stopped, all updates handled"),
+        _ = drain_deadline => log::warn!(
+            "In-flight updates did not finish within {:?}, exiting anyway",
+            SHUTDOWN_DRAIN_TIMEOUT
+        ),
     }
 
+    // Wait for any storage write that is still holding the lock
+    drop(translation::lock_storage());
+
     log::info!("Bot shutdown complete");
 }
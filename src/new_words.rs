@@ -0,0 +1,133 @@
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup},
+    Bot,
+};
+
+use crate::{
+    commands_messages::MAX_CALLBACK_DATA_LEN,
+    retry::SendRetrying,
+    translation::{
+        add_translation, canonical_key, format_translation_response, get_translation,
+        parse_word_reply, read_translations, translate_text,
+    },
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub const LEARN_CALLBACK_PREFIX: &str = "learn:";
+const MAX_SUGGESTIONS: usize = 8;
+/// Lowercase words at least this long are offered too, shorter ones are
+/// mostly everyday words.
+const MIN_UNCOMMON_LEN: usize = 7;
+
+/// Picks words from a user's German message that may be worth saving: nouns
+/// (capitalized mid-sentence) and longer words.
+pub fn collect_candidates(text: &str, candidates: &mut Vec<String>) {
+    let mut sentence_start = true;
+    for token in text.split_whitespace() {
+        let word = token.trim_matches(|c: char| !c.is_alphabetic());
+        let is_latin =
+            !word.is_empty() && word.chars().all(|c| c.is_alphabetic() && !is_cyrillic(c));
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+
+        if is_latin
+            && ((capitalized && !sentence_start) || word.chars().count() >= MIN_UNCOMMON_LEN)
+            && !candidates
+                .iter()
+                .any(|c| canonical_key(c) == canonical_key(word))
+        {
+            candidates.push(word.to_string());
+        }
+        sentence_start = token.ends_with(['.', '!', '?']);
+    }
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}')
+}
+
+/// Candidates that aren't in the vocabulary yet.
+fn find_new_words(candidates: &[String]) -> Result<Vec<String>> {
+    let translations = read_translations()?;
+    Ok(candidates
+        .iter()
+        .filter(|word| !translations.iter().any(|t| t.matches(word)))
+        .filter(|word| format!("{}{}", LEARN_CALLBACK_PREFIX, word).len() <= MAX_CALLBACK_DATA_LEN)
+        .take(MAX_SUGGESTIONS)
+        .cloned()
+        .collect())
+}
+
+/// After a talk or picture session, offers the new words the user wrote with
+/// a button each to add them.
+pub async fn offer_new_words(bot: &Bot, chat_id: ChatId, candidates: &[String]) -> Result<()> {
+    let new_words = find_new_words(candidates)?;
+    if new_words.is_empty() {
+        return Ok(());
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(new_words.iter().map(|word| {
+        vec![InlineKeyboardButton::callback(
+            format!("➕ {}", word),
+            format!("{}{}", LEARN_CALLBACK_PREFIX, word),
+        )]
+    }));
+    bot.send_message(
+        chat_id,
+        "🆕 Новые слова из ваших сообщений. Добавить в словарь?",
+    )
+    .reply_markup(keyboard)
+    .send_retrying()
+    .await?;
+    Ok(())
+}
+
+/// Translates and saves a word picked from the suggestions.
+pub async fn learn_word(
+    bot: &Bot,
+    chat_id: ChatId,
+    word: &str,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<()> {
+    if let Some(existing) = get_translation(word)? {
+        bot.send_message(chat_id, format_translation_response(&existing))
+            .send_retrying()
+            .await?;
+        return Ok(());
+    }
+
+    let response = translate_text(word, Some(chat_id.0), use_chatgpt, use_deepseek).await?;
+    let translation = match parse_word_reply(
+        word,
+        &response,
+        Some(chat_id.0),
+        use_chatgpt,
+        use_deepseek,
+    )
+    .await?
+    {
+        Ok(translation) => translation,
+        Err(e) => {
+            log::warn!("Not saving suggested word '{}': {}", word, e);
+            bot.send_message(chat_id, format!("❌ Could not translate '{}'.", word))
+                .send_retrying()
+                .await?;
+            return Ok(());
+        }
+    };
+
+    add_translation(translation.clone())?;
+    bot.send_message(
+        chat_id,
+        format!(
+            "✅ Добавлено:\n{}",
+            format_translation_response(&translation)
+        ),
+    )
+    .send_retrying()
+    .await?;
+    Ok(())
+}
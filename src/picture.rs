@@ -1,8 +1,11 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+use std::time::{Duration, Instant};
 use teloxide::{
     payloads::SendPhotoSetters,
     prelude::Requester,
@@ -12,31 +15,145 @@ use teloxide::{
 use tokio::sync::Mutex;
 use url::Url;
 
-use crate::ai::{make_claude_request, ClaudeMessage, ClaudeRequest};
+use crate::ai::{
+    first_text, http_client, make_claude_request, ClaudeMessage, ClaudeRequest, ServiceUnavailable,
+    SERVICE_UNAVAILABLE_MESSAGE,
+};
+use crate::new_words::{collect_candidates, offer_new_words};
+use crate::retry::SendRetrying;
+use crate::typing::TypingIndicator;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-const GRAMMAR_CHECK_PROMPT: &str = "Ты преподаватель немецкого. Проверь следующее описание фотографии на предмет грамматических ошибок и поправь их. Вот описание:\n\n";
+/// Pixabay throttles keys that search too often, so rounds that come in
+/// quicker than this keep the current image.
+const MIN_FETCH_INTERVAL: Duration = Duration::from_secs(20);
+const THROTTLED_MESSAGE: &str =
+    "Pixabay hat das Limit für neue Bilder erreicht. Bitte versuche es in einer Minute noch einmal.";
+
+const GRAMMAR_CHECK_PROMPT: &str = "Ты преподаватель немецкого. Проверь следующее описание фотографии на предмет грамматических ошибок и поправь их. В последней строке оцени описание по шкале от 1 до 10 в формате «Оценка: N/10». Вот описание:\n\n";
+
+/// Descriptions scored at least this well keep their image for review in
+/// curated mode.
+const GOOD_DESCRIPTION_SCORE: u32 = 8;
+const MAX_CURATED_PER_CATEGORY: usize = 5;
+/// Chance that a curated round brings back a remembered image instead of
+/// fetching a new one.
+const CURATED_REVIEW_CHANCE: f64 = 0.3;
 
 #[derive(Clone)]
 pub struct PictureSession {
     last_image_url: Option<String>,
+    /// Search term the current image was found with.
+    last_category: Option<String>,
+    last_fetch: Option<Instant>,
+    word_candidates: Vec<String>,
+    /// Started with `/pic curated`: well described images are remembered and
+    /// shown again now and then.
+    curated: bool,
 }
 
 impl PictureSession {
-    fn new() -> Self {
+    fn new(curated: bool) -> Self {
         Self {
             last_image_url: None,
+            last_category: None,
+            last_fetch: None,
+            word_candidates: Vec::new(),
+            curated,
         }
     }
+
+    /// The current image, if a new one was fetched too recently to ask again.
+    fn reusable_image(&self) -> Option<String> {
+        let recent = self
+            .last_fetch
+            .is_some_and(|fetched| fetched.elapsed() < MIN_FETCH_INTERVAL);
+        self.last_image_url.clone().filter(|_| recent)
+    }
 }
 
+/// Returned when Pixabay rejects a search because of its rate limit.
+#[derive(Debug)]
+struct PixabayThrottled;
+
+impl fmt::Display for PixabayThrottled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pixabay rate limit exceeded")
+    }
+}
+
+impl std::error::Error for PixabayThrottled {}
+
 pub type PictureSessions = Arc<Mutex<HashMap<i64, PictureSession>>>;
 
 #[derive(Deserialize)]
 struct PixabayImage {
+    #[serde(rename = "previewURL")]
+    preview_url: Option<String>,
     #[serde(rename = "webformatURL")]
-    webformat_url: String,
+    webformat_url: Option<String>,
+    #[serde(rename = "largeImageURL")]
+    large_image_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ImageSize {
+    Preview,
+    Webformat,
+    Large,
+}
+
+impl ImageSize {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "preview" | "previewurl" => Some(Self::Preview),
+            "webformat" | "webformaturl" => Some(Self::Webformat),
+            "large" | "largeimageurl" => Some(Self::Large),
+            _ => None,
+        }
+    }
+
+    fn from_env() -> Self {
+        match std::env::var("PIXABAY_IMAGE_SIZE") {
+            Ok(value) => Self::parse(&value).unwrap_or_else(|| {
+                log::warn!(
+                    "Invalid PIXABAY_IMAGE_SIZE '{}', expected preview, webformat or large. Using webformat.",
+                    value
+                );
+                Self::Webformat
+            }),
+            Err(_) => Self::Webformat,
+        }
+    }
+}
+
+impl PixabayImage {
+    fn url(&self, size: ImageSize) -> Option<&String> {
+        let preferred = match size {
+            ImageSize::Preview => &self.preview_url,
+            ImageSize::Webformat => &self.webformat_url,
+            ImageSize::Large => &self.large_image_url,
+        };
+        preferred.as_ref().or(self.webformat_url.as_ref())
+    }
+}
+
+fn safesearch_from_env() -> bool {
+    match std::env::var("PIXABAY_SAFESEARCH") {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "false" | "0" | "no" | "off" => false,
+            "true" | "1" | "yes" | "on" => true,
+            _ => {
+                log::warn!(
+                    "Invalid PIXABAY_SAFESEARCH '{}', expected true or false. Using true.",
+                    value
+                );
+                true
+            }
+        },
+        Err(_) => true,
+    }
 }
 
 fn get_random_search_params() -> (String, u32) {
@@ -46,21 +163,148 @@ fn get_random_search_params() -> (String, u32) {
     (search_term, page)
 }
 
-async fn fetch_random_image() -> Result<String> {
+/// An image from a curated round that was described well.
+#[derive(Clone, Serialize, Deserialize)]
+struct CuratedImage {
+    category: String,
+    url: String,
+}
+
+/// Serializes the read-modify-write of the curated images file.
+static CURATED_LOCK: StdMutex<()> = StdMutex::new(());
+
+/// Defaults to curated_images.json next to the translations storage file.
+fn get_curated_images_path() -> String {
+    std::env::var("CURATED_IMAGES_FILE").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("curated_images.json")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+/// Remembered images by chat id. Files from before images were kept per chat
+/// hold a plain list with no owner, so those images are dropped.
+fn read_curated_images() -> Result<HashMap<i64, Vec<CuratedImage>>> {
+    let path = get_curated_images_path();
+    if !Path::new(&path).exists() {
+        return Ok(HashMap::new());
+    }
+    let data = std::fs::read_to_string(&path)?;
+    if data.trim_start().starts_with('[') {
+        log::warn!("Dropping curated images saved without a chat id");
+        return Ok(HashMap::new());
+    }
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Keeps the image for the chat's reviews, dropping the oldest one of its
+/// category when the category is full.
+fn remember_image(chat_id: i64, category: &str, url: &str) -> Result<()> {
+    let _guard = CURATED_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut all_images = read_curated_images()?;
+    let images = all_images.entry(chat_id).or_default();
+    images.retain(|image| image.url != url);
+    images.push(CuratedImage {
+        category: category.to_string(),
+        url: url.to_string(),
+    });
+    while images.iter().filter(|i| i.category == category).count() > MAX_CURATED_PER_CATEGORY {
+        if let Some(oldest) = images.iter().position(|i| i.category == category) {
+            images.remove(oldest);
+        }
+    }
+
+    let path = get_curated_images_path();
+    let tmp_path = format!("{}.tmp", path);
+    std::fs::write(&tmp_path, serde_json::to_string(&all_images)?)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// One of the chat's remembered images other than the current one, if it's
+/// time for review.
+fn pick_curated_image(chat_id: i64, current: Option<&str>) -> Option<CuratedImage> {
+    let mut rng = rand::thread_rng();
+    if !rng.gen_bool(CURATED_REVIEW_CHANCE) {
+        return None;
+    }
+    let images = read_curated_images()
+        .inspect_err(|e| log::warn!("Failed to read curated images: {}", e))
+        .ok()?;
+    choose_curated_image(images.get(&chat_id)?, current, &mut rng).cloned()
+}
+
+/// Picks a category first and then an image in it, so a category with many
+/// remembered images doesn't crowd out the others.
+fn choose_curated_image<'a>(
+    images: &'a [CuratedImage],
+    current: Option<&str>,
+    rng: &mut impl Rng,
+) -> Option<&'a CuratedImage> {
+    let candidates: Vec<&CuratedImage> = images
+        .iter()
+        .filter(|image| Some(image.url.as_str()) != current)
+        .collect();
+    let mut categories: Vec<&str> = candidates
+        .iter()
+        .map(|image| image.category.as_str())
+        .collect();
+    categories.sort_unstable();
+    categories.dedup();
+    let category = *categories.choose(rng)?;
+    let in_category: Vec<&CuratedImage> = candidates
+        .into_iter()
+        .filter(|image| image.category == category)
+        .collect();
+    in_category.choose(rng).copied()
+}
+
+/// The "Оценка: N/10" line the grammar check ends with.
+fn parse_description_score(feedback: &str) -> Option<u32> {
+    let line = feedback
+        .lines()
+        .rev()
+        .find(|line| line.contains("Оценка"))?;
+    let (_, score) = line.split_once(':')?;
+    score
+        .trim()
+        .trim_start_matches(['*', '«'])
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Returns the search term along with the image URL.
+async fn fetch_random_image() -> Result<(String, String)> {
     let api_key = std::env::var("PIXABAY_API_KEY")?;
     let (search_term, page) = get_random_search_params();
+    let image_size = ImageSize::from_env();
 
     let url = format!(
-        "https://pixabay.com/api/?key={}&q={}&image_type=photo&safesearch=true&order=random&page={}&per_page=50",
-        api_key, search_term, page
+        "https://pixabay.com/api/?key={}&q={}&image_type=photo&safesearch={}&order=random&page={}&per_page=50",
+        api_key,
+        search_term,
+        safesearch_from_env(),
+        page
     );
 
-    let response = reqwest::get(&url).await?.json::<PixabayResponse>().await?;
+    let response = http_client().get(&url).send().await?;
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(PixabayThrottled.into());
+    }
+    let response = response
+        .error_for_status()?
+        .json::<PixabayResponse>()
+        .await?;
 
     response
         .hits
         .choose(&mut rand::thread_rng())
-        .map(|image| image.webformat_url.clone())
+        .and_then(|image| image.url(image_size).cloned())
+        .map(|url| (search_term, url))
         .ok_or_else(|| "No images found".into())
 }
 
@@ -77,13 +321,14 @@ async fn check_grammar(description: &str) -> Result<String> {
     };
 
     let response = make_claude_request(&request).await?;
-    Ok(response.content[0].text.clone())
+    first_text(&response)
 }
 
 pub async fn start_picture_session(
     bot: &Bot,
     msg: &Message,
     sessions: &PictureSessions,
+    curated: bool,
 ) -> Result<()> {
     let mut sessions = sessions.lock().await;
     let chat_id = msg.chat.id;
@@ -93,19 +338,39 @@ pub async fn start_picture_session(
             msg.chat.id,
             "Du bist bereits im Bildbeschreibungsmodus. Benutze /stoppic um den Modus zu beenden.",
         )
+        .send_retrying()
         .await?;
         return Ok(());
     }
 
-    let image_url = fetch_random_image().await?;
+    let (category, image_url) = match fetch_random_image().await {
+        Err(e) if e.is::<PixabayThrottled>() => {
+            bot.send_message(msg.chat.id, THROTTLED_MESSAGE)
+                .send_retrying()
+                .await?;
+            return Ok(());
+        }
+        result => result?,
+    };
     let url = Url::parse(&image_url)?;
 
+    if curated {
+        bot.send_message(
+            msg.chat.id,
+            "Kuratierter Modus: Bilder, die du gut beschreibst, kommen später zur Wiederholung zurück.",
+        )
+        .send_retrying()
+        .await?;
+    }
     bot.send_photo(msg.chat.id, InputFile::url(url))
         .caption("Bitte beschreibe dieses Bild auf Deutsch. Was siehst du? Was passiert im Bild?")
+        .send_retrying()
         .await?;
 
-    let mut session = PictureSession::new();
+    let mut session = PictureSession::new(curated);
     session.last_image_url = Some(image_url);
+    session.last_category = Some(category);
+    session.last_fetch = Some(Instant::now());
     sessions.insert(chat_id.0, session);
 
     Ok(())
@@ -119,14 +384,17 @@ pub async fn stop_picture_session(
     let mut sessions = sessions.lock().await;
     let chat_id = msg.chat.id;
 
-    if sessions.remove(&chat_id.0).is_some() {
+    if let Some(session) = sessions.remove(&chat_id.0) {
         bot.send_message(msg.chat.id, "Bildbeschreibungsmodus beendet.")
+            .send_retrying()
             .await?;
+        offer_new_words(bot, chat_id, &session.word_candidates).await?;
     } else {
         bot.send_message(
             msg.chat.id,
             "Du bist nicht im Bildbeschreibungsmodus. Benutze /pic um zu starten.",
         )
+        .send_retrying()
         .await?;
     }
 
@@ -136,23 +404,102 @@ pub async fn stop_picture_session(
 pub async fn handle_picture_message(
     bot: &Bot,
     msg: &Message,
+    text: &str,
     sessions: &PictureSessions,
 ) -> Result<()> {
-    if let Some(text) = msg.text() {
-        let feedback = check_grammar(text).await?;
-        bot.send_message(msg.chat.id, feedback).await?;
-
-        // Send a new image for the next round
-        let image_url = fetch_random_image().await?;
-        let url = Url::parse(&image_url)?;
-        bot.send_photo(msg.chat.id, InputFile::url(url))
-            .caption("Gut gemacht! Hier ist das nächste Bild. Was siehst du?")
+    let typing = TypingIndicator::start(bot, msg.chat.id);
+    let feedback = check_grammar(text).await;
+    drop(typing);
+    let feedback = match feedback {
+        Err(e) if e.is::<ServiceUnavailable>() => {
+            bot.send_message(msg.chat.id, SERVICE_UNAVAILABLE_MESSAGE)
+                .send_retrying()
+                .await?;
+            return Ok(());
+        }
+        result => result?,
+    };
+    let score = parse_description_score(&feedback);
+    bot.send_message(msg.chat.id, feedback)
+        .send_retrying()
+        .await?;
+
+    let (current_image, reusable_image, curated) =
+        match sessions.lock().await.get_mut(&msg.chat.id.0) {
+            Some(session) => {
+                collect_candidates(text, &mut session.word_candidates);
+                if let (true, Some(category), Some(url)) = (
+                    session.curated,
+                    &session.last_category,
+                    &session.last_image_url,
+                ) {
+                    if score.is_some_and(|score| score >= GOOD_DESCRIPTION_SCORE) {
+                        if let Err(e) = remember_image(msg.chat.id.0, category, url) {
+                            log::error!("Failed to remember curated image: {}", e);
+                        }
+                    }
+                }
+                (
+                    session.last_image_url.clone(),
+                    session.reusable_image(),
+                    session.curated,
+                )
+            }
+            None => return Ok(()),
+        };
+
+    // Bring back a well described image now and then, without asking Pixabay
+    if let Some(image) = curated
+        .then(|| pick_curated_image(msg.chat.id.0, current_image.as_deref()))
+        .flatten()
+    {
+        bot.send_photo(msg.chat.id, InputFile::url(Url::parse(&image.url)?))
+            .caption("Dieses Bild hast du schon einmal gut beschrieben. Wie würdest du es heute beschreiben?")
+            .send_retrying()
+            .await?;
+        if let Some(session) = sessions.lock().await.get_mut(&msg.chat.id.0) {
+            session.last_image_url = Some(image.url);
+            session.last_category = Some(image.category);
+        }
+        return Ok(());
+    }
+
+    if let Some(image_url) = reusable_image {
+        bot.send_photo(msg.chat.id, InputFile::url(Url::parse(&image_url)?))
+            .caption("Gut gemacht! Beschreibe dieses Bild gerne noch genauer.")
+            .send_retrying()
             .await?;
+        return Ok(());
+    }
 
-        let mut sessions = sessions.lock().await;
-        if let Some(session) = sessions.get_mut(&msg.chat.id.0) {
-            session.last_image_url = Some(image_url);
+    // Send a new image for the next round
+    let (category, image_url) = match fetch_random_image().await {
+        Err(e) if e.is::<PixabayThrottled>() => {
+            let message = match current_image {
+                Some(_) => format!(
+                    "{} Bis dahin kannst du das aktuelle Bild weiter beschreiben.",
+                    THROTTLED_MESSAGE
+                ),
+                None => THROTTLED_MESSAGE.to_string(),
+            };
+            bot.send_message(msg.chat.id, message)
+                .send_retrying()
+                .await?;
+            return Ok(());
         }
+        result => result?,
+    };
+    let url = Url::parse(&image_url)?;
+    bot.send_photo(msg.chat.id, InputFile::url(url))
+        .caption("Gut gemacht! Hier ist das nächste Bild. Was siehst du?")
+        .send_retrying()
+        .await?;
+
+    let mut sessions = sessions.lock().await;
+    if let Some(session) = sessions.get_mut(&msg.chat.id.0) {
+        session.last_image_url = Some(image_url);
+        session.last_category = Some(category);
+        session.last_fetch = Some(Instant::now());
     }
 
     Ok(())
@@ -167,3 +514,59 @@ const SEARCH_TERMS: &[&str] = &[
     "people", "city", "nature", "food", "animal", "travel", "building", "work", "sport", "family",
     "garden", "market",
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestStorage;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn image(category: &str, url: &str) -> CuratedImage {
+        CuratedImage {
+            category: category.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn remembered_images_belong_to_their_chat() {
+        let mut storage = TestStorage::new("curated-images");
+        let path = storage.sibling(".curated");
+        storage.env("CURATED_IMAGES_FILE", &path.to_string_lossy());
+
+        remember_image(1, "cat", "https://example.com/a.jpg").unwrap();
+        remember_image(2, "dog", "https://example.com/b.jpg").unwrap();
+        let images = read_curated_images().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(images[&1].len(), 1);
+        assert_eq!(images[&1][0].url, "https://example.com/a.jpg");
+        assert_eq!(images[&2].len(), 1);
+        assert_eq!(images[&2][0].category, "dog");
+    }
+
+    #[test]
+    fn each_category_comes_back_equally_often() {
+        let mut images: Vec<CuratedImage> = (0..5)
+            .map(|i| image("cat", &format!("https://example.com/cat{}.jpg", i)))
+            .collect();
+        images.push(image("dog", "https://example.com/dog.jpg"));
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let dogs = (0..1000)
+            .filter(|_| {
+                choose_curated_image(&images, None, &mut rng)
+                    .unwrap()
+                    .category
+                    == "dog"
+            })
+            .count();
+        assert!((400..600).contains(&dogs), "{}", dogs);
+
+        let only_current = [image("dog", "https://example.com/dog.jpg")];
+        assert!(
+            choose_curated_image(&only_current, Some("https://example.com/dog.jpg"), &mut rng)
+                .is_none()
+        );
+    }
+}
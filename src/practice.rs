@@ -1,23 +1,74 @@
-use std::fs;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
 
-use serde::Deserialize;
-use strsim::jaro_winkler;
-use teloxide::{prelude::Requester, types::Message, Bot};
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    payloads::{SendMessageSetters, SendVoiceSetters},
+    prelude::Requester,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message, MessageId},
+    Bot,
+};
 
-use crate::{translation::*, PracticeSessions};
+use crate::{
+    activity::record_answer,
+    ai::MISTAKE_EXPLANATION_PROMPT,
+    commands_messages::MAX_CALLBACK_DATA_LEN,
+    daily_set::mark_done,
+    input::fold_ascii_digraphs,
+    language::TargetLanguage,
+    retry::SendRetrying,
+    translation::*,
+    tts::{is_tts_configured, synthesize_speech},
+    user_config::{read_user_config, Similarity, UserConfig, WordKind},
+    PracticeSessions,
+};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-const SIMILARITY_THRESHOLD: f64 = 0.85;
-const STATS_INTERVAL: u32 = 10;
+const DRILL_REPETITIONS: u32 = 3;
+const CHOICE_COUNT: usize = 4;
+pub const CHOICE_CALLBACK_PREFIX: &str = "choice:";
+pub const LEECH_CALLBACK_PREFIX: &str = "leech:";
 const ARTICLES: [&str; 3] = ["der", "die", "das"];
+/// Wrong tries on one question before the answer is revealed and practice
+/// moves on.
+const MAX_ATTEMPTS_PER_QUESTION: u32 = 3;
+/// Wrong answers in quick succession share one explanation instead of each
+/// costing an API call.
+const MISTAKE_EXPLANATION_COOLDOWN: Duration = Duration::from_secs(15);
+/// How long a session reuses its loaded words and sentences before reading
+/// them from disk again.
+const PRACTICE_CACHE_TTL: Duration = Duration::from_secs(60);
+/// How far back `/practice recent` looks for newly saved words.
+const RECENT_WINDOW_DAYS: i64 = 7;
+/// Words drawn from by `/practice stale`, least recently practiced first.
+const STALE_WORD_COUNT: usize = 20;
+/// Longest list of missed words read out in the audio summary after /stop.
+const MAX_MISSED_AUDIO_WORDS: usize = 20;
 
 #[derive(Debug)]
 enum AnswerResult {
     Correct,
-    AlmostCorrect { expected: String, similarity: f64 },
-    WrongArticle { expected: String },
-    Wrong { expected: String },
+    AlmostCorrect {
+        expected: String,
+        similarity: f64,
+    },
+    WrongArticle {
+        expected: String,
+    },
+    /// The noun is right but the article was left out. Only with partial
+    /// credit enabled in the settings; counts as correct.
+    MissingArticle {
+        expected: String,
+    },
+    Wrong {
+        expected: String,
+    },
 }
 
 struct AnswerCheck {
@@ -40,7 +91,10 @@ impl AnswerCheck {
                 )
             }
             AnswerResult::WrongArticle { expected } => {
-                format!("❌ Неправильный артикль! Правильный ответ: {}", expected)
+                format!("❌ Неправильный артикль! Слово верно, нужно: {}", expected)
+            }
+            AnswerResult::MissingArticle { expected } => {
+                format!("✅ Слово верно, но не забудьте артикль: {}", expected)
             }
             AnswerResult::Wrong { expected } => {
                 format!("❌ Неправильно! Правильный ответ: {}", expected)
@@ -62,9 +116,249 @@ pub struct PracticeSession {
     current_sentence: Option<PracticeSentence>,
     practice_type: PracticeType,
     expecting_russian: bool,
+    options: PracticeOptions,
     words_practiced: u32,
     correct_answers: u32,
     wrong_answers: u32,
+    /// Options of the current multiple-choice question.
+    choices: Vec<String>,
+    correct_choice: usize,
+    /// Message holding the choice buttons, so stale presses can be ignored.
+    choice_message: Option<MessageId>,
+    config: UserConfig,
+    cache: Option<PracticeCache>,
+    /// German words answered wrong this session, for the audio summary.
+    missed_words: Vec<String>,
+    last_explanation: Option<Instant>,
+    /// Wrong tries on the current question.
+    attempts_on_current: u32,
+}
+
+/// Words and sentences loaded for a session, so consecutive questions don't
+/// each re-read the storage and the sentences file.
+#[derive(Clone)]
+struct PracticeCache {
+    translations: Vec<Translation>,
+    sentences: Vec<PracticeSentence>,
+    loaded_at: Instant,
+    version: u64,
+}
+
+impl PracticeCache {
+    fn is_fresh(&self) -> bool {
+        self.loaded_at.elapsed() < PRACTICE_CACHE_TTL && self.version == vocabulary_version()
+    }
+}
+
+impl PracticeSession {
+    /// Whether `other` is a copy of this session taken before any answer to
+    /// the current question was recorded.
+    fn is_same_question(&self, other: &PracticeSession) -> bool {
+        self.words_practiced == other.words_practiced
+            && self.current_word.original == other.current_word.original
+    }
+
+    fn new(options: PracticeOptions, config: UserConfig) -> Self {
+        Self {
+            current_word: Translation::default(),
+            current_sentence: None,
+            practice_type: PracticeType::WordTranslation,
+            expecting_russian: false,
+            options,
+            words_practiced: 0,
+            correct_answers: 0,
+            wrong_answers: 0,
+            choices: Vec::new(),
+            correct_choice: 0,
+            choice_message: None,
+            config,
+            cache: None,
+            missed_words: Vec::new(),
+            last_explanation: None,
+            attempts_on_current: 0,
+        }
+    }
+
+    /// The session's words and sentences, reloaded when the cache has expired
+    /// or the vocabulary changed since it was filled.
+    async fn practice_data(
+        &mut self,
+        bot: &Bot,
+        chat_id: ChatId,
+    ) -> Result<(Vec<Translation>, Vec<PracticeSentence>)> {
+        if let Some(cache) = self.cache.as_ref().filter(|cache| cache.is_fresh()) {
+            return Ok((cache.translations.clone(), cache.sentences.clone()));
+        }
+
+        let version = vocabulary_version();
+        let translations = self.options.filter_words(read_translations()?);
+        let mut sentences = load_practice_sentences_or_report(bot, chat_id).await;
+        sentences.extend(vocabulary_sentences(&translations));
+
+        self.cache = Some(PracticeCache {
+            translations: translations.clone(),
+            sentences: sentences.clone(),
+            loaded_at: Instant::now(),
+            version,
+        });
+        Ok((translations, sentences))
+    }
+
+    /// Correct answer to the current question, as shown when it's revealed.
+    fn current_answer(&self) -> String {
+        match (&self.practice_type, &self.current_sentence) {
+            (PracticeType::SentenceCompletion, Some(sentence)) => sentence.missing_word.clone(),
+            (PracticeType::MultipleChoice, _) if self.correct_choice < self.choices.len() => {
+                self.choices[self.correct_choice].clone()
+            }
+            _ if self.expecting_russian => format_meanings(&self.current_word.translation),
+            _ => self.current_word.with_article(),
+        }
+    }
+
+    fn record_missed_word(&mut self) {
+        let missed = match (&self.practice_type, &self.current_sentence) {
+            (PracticeType::SentenceCompletion, Some(sentence)) => sentence.missing_word.clone(),
+            _ => self.current_word.with_article(),
+        };
+        if !missed.is_empty() && !self.missed_words.contains(&missed) {
+            self.missed_words.push(missed);
+        }
+    }
+
+    /// Mirrors a stored statistics update in the cached words so the weighting
+    /// stays current without a reload.
+    fn record_cached_result(&mut self, word: &str, correct: bool) {
+        if let Some(cache) = self.cache.as_mut() {
+            if let Some(translation) = cache.translations.iter_mut().find(|t| t.matches(word)) {
+                translation.record_result(correct);
+            }
+        }
+    }
+}
+
+/// Fixed translation direction for a practice session, set via
+/// `/practice ru` or `/practice de`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PracticeDirection {
+    #[serde(rename = "ru")]
+    ToRussian,
+    #[serde(rename = "de")]
+    ToGerman,
+}
+
+impl PracticeDirection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "ru" => Some(Self::ToRussian),
+            "de" => Some(Self::ToGerman),
+            _ => None,
+        }
+    }
+}
+
+fn is_recent(translation: &Translation) -> bool {
+    translation
+        .added_at
+        .is_some_and(|added_at| unix_now() - added_at <= RECENT_WINDOW_DAYS * 24 * 60 * 60)
+}
+
+/// Settings chosen when starting practice, e.g. `/practice de #food`.
+#[derive(Clone, Debug, Default)]
+pub struct PracticeOptions {
+    pub direction: Option<PracticeDirection>,
+    pub tag: Option<String>,
+    /// Original of the single word practiced with `/drill`.
+    pub drill_word: Option<String>,
+    /// Only nouns, verbs or other words, from the chat's settings.
+    pub word_kind: Option<WordKind>,
+    /// Only words saved within the last week, from `/practice recent`.
+    pub recent: bool,
+    /// Only the words practiced longest ago, from `/practice stale`.
+    pub stale: bool,
+    /// Only words without a single answer yet, from `/neverpracticed`.
+    pub never_practiced: bool,
+    /// Keys of the `/today` words not yet answered correctly.
+    pub daily_words: Option<BTreeSet<String>>,
+}
+
+impl PracticeOptions {
+    pub fn parse(args: &str) -> std::result::Result<Self, String> {
+        let mut options = Self::default();
+        for arg in args.split_whitespace() {
+            if let Some(tag) = arg.strip_prefix('#') {
+                options.tag = Some(normalize_tag(tag));
+            } else if let Some(direction) = PracticeDirection::parse(arg) {
+                options.direction = Some(direction);
+            } else if arg.eq_ignore_ascii_case("recent") {
+                options.recent = true;
+            } else if arg.eq_ignore_ascii_case("stale") {
+                options.stale = true;
+            } else {
+                return Err(format!("Unknown practice option '{}'", arg));
+            }
+        }
+        Ok(options)
+    }
+
+    /// Fills in what the command didn't specify from the chat's settings.
+    fn apply_config(&mut self, config: &UserConfig) {
+        self.direction = self.direction.or(config.direction);
+        // A drill or the daily set is about specific words, whatever their kind
+        if self.drill_word.is_none() && self.daily_words.is_none() {
+            self.word_kind = config.word_kind;
+        }
+    }
+
+    /// Words eligible for this session.
+    fn filter_words(&self, translations: Vec<Translation>) -> Vec<Translation> {
+        let mut words: Vec<Translation> = translations
+            .into_iter()
+            .filter(|t| !t.archived)
+            .filter(|t| self.tag.as_ref().is_none_or(|tag| t.tags.contains(tag)))
+            .filter(|t| {
+                self.drill_word
+                    .as_ref()
+                    .is_none_or(|word| t.key() == canonical_key(word))
+            })
+            .filter(|t| self.word_kind.is_none_or(|kind| kind.matches(t)))
+            .filter(|t| !self.recent || is_recent(t))
+            .filter(|t| !self.never_practiced || t.correct_answers + t.wrong_answers == 0)
+            .filter(|t| {
+                self.daily_words
+                    .as_ref()
+                    .is_none_or(|words| words.contains(&t.key()))
+            })
+            .collect();
+        // Never practiced words sort first. The set is picked again whenever
+        // the session reloads, so practiced words make room for the next ones
+        if self.stale {
+            words.sort_by_key(|t| (t.last_practiced, t.added_at));
+            words.truncate(STALE_WORD_COUNT);
+        }
+        words
+    }
+
+    fn pick_practice_type(&self, has_sentences: bool) -> PracticeType {
+        use rand::Rng;
+
+        if self.drill_word.is_some() {
+            return PracticeType::WordTranslation;
+        }
+        match rand::thread_rng().gen_range(0..3) {
+            0 => PracticeType::MultipleChoice,
+            1 if has_sentences && self.daily_words.is_none() => PracticeType::SentenceCompletion,
+            _ => PracticeType::WordTranslation,
+        }
+    }
+}
+
+fn pick_expecting_russian(direction: Option<PracticeDirection>) -> bool {
+    match direction {
+        Some(PracticeDirection::ToRussian) => true,
+        Some(PracticeDirection::ToGerman) => false,
+        None => rand::random::<bool>(),
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -77,14 +371,68 @@ pub struct PracticeSentence {
 #[derive(Clone)]
 pub enum PracticeType {
     WordTranslation,
+    /// Word translation answered with inline buttons.
+    MultipleChoice,
     SentenceCompletion,
 }
 
-fn load_practice_sentences() -> Result<Vec<PracticeSentence>> {
-    let file_path = std::env::current_dir()?.join("practice_sentences.json");
+const PRACTICE_SENTENCES_FILE: &str = "practice_sentences.json";
+
+/// Modification time of the sentences file when it was last reported as
+/// unreadable, so the warning isn't repeated on every cache refresh.
+static REPORTED_SENTENCES_ERROR: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+fn practice_sentences_path() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(PRACTICE_SENTENCES_FILE))
+}
+
+fn load_practice_sentences(file_path: &Path) -> Result<Vec<PracticeSentence>> {
+    if !file_path.exists() {
+        fs::write(file_path, "[]")?;
+    }
     let file_content = fs::read_to_string(file_path)?;
-    let sentences: Vec<PracticeSentence> = serde_json::from_str(&file_content)?;
-    Ok(sentences)
+    serde_json::from_str(&file_content)
+        .map_err(|e| format!("{} is malformed: {}", PRACTICE_SENTENCES_FILE, e).into())
+}
+
+/// Whether this version of the file was already reported as unreadable.
+fn sentences_error_reported(file_path: &Path) -> bool {
+    let modified = fs::metadata(file_path).and_then(|m| m.modified()).ok();
+    let mut reported = REPORTED_SENTENCES_ERROR
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if modified.is_some() && *reported == modified {
+        return true;
+    }
+    *reported = modified;
+    false
+}
+
+/// Loads the practice sentences, telling the user (rather than only the log)
+/// when they can't be read. Practice continues with words only in that case.
+async fn load_practice_sentences_or_report(bot: &Bot, chat_id: ChatId) -> Vec<PracticeSentence> {
+    let file_path = match practice_sentences_path() {
+        Ok(file_path) => file_path,
+        Err(e) => {
+            log::error!("Failed to locate practice sentences: {}", e);
+            return Vec::new();
+        }
+    };
+    match load_practice_sentences(&file_path) {
+        Ok(sentences) => sentences,
+        Err(_) if sentences_error_reported(&file_path) => Vec::new(),
+        Err(e) => {
+            log::error!("Failed to load practice sentences: {}", e);
+            let message = format!(
+                "⚠️ Could not load practice sentences ({}). Practicing words only.",
+                e
+            );
+            if let Err(e) = bot.send_message(chat_id, message).send_retrying().await {
+                log::error!("Failed to send message: {}", e);
+            }
+            Vec::new()
+        }
+    }
 }
 
 fn get_random_sentence(sentences: &[PracticeSentence]) -> Option<PracticeSentence> {
@@ -93,6 +441,63 @@ fn get_random_sentence(sentences: &[PracticeSentence]) -> Option<PracticeSentenc
     sentences.choose(&mut rng).cloned()
 }
 
+/// Finds the headword (or an inflected form sharing its stem) in a German
+/// sentence and blanks it out. Returns the masked sentence and the word as
+/// it appears in the sentence.
+fn mask_word(sentence: &str, headword: &str) -> Option<(String, String)> {
+    let word = headword
+        .split_whitespace()
+        .find(|w| !ARTICLES.contains(&w.to_lowercase().as_str()) && *w != "sich")?
+        .to_lowercase();
+    let stem = word
+        .strip_suffix("en")
+        .or_else(|| word.strip_suffix('n'))
+        .or_else(|| word.strip_suffix('e'))
+        .filter(|stem| stem.chars().count() >= 3)
+        .unwrap_or(&word)
+        .to_string();
+
+    let found = sentence
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c: char| !c.is_alphabetic()))
+        .find(|token| {
+            let token = token.to_lowercase();
+            token == word || (stem.chars().count() >= 3 && token.starts_with(&stem))
+        })?;
+
+    // Only replace a whole-word occurrence, not a substring of another word
+    let (start, _) = sentence.match_indices(found).find(|(start, _)| {
+        let before = sentence[..*start].chars().next_back();
+        let after = sentence[start + found.len()..].chars().next();
+        !before.is_some_and(char::is_alphabetic) && !after.is_some_and(char::is_alphabetic)
+    })?;
+    let masked = format!(
+        "{}___{}",
+        &sentence[..start],
+        &sentence[start + found.len()..]
+    );
+    Some((masked, found.to_string()))
+}
+
+/// Builds cloze exercises from the German examples stored with the user's
+/// own vocabulary.
+fn vocabulary_sentences(translations: &[Translation]) -> Vec<PracticeSentence> {
+    translations
+        .iter()
+        .flat_map(|translation| {
+            translation.examples.iter().filter_map(|example| {
+                let (german_sentence, missing_word) =
+                    mask_word(&example.german, &translation.original)?;
+                Some(PracticeSentence {
+                    german_sentence,
+                    russian_translation: example.russian.clone(),
+                    missing_word,
+                })
+            })
+        })
+        .collect()
+}
+
 fn format_practice_question(translation: &Translation, expecting_russian: bool) -> String {
     if expecting_russian {
         if let Some(first_form) = translation.grammar_forms.first() {
@@ -134,97 +539,284 @@ pub async fn start_practice_session(
     bot: &Bot,
     msg: &Message,
     sessions: &PracticeSessions,
+    mut options: PracticeOptions,
 ) -> Result<()> {
-    let translations = read_translations()?;
-    let practice_sentences = load_practice_sentences()?;
+    let config = read_user_config(msg.chat.id.0).unwrap_or_else(|e| {
+        log::warn!("Failed to read settings for {}: {}", msg.chat.id.0, e);
+        UserConfig::default()
+    });
+    options.apply_config(&config);
+    let translations = options.filter_words(read_translations()?);
 
-    if translations.is_empty() || practice_sentences.is_empty() {
-        bot.send_message(msg.chat.id, "No words or practice sentences available!")
+    if let (true, Some(tag)) = (translations.is_empty(), &options.tag) {
+        bot.send_message(msg.chat.id, format!("No words tagged #{}!", tag))
+            .send_retrying()
             .await?;
         return Ok(());
     }
 
-    let practice_type = if rand::random() {
-        PracticeType::WordTranslation
-    } else {
-        PracticeType::SentenceCompletion
-    };
+    if let (true, Some(kind)) = (translations.is_empty(), options.word_kind) {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "No {} words to practice! Change it with /settings words=any",
+                kind
+            ),
+        )
+        .send_retrying()
+        .await?;
+        return Ok(());
+    }
 
-    let (question, session) = match practice_type {
-        PracticeType::WordTranslation => {
-            let translation = get_weighted_translation(&translations)
-                .ok_or("Failed to get weighted translation")?;
-            let expecting_russian = rand::random::<bool>();
-            let question = format_practice_question(&translation, expecting_russian);
-
-            (
-                question,
-                PracticeSession {
-                    current_word: translation,
-                    current_sentence: None,
-                    practice_type,
-                    expecting_russian,
-                    words_practiced: 0,
-                    correct_answers: 0,
-                    wrong_answers: 0,
-                },
+    if options.recent {
+        let message = if translations.is_empty() {
+            format!("No words added in the last {} days!", RECENT_WINDOW_DAYS)
+        } else {
+            format!(
+                "Found {} words added in the last {} days.",
+                translations.len(),
+                RECENT_WINDOW_DAYS
             )
+        };
+        bot.send_message(msg.chat.id, message)
+            .send_retrying()
+            .await?;
+    }
+
+    if translations.is_empty() {
+        if !options.recent {
+            bot.send_message(msg.chat.id, "No words available for practice!")
+                .send_retrying()
+                .await?;
+        }
+        return Ok(());
+    }
+
+    let mut sessions = sessions.lock().await;
+
+    let start_message = match (&options.drill_word, &options.daily_words) {
+        (Some(word), _) => format!(
+            "Drill started for '{}'! Answer correctly {} times to finish. Use /stop to end practice.",
+            word, DRILL_REPETITIONS
+        ),
+        (None, Some(_)) => format!(
+            "📅 Слова дня: осталось {} слов на сегодня. /skip — пропустить, /stop — закончить.",
+            translations.len()
+        ),
+        (None, None) => "Practice mode started! Use /skip to skip a question and /stop to end practice.".to_string(),
+    };
+    bot.send_message(msg.chat.id, start_message)
+        .send_retrying()
+        .await?;
+
+    let mut session = PracticeSession::new(options, config);
+    if ask_next_question(bot, msg.chat.id, &mut session).await? {
+        sessions.insert(msg.chat.id.0, session);
+    }
+
+    Ok(())
+}
+
+/// Picks the next exercise, stores it in the session and sends the question.
+/// Returns `false` if there was nothing to ask.
+async fn ask_next_question(
+    bot: &Bot,
+    chat_id: ChatId,
+    session: &mut PracticeSession,
+) -> Result<bool> {
+    let (translations, practice_sentences) = session.practice_data(bot, chat_id).await?;
+    let practice_type = session
+        .options
+        .pick_practice_type(!practice_sentences.is_empty());
+
+    session.choices.clear();
+    session.choice_message = None;
+    session.attempts_on_current = 0;
+
+    match practice_type {
+        PracticeType::WordTranslation | PracticeType::MultipleChoice => {
+            let just_asked = Some(session.current_word.original.as_str());
+            let skip_leeches =
+                session.config.suspend_leeches && session.options.drill_word.is_none();
+            let Some(next_translation) =
+                get_weighted_translation(&translations, just_asked, skip_leeches)
+            else {
+                return Ok(false);
+            };
+            // Drills alternate directions unless one is locked
+            let expecting_russian = match session.options.direction {
+                None if session.options.drill_word.is_some() && session.words_practiced > 0 => {
+                    !session.expecting_russian
+                }
+                direction => pick_expecting_russian(direction),
+            };
+            let mut question = format_practice_question(&next_translation, expecting_russian);
+            if has_elevated_weight(&next_translation) {
+                question.push_str("\n(повтор: высокая частота из-за ошибок)");
+            }
+
+            let choices = matches!(practice_type, PracticeType::MultipleChoice)
+                .then(|| build_choices(&next_translation, &translations, expecting_russian))
+                .flatten();
+
+            session.current_word = next_translation;
+            session.current_sentence = None;
+            session.expecting_russian = expecting_russian;
+
+            match choices {
+                Some((choices, correct_choice)) => {
+                    let sent = bot
+                        .send_message(chat_id, question)
+                        .reply_markup(choice_keyboard(&choices))
+                        .send_retrying()
+                        .await?;
+                    session.practice_type = PracticeType::MultipleChoice;
+                    session.choices = choices;
+                    session.correct_choice = correct_choice;
+                    session.choice_message = Some(sent.id);
+                }
+                None => {
+                    session.practice_type = PracticeType::WordTranslation;
+                    bot.send_message(chat_id, question).send_retrying().await?;
+                }
+            }
         }
         PracticeType::SentenceCompletion => {
-            let sentence = get_random_sentence(&practice_sentences)
-                .ok_or("Failed to get practice sentence")?;
+            let Some(sentence) = get_random_sentence(&practice_sentences) else {
+                return Ok(false);
+            };
             let question = format!(
                 "Заполните пропуск правильным словом:\n\n{}\n\nПеревод: {}",
                 sentence.german_sentence, sentence.russian_translation
             );
-
-            (
-                question,
-                PracticeSession {
-                    current_word: Translation::default(), // You'll need to implement Default for Translation
-                    current_sentence: Some(sentence),
-                    practice_type,
-                    expecting_russian: false,
-                    words_practiced: 0,
-                    correct_answers: 0,
-                    wrong_answers: 0,
-                },
-            )
+            session.current_sentence = Some(sentence);
+            session.current_word = Translation::default();
+            session.practice_type = practice_type;
+            bot.send_message(chat_id, question).send_retrying().await?;
         }
-    };
+    }
 
-    let mut sessions = sessions.lock().await;
-    sessions.insert(msg.chat.id.0, session);
+    Ok(true)
+}
 
-    bot.send_message(
-        msg.chat.id,
-        "Practice mode started! Use /stop to end practice.",
-    )
-    .await?;
-    bot.send_message(msg.chat.id, question).await?;
+/// How a word is shown as a multiple-choice option: its first Russian meaning,
+/// or the German original with its article.
+fn choice_label(translation: &Translation, expecting_russian: bool) -> String {
+    if expecting_russian {
+        split_meanings(&translation.translation)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| translation.translation.clone())
+    } else {
+        translation.with_article()
+    }
+}
 
-    Ok(())
+/// Returns the options in random order and the index of the correct one, or
+/// `None` if there aren't enough other words to draw distractors from.
+fn build_choices(
+    translation: &Translation,
+    translations: &[Translation],
+    expecting_russian: bool,
+) -> Option<(Vec<String>, usize)> {
+    use rand::{seq::SliceRandom, Rng};
+
+    let correct = choice_label(translation, expecting_russian);
+    let mut distractors: Vec<String> = translations
+        .iter()
+        .filter(|t| t.key() != translation.key())
+        .map(|t| choice_label(t, expecting_russian))
+        .filter(|label| !label.is_empty() && canonical_key(label) != canonical_key(&correct))
+        .collect();
+    distractors.sort();
+    distractors.dedup();
+    if distractors.len() < CHOICE_COUNT - 1 {
+        return None;
+    }
+
+    let mut rng = rand::thread_rng();
+    distractors.shuffle(&mut rng);
+    distractors.truncate(CHOICE_COUNT - 1);
+    let correct_choice = rng.gen_range(0..CHOICE_COUNT);
+    distractors.insert(correct_choice, correct);
+    Some((distractors, correct_choice))
 }
 
-fn check_answer(answer: &str, translation: &Translation, expecting_russian: bool) -> AnswerCheck {
-    let answer = normalize(answer);
+fn choice_keyboard(choices: &[String]) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(choices.iter().enumerate().map(|(i, choice)| {
+        vec![InlineKeyboardButton::callback(
+            choice.clone(),
+            format!("{}{}", CHOICE_CALLBACK_PREFIX, i),
+        )]
+    }))
+}
 
+fn check_answer(
+    answer: &str,
+    translation: &Translation,
+    expecting_russian: bool,
+    config: &UserConfig,
+) -> AnswerCheck {
     if expecting_russian {
-        check_russian_answer(answer, translation)
+        return check_russian_answer(normalize(answer), translation, config);
+    }
+
+    let check = check_german_answer(normalize(answer), translation, config);
+    if !config.ascii_digraphs || matches!(check.result, AnswerResult::Correct) {
+        return check;
+    }
+
+    // Try again with "Fussball" read as "Fußball"
+    let folded = fold_ascii_digraphs(answer);
+    if folded == answer {
+        return check;
+    }
+    let folded_check = check_german_answer(normalize(&folded), translation, config);
+    if matches!(
+        folded_check.result,
+        AnswerResult::Correct | AnswerResult::MissingArticle { .. }
+    ) {
+        folded_check
     } else {
-        check_german_answer(answer, translation)
+        check
     }
 }
 
-fn check_russian_answer(answer: String, translation: &Translation) -> AnswerCheck {
+/// Highest similarity of the answer to any variant, 0.0 for an empty answer
+/// or no variants. NaN scores are skipped rather than compared.
+fn best_similarity(similarity: Similarity, answer: &str, variants: &[String]) -> f64 {
+    if answer.is_empty() {
+        return 0.0;
+    }
+    variants
+        .iter()
+        .map(|variant| similarity.score(answer, variant))
+        .filter(|score| !score.is_nan())
+        .max_by(f64::total_cmp)
+        .unwrap_or(0.0)
+}
+
+fn check_russian_answer(
+    answer: String,
+    translation: &Translation,
+    config: &UserConfig,
+) -> AnswerCheck {
     let expected = normalize(&translation.translation);
-    let expected_variants: Vec<String> = translation
-        .translation
-        .split(',')
-        .map(normalize)
+    let expected_variants: Vec<String> = split_meanings(&translation.translation)
+        .iter()
+        .map(|meaning| normalize(meaning))
         .chain(translation.examples.iter().map(|ex| normalize(&ex.russian)))
+        .filter(|variant| !variant.is_empty())
         .collect();
 
+    if answer.is_empty() {
+        return AnswerCheck {
+            result: AnswerResult::Wrong {
+                expected: format_meanings(&translation.translation),
+            },
+            feedback: String::new(),
+        };
+    }
     if expected_variants.contains(&answer) || answer == expected {
         return AnswerCheck {
             result: AnswerResult::Correct,
@@ -232,16 +824,12 @@ fn check_russian_answer(answer: String, translation: &Translation) -> AnswerChec
         };
     }
 
-    let best_match = expected_variants
-        .iter()
-        .map(|variant| jaro_winkler(&answer, variant))
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap_or(0.0);
+    let best_match = best_similarity(config.similarity, &answer, &expected_variants);
 
-    if best_match > SIMILARITY_THRESHOLD {
+    if best_match > config.similarity_threshold {
         AnswerCheck {
             result: AnswerResult::AlmostCorrect {
-                expected: translation.translation.clone(),
+                expected: format_meanings(&translation.translation),
                 similarity: best_match,
             },
             feedback: String::new(),
@@ -249,14 +837,18 @@ fn check_russian_answer(answer: String, translation: &Translation) -> AnswerChec
     } else {
         AnswerCheck {
             result: AnswerResult::Wrong {
-                expected: translation.translation.clone(),
+                expected: format_meanings(&translation.translation),
             },
             feedback: String::new(),
         }
     }
 }
 
-fn check_german_answer(answer: String, translation: &Translation) -> AnswerCheck {
+fn check_german_answer(
+    answer: String,
+    translation: &Translation,
+    config: &UserConfig,
+) -> AnswerCheck {
     let is_noun = translation
         .grammar_forms
         .first()
@@ -264,13 +856,18 @@ fn check_german_answer(answer: String, translation: &Translation) -> AnswerCheck
         .unwrap_or(false);
 
     if is_noun {
-        check_german_noun_answer(answer, translation)
+        check_german_noun_answer(answer, translation, config)
     } else {
-        check_german_word_answer(answer, translation)
+        check_german_word_answer(answer, translation, config)
     }
 }
 
-fn check_german_noun_answer(answer: String, translation: &Translation) -> AnswerCheck {
+fn check_german_noun_answer(
+    answer: String,
+    translation: &Translation,
+    config: &UserConfig,
+) -> AnswerCheck {
+    let threshold = config.similarity_threshold;
     let expected_article = translation
         .grammar_forms
         .first()
@@ -279,41 +876,64 @@ fn check_german_noun_answer(answer: String, translation: &Translation) -> Answer
     let expected_noun = normalize(&translation.original);
     let expected = format!("{} {}", expected_article, expected_noun);
 
+    // Accept the article on either side of the noun: "das Haus" or "Haus, das"
     let parts: Vec<&str> = answer.split_whitespace().collect();
-    match parts.as_slice() {
-        [article, noun, ..] => {
-            if article.to_lowercase() != expected_article {
-                return AnswerCheck {
-                    result: AnswerResult::WrongArticle { expected },
-                    feedback: String::new(),
-                };
-            }
+    let (article, noun) = match parts.as_slice() {
+        [article, rest @ ..] if !rest.is_empty() && ARTICLES.contains(article) => {
+            (Some(*article), rest.join(" "))
+        }
+        [rest @ .., article] if !rest.is_empty() && ARTICLES.contains(article) => {
+            (Some(*article), rest.join(" "))
+        }
+        _ => (None, answer.clone()),
+    };
+    let similarity = config.similarity.score(&noun, &expected_noun);
+    let noun_matches = similarity > threshold;
 
-            let similarity = jaro_winkler(&normalize(noun), &expected_noun);
-            if similarity > SIMILARITY_THRESHOLD {
-                AnswerCheck {
-                    result: AnswerResult::Correct,
-                    feedback: String::new(),
-                }
+    let result = match article {
+        Some(article) if article == expected_article => {
+            if noun_matches {
+                AnswerResult::Correct
             } else {
-                AnswerCheck {
-                    result: AnswerResult::AlmostCorrect {
-                        expected: expected.clone(),
-                        similarity,
-                    },
-                    feedback: String::new(),
+                AnswerResult::AlmostCorrect {
+                    expected,
+                    similarity,
                 }
             }
         }
-        _ => AnswerCheck {
-            result: AnswerResult::Wrong { expected },
-            feedback: "Не забудьте указать артикль!".to_string(),
+        Some(_) if noun_matches => AnswerResult::WrongArticle {
+            expected: translation.with_article(),
         },
+        Some(_) => AnswerResult::Wrong { expected },
+        None if config.article_partial_credit && noun_matches => {
+            AnswerResult::MissingArticle { expected }
+        }
+        None => {
+            return AnswerCheck {
+                result: AnswerResult::Wrong { expected },
+                feedback: "Не забудьте указать артикль!".to_string(),
+            }
+        }
+    };
+
+    AnswerCheck {
+        result,
+        feedback: String::new(),
     }
 }
 
-fn check_german_word_answer(answer: String, translation: &Translation) -> AnswerCheck {
+fn check_german_word_answer(
+    answer: String,
+    translation: &Translation,
+    config: &UserConfig,
+) -> AnswerCheck {
+    // The German side may hold several synonyms, any of which is correct
     let mut correct_variants = vec![normalize(&translation.original)];
+    correct_variants.extend(
+        split_meanings(&translation.original)
+            .iter()
+            .map(|s| normalize(s)),
+    );
     if let Some(conjugations) = &translation.conjugations {
         correct_variants.extend(
             conjugations
@@ -330,21 +950,18 @@ fn check_german_word_answer(answer: String, translation: &Translation) -> Answer
             .flat_map(|ex| ex.german.split_whitespace())
             .map(normalize),
     );
+    correct_variants.retain(|variant| !variant.is_empty());
 
-    if correct_variants.contains(&answer) {
+    if !answer.is_empty() && correct_variants.contains(&answer) {
         return AnswerCheck {
             result: AnswerResult::Correct,
             feedback: String::new(),
         };
     }
 
-    let best_match = correct_variants
-        .iter()
-        .map(|variant| jaro_winkler(&answer, variant))
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap_or(0.0);
+    let best_match = best_similarity(config.similarity, &answer, &correct_variants);
 
-    if best_match > SIMILARITY_THRESHOLD {
+    if best_match > config.similarity_threshold {
         AnswerCheck {
             result: AnswerResult::AlmostCorrect {
                 expected: translation.original.clone(),
@@ -365,23 +982,47 @@ fn check_german_word_answer(answer: String, translation: &Translation) -> Answer
 pub async fn check_practice_answer(
     bot: &Bot,
     msg: &Message,
+    answer: &str,
     sessions: &PracticeSessions,
+    use_chatgpt: bool,
+    use_deepseek: bool,
 ) -> Result<()> {
-    let mut sessions = sessions.lock().await;
+    // The lock isn't held while the mistake explanation is generated, so other
+    // chats can keep practicing in the meantime.
+    let session = sessions.lock().await.get(&msg.chat.id.0).cloned();
 
-    if let Some(mut session) = sessions.get(&msg.chat.id.0).cloned() {
-        let answer = msg.text().unwrap_or("").trim();
+    if let Some(mut session) = session {
+        let answer = answer.trim();
         let (is_correct, feedback) = match &session.practice_type {
-            PracticeType::WordTranslation => {
-                let check_result =
-                    check_answer(answer, &session.current_word, session.expecting_russian);
-                let is_correct = matches!(check_result.result, AnswerResult::Correct);
+            PracticeType::WordTranslation | PracticeType::MultipleChoice => {
+                let mut check_result = check_answer(
+                    answer,
+                    &session.current_word,
+                    session.expecting_russian,
+                    &session.config,
+                );
+                let is_correct = matches!(
+                    check_result.result,
+                    AnswerResult::Correct | AnswerResult::MissingArticle { .. }
+                );
+                if !is_correct && session.config.explain_mistakes {
+                    add_mistake_explanation(
+                        &mut session,
+                        answer,
+                        &mut check_result,
+                        use_chatgpt,
+                        use_deepseek,
+                    )
+                    .await;
+                }
                 (is_correct, check_result.format_message())
             }
             PracticeType::SentenceCompletion => {
                 if let Some(sentence) = &session.current_sentence {
-                    let is_correct =
-                        answer.trim().to_lowercase() == sentence.missing_word.to_lowercase();
+                    let expected = sentence.missing_word.to_lowercase();
+                    let is_correct = answer.to_lowercase() == expected
+                        || (session.config.ascii_digraphs
+                            && fold_ascii_digraphs(answer).to_lowercase() == expected);
                     let feedback = if is_correct {
                         "✅ Правильно!".to_string()
                     } else {
@@ -397,95 +1038,412 @@ pub async fn check_practice_answer(
             }
         };
 
-        // Update statistics
-        session.words_practiced += 1;
-        if is_correct {
-            session.correct_answers += 1;
-        } else {
-            session.wrong_answers += 1;
+        let mut sessions = sessions.lock().await;
+        if !sessions
+            .get(&msg.chat.id.0)
+            .is_some_and(|current| current.is_same_question(&session))
+        {
+            log::info!(
+                "Practice in chat {} moved on while grading, dropping the answer",
+                msg.chat.id
+            );
+            return Ok(());
+        }
+        finish_answer(
+            bot,
+            msg.chat.id,
+            &mut sessions,
+            session,
+            is_correct,
+            feedback,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Appends a short AI explanation of a wrong answer to the feedback, at most
+/// once per cooldown. Failures only cost the explanation, not the answer.
+async fn add_mistake_explanation(
+    session: &mut PracticeSession,
+    answer: &str,
+    check: &mut AnswerCheck,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) {
+    if session
+        .last_explanation
+        .is_some_and(|at| at.elapsed() < MISTAKE_EXPLANATION_COOLDOWN)
+    {
+        return;
+    }
+    let expected = match &check.result {
+        AnswerResult::AlmostCorrect { expected, .. }
+        | AnswerResult::WrongArticle { expected }
+        | AnswerResult::Wrong { expected } => expected.clone(),
+        AnswerResult::Correct | AnswerResult::MissingArticle { .. } => return,
+    };
+    session.last_explanation = Some(Instant::now());
+
+    let language = session.config.target_language();
+    match explain_mistake(&expected, answer, language, use_chatgpt, use_deepseek).await {
+        Ok(explanation) => {
+            if !check.feedback.is_empty() {
+                check.feedback.push('\n');
+            }
+            check
+                .feedback
+                .push_str(&format!("💡 {}", explanation.trim()));
         }
+        Err(e) => log::warn!("Failed to explain practice mistake: {}", e),
+    }
+}
+
+async fn explain_mistake(
+    expected: &str,
+    answer: &str,
+    language: TargetLanguage,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<String> {
+    let prompt = MISTAKE_EXPLANATION_PROMPT
+        .replace("{expected}", expected)
+        .replace("{answer}", answer);
+
+    send_prompt(prompt, "", language, use_chatgpt, use_deepseek).await
+}
 
-        // Format response
-        let mut response = feedback;
-        if session.words_practiced % STATS_INTERVAL == 0 {
+/// Grades a press on one of the multiple-choice buttons.
+pub async fn check_choice_answer(
+    bot: &Bot,
+    message: &Message,
+    choice: &str,
+    sessions: &PracticeSessions,
+) -> Result<()> {
+    let chat_id = message.chat.id;
+    let mut sessions = sessions.lock().await;
+
+    let Some(session) = sessions.get(&chat_id.0).cloned() else {
+        return Ok(());
+    };
+    // Buttons of questions that were already answered do nothing
+    if session.choice_message != Some(message.id) {
+        return Ok(());
+    }
+    let Some(chosen) = choice
+        .parse::<usize>()
+        .ok()
+        .filter(|i| *i < session.choices.len())
+    else {
+        return Ok(());
+    };
+
+    if let Err(e) = bot
+        .edit_message_reply_markup(chat_id, message.id)
+        .send_retrying()
+        .await
+    {
+        log::warn!("Failed to remove the answered question's buttons: {}", e);
+    }
+
+    let is_correct = chosen == session.correct_choice;
+    let feedback = if is_correct {
+        "✅ Правильно!".to_string()
+    } else {
+        format!(
+            "❌ Неправильно! Правильный ответ: {}",
+            session.choices[session.correct_choice]
+        )
+    };
+
+    finish_answer(bot, chat_id, &mut sessions, session, is_correct, feedback).await
+}
+
+/// Records the answer, replies with feedback and moves on to the next question
+/// after a correct answer or too many wrong tries. Multiple choice reveals the
+/// answer, so it always moves on.
+async fn finish_answer(
+    bot: &Bot,
+    chat_id: ChatId,
+    sessions: &mut HashMap<i64, PracticeSession>,
+    mut session: PracticeSession,
+    is_correct: bool,
+    feedback: String,
+) -> Result<()> {
+    let is_choice = matches!(session.practice_type, PracticeType::MultipleChoice);
+    let question_over = count_attempt(&mut session, is_correct);
+    let mut response = feedback;
+    if !is_correct && !is_choice {
+        response.push_str(if question_over {
+            "\nПереходим к следующему вопросу."
+        } else {
+            "\nПопробуйте ещё раз или /skip"
+        });
+    }
+
+    if question_over {
+        record_question_result(chat_id.0, &mut session, is_correct)?;
+        if session
+            .words_practiced
+            .is_multiple_of(session.config.stats_interval)
+        {
             response.push_str(&format_practice_stats(&session));
         }
+    }
 
-        // Update word statistics in database if it's a word translation
-        if let PracticeType::WordTranslation = session.practice_type {
-            let word = if session.expecting_russian {
-                &session.current_word.original
-            } else {
-                &session.current_word.translation
-            };
-            update_translation_stats(word, is_correct)?;
+    let mut daily_remaining = None;
+    if is_correct && session.options.daily_words.is_some() {
+        let key = session.current_word.key();
+        if let Some(words) = session.options.daily_words.as_mut() {
+            words.remove(&key);
         }
+        if let Some(cache) = session.cache.as_mut() {
+            cache.translations.retain(|t| t.key() != key);
+        }
+        match mark_done(chat_id.0, &session.current_word.original) {
+            Ok(Some(remaining)) => {
+                if remaining > 0 {
+                    response.push_str(&format!("\nОсталось {} слов на сегодня.", remaining));
+                }
+                daily_remaining = Some(remaining);
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to update today's words: {}", e),
+        }
+    }
 
-        bot.send_message(msg.chat.id, response).await?;
+    bot.send_message(chat_id, response).send_retrying().await?;
 
-        // If correct, get next practice item
-        if is_correct {
-            let translations = read_translations()?;
-            let practice_sentences = load_practice_sentences()?;
+    if daily_remaining == Some(0) {
+        let message = format!(
+            "🎉 Все слова на сегодня пройдены! Завтра будет новый набор.\n{}",
+            format_practice_stats(&session)
+        );
+        bot.send_message(chat_id, message).send_retrying().await?;
+        sessions.remove(&chat_id.0);
+        return Ok(());
+    }
 
-            let practice_type = if rand::random() {
-                PracticeType::WordTranslation
-            } else {
-                PracticeType::SentenceCompletion
-            };
+    if question_over && !is_correct && !session.current_word.leech {
+        notify_if_leech(bot, chat_id, &mut session).await?;
+    }
 
-            let question = match practice_type {
-                PracticeType::WordTranslation => {
-                    if let Some(next_translation) = get_weighted_translation(&translations) {
-                        let expecting_russian = rand::random::<bool>();
-                        session.current_word = next_translation.clone();
-                        session.current_sentence = None;
-                        session.practice_type = practice_type;
-                        session.expecting_russian = expecting_russian;
-                        format_practice_question(&next_translation, expecting_russian)
-                    } else {
-                        return Ok(());
-                    }
-                }
-                PracticeType::SentenceCompletion => {
-                    if let Some(sentence) = get_random_sentence(&practice_sentences) {
-                        session.current_sentence = Some(sentence.clone());
-                        session.current_word = Translation::default();
-                        session.practice_type = practice_type;
-                        format!(
-                            "Заполните пропуск правильным словом:\n\n{}\n\nПеревод: {}",
-                            sentence.german_sentence, sentence.russian_translation
-                        )
-                    } else {
-                        return Ok(());
-                    }
-                }
-            };
+    // A drill ends once the word has been answered correctly enough times
+    if session.options.drill_word.is_some() && session.correct_answers >= DRILL_REPETITIONS {
+        let message = format!("🎯 Drill complete!\n{}", format_practice_stats(&session));
+        bot.send_message(chat_id, message).send_retrying().await?;
+        sessions.remove(&chat_id.0);
+        return Ok(());
+    }
 
-            bot.send_message(msg.chat.id, question).await?;
-        }
+    if question_over && !ask_next_question(bot, chat_id, &mut session).await? {
+        return end_practice_without_words(bot, chat_id, &session).await;
+    }
 
-        sessions.insert(msg.chat.id.0, session);
+    sessions.insert(chat_id.0, session);
+    Ok(())
+}
+
+/// Counts a try on the current question and returns whether the question is
+/// over. Multiple choice reveals the answer, the others allow a few more
+/// tries.
+fn count_attempt(session: &mut PracticeSession, is_correct: bool) -> bool {
+    if is_correct || matches!(session.practice_type, PracticeType::MultipleChoice) {
+        return true;
     }
+    session.attempts_on_current += 1;
+    session.attempts_on_current >= MAX_ATTEMPTS_PER_QUESTION
+}
 
+/// Records how a question ended in the session counters, the word's stored
+/// statistics and the activity log. Retries on the same question only count
+/// once, through this.
+fn record_question_result(
+    chat_id: i64,
+    session: &mut PracticeSession,
+    is_correct: bool,
+) -> Result<()> {
+    session.words_practiced += 1;
+    if is_correct {
+        session.correct_answers += 1;
+    } else {
+        session.wrong_answers += 1;
+        session.record_missed_word();
+    }
+
+    // Update word statistics in database if it's a word translation
+    if let PracticeType::WordTranslation | PracticeType::MultipleChoice = session.practice_type {
+        let word = if session.expecting_russian {
+            session.current_word.original.clone()
+        } else {
+            session.current_word.translation.clone()
+        };
+        update_translation_stats(&word, is_correct)?;
+        session.record_cached_result(&word, is_correct);
+    }
+    let practiced = match (&session.practice_type, &session.current_sentence) {
+        (PracticeType::SentenceCompletion, Some(sentence)) => sentence.missing_word.clone(),
+        _ => session.current_word.original.clone(),
+    };
+    if let Err(e) = record_answer(chat_id, &practiced, is_correct) {
+        log::error!("Failed to record practice activity: {}", e);
+    }
     Ok(())
 }
 
-pub async fn stop_practice_session(
+/// Ends a session that has nothing left to ask, with its final stats.
+async fn end_practice_without_words(
+    bot: &Bot,
+    chat_id: ChatId,
+    session: &PracticeSession,
+) -> Result<()> {
+    let message = format!(
+        "No words available for practice!\n{}",
+        format_practice_stats(session)
+    );
+    bot.send_message(chat_id, message).send_retrying().await?;
+    Ok(())
+}
+
+/// Tells the user when a wrong answer just made the current word a leech. The
+/// card is included so a note can be added by replying to it with `?:`.
+async fn notify_if_leech(bot: &Bot, chat_id: ChatId, session: &mut PracticeSession) -> Result<()> {
+    let Some(translation) = get_translation(&session.current_word.original)? else {
+        return Ok(());
+    };
+    if !translation.leech {
+        return Ok(());
+    }
+    session.current_word.leech = true;
+
+    let mut message = format!(
+        "{}\n\n🐌 Это слово даётся тяжело — добавить заметку? Ответьте на это сообщение «?: ваше пояснение».",
+        format_translation_response(&translation)
+    );
+    if !session.config.suspend_leeches {
+        bot.send_message(chat_id, message).send_retrying().await?;
+        return Ok(());
+    }
+
+    message.push_str("\nПока слово не будет появляться в практике. Список: /leeches");
+    let data = format!("{}{}", LEECH_CALLBACK_PREFIX, translation.original);
+    let keyboard = (data.len() <= MAX_CALLBACK_DATA_LEN).then(|| {
+        InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+            "✅ Вернуть в практику",
+            data,
+        )]])
+    });
+    match keyboard {
+        Some(keyboard) => {
+            bot.send_message(chat_id, message)
+                .reply_markup(keyboard)
+                .send_retrying()
+                .await?
+        }
+        None => bot.send_message(chat_id, message).send_retrying().await?,
+    };
+    Ok(())
+}
+
+/// Reveals the answer to the current question and moves on without grading.
+pub async fn skip_practice_question(
     bot: &Bot,
     msg: &Message,
     sessions: &PracticeSessions,
 ) -> Result<()> {
+    let chat_id = msg.chat.id;
     let mut sessions = sessions.lock().await;
-    if let Some(session) = sessions.get(&msg.chat.id.0) {
-        let stats = format_practice_stats(session);
-        let message = format!("Practice mode stopped!\n{}", stats);
-        bot.send_message(msg.chat.id, message).await?;
-    } else {
+    let Some(mut session) = sessions.remove(&chat_id.0) else {
+        bot.send_message(chat_id, "Практика не запущена. Начните с /practice")
+            .send_retrying()
+            .await?;
+        return Ok(());
+    };
+
+    // The session is out of the map here, so errors on the way must not end it
+    if let Some(choice_message) = session.choice_message {
+        if let Err(e) = bot
+            .edit_message_reply_markup(chat_id, choice_message)
+            .send_retrying()
+            .await
+        {
+            log::warn!("Failed to remove the skipped question's buttons: {}", e);
+        }
+    }
+    if let Err(e) = bot
+        .send_message(
+            chat_id,
+            format!(
+                "⏭ Пропущено. Правильный ответ: {}",
+                session.current_answer()
+            ),
+        )
+        .send_retrying()
+        .await
+    {
+        sessions.insert(chat_id.0, session);
+        return Err(e.into());
+    }
+    if let Err(e) = record_question_result(chat_id.0, &mut session, false) {
+        log::error!("Failed to record the skipped question: {}", e);
+    }
+
+    match ask_next_question(bot, chat_id, &mut session).await {
+        Ok(true) => {
+            sessions.insert(chat_id.0, session);
+            Ok(())
+        }
+        Ok(false) => end_practice_without_words(bot, chat_id, &session).await,
+        Err(e) => {
+            sessions.insert(chat_id.0, session);
+            Err(e)
+        }
+    }
+}
+
+pub async fn stop_practice_session(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &PracticeSessions,
+) -> Result<()> {
+    let session = sessions.lock().await.remove(&msg.chat.id.0);
+    let Some(session) = session else {
         bot.send_message(msg.chat.id, "Practice mode stopped!")
+            .send_retrying()
             .await?;
+        return Ok(());
+    };
+
+    let stats = format_practice_stats(&session);
+    let message = format!("Practice mode stopped!\n{}", stats);
+    bot.send_message(msg.chat.id, message)
+        .send_retrying()
+        .await?;
+
+    if !session.missed_words.is_empty() && is_tts_configured() {
+        send_missed_words_audio(bot, msg.chat.id, &session.missed_words).await?;
+    }
+    Ok(())
+}
+
+/// Reads the session's missed words out in one voice message.
+async fn send_missed_words_audio(bot: &Bot, chat_id: ChatId, words: &[String]) -> Result<()> {
+    let words = &words[..words.len().min(MAX_MISSED_AUDIO_WORDS)];
+    match synthesize_speech(&words.join(". ")).await {
+        Ok(audio) => {
+            bot.send_voice(chat_id, InputFile::memory(audio))
+                .caption(format!("🔊 Ошибки этой сессии:\n{}", words.join("\n")))
+                .send_retrying()
+                .await?;
+        }
+        Err(e) => {
+            log::error!("Failed to generate missed words audio: {}", e);
+            bot.send_message(chat_id, format!("❌ Error generating audio: {}", e))
+                .send_retrying()
+                .await?;
+        }
     }
-    sessions.remove(&msg.chat.id.0);
     Ok(())
 }
 
@@ -505,3 +1463,140 @@ fn format_practice_stats(session: &PracticeSession) -> String {
     )
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestStorage;
+
+    fn temp_sentences_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("zungenrede-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn noun(original: &str, article: &str, translation: &str) -> Translation {
+        Translation {
+            original: original.to_string(),
+            translation: translation.to_string(),
+            grammar_forms: vec![article.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn noun_answers_accept_either_order_and_casing() {
+        let haus = noun("Haus", "das", "дом");
+        for answer in ["das Haus", "Das haus", "Haus das", "Haus, das"] {
+            let check = check_answer(answer, &haus, false, &UserConfig::default());
+            assert!(matches!(check.result, AnswerResult::Correct), "{}", answer);
+        }
+    }
+
+    #[test]
+    fn wrong_article_shows_the_expected_one() {
+        let haus = noun("Haus", "das", "дом");
+        let check = check_answer("die Haus", &haus, false, &UserConfig::default());
+        match check.result {
+            AnswerResult::WrongArticle { expected } => assert_eq!(expected, "das Haus"),
+            other => panic!("expected a wrong article, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_sentences_file_is_created_empty() {
+        let path = temp_sentences_file("sentences-missing");
+        assert!(load_practice_sentences(&path).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn malformed_sentences_file_is_reported_once() {
+        let path = temp_sentences_file("sentences-malformed");
+        fs::write(&path, "[{\"german_sentence\": ").unwrap();
+
+        let error = load_practice_sentences(&path).unwrap_err();
+        assert!(error.to_string().contains("is malformed"));
+        assert!(!sentences_error_reported(&path));
+        assert!(sentences_error_reported(&path));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn answers_graded_against_a_stale_question_are_detected() {
+        let mut stored = PracticeSession::new(PracticeOptions::default(), UserConfig::default());
+        stored.current_word = noun("Haus", "das", "дом");
+        let snapshot = stored.clone();
+        assert!(stored.is_same_question(&snapshot));
+
+        stored.words_practiced += 1;
+        assert!(!stored.is_same_question(&snapshot));
+
+        let mut next = snapshot.clone();
+        next.current_word = noun("Katze", "die", "кошка");
+        assert!(!next.is_same_question(&snapshot));
+    }
+
+    #[test]
+    fn empty_answers_and_variants_score_zero() {
+        let variants = vec!["дом".to_string()];
+        for similarity in [
+            Similarity::JaroWinkler,
+            Similarity::Levenshtein,
+            Similarity::Exact,
+        ] {
+            assert_eq!(best_similarity(similarity, "", &variants), 0.0);
+            assert_eq!(best_similarity(similarity, "дом", &[]), 0.0);
+        }
+    }
+
+    #[test]
+    fn punctuation_only_answers_are_wrong() {
+        let haus = noun("Haus", "das", "дом");
+        for answer in ["?!", " ... ", "-"] {
+            let check = check_answer(answer, &haus, true, &UserConfig::default());
+            assert!(
+                matches!(check.result, AnswerResult::Wrong { .. }),
+                "{}",
+                answer
+            );
+        }
+    }
+
+    #[test]
+    fn entries_without_variants_never_match() {
+        let empty = Translation::default();
+        let config = UserConfig::default();
+        let russian = check_answer("дом", &empty, true, &config);
+        assert!(matches!(russian.result, AnswerResult::Wrong { .. }));
+        let german = check_answer("Haus", &empty, false, &config);
+        assert!(matches!(german.result, AnswerResult::Wrong { .. }));
+    }
+
+    #[test]
+    fn retries_count_once_per_question() {
+        let mut storage = TestStorage::new("practice-retries");
+        let activity = storage.sibling(".activity");
+        storage.env("PRACTICE_ACTIVITY_FILE", &activity.to_string_lossy());
+        add_translation(noun("Haus", "das", "дом")).unwrap();
+
+        let mut session = PracticeSession::new(PracticeOptions::default(), UserConfig::default());
+        session.current_word = noun("Haus", "das", "дом");
+        session.expecting_russian = true;
+        let mut finished = 0;
+        for _ in 0..MAX_ATTEMPTS_PER_QUESTION {
+            if count_attempt(&mut session, false) {
+                record_question_result(1, &mut session, false).unwrap();
+                finished += 1;
+            }
+        }
+        let _ = fs::remove_file(&activity);
+
+        assert_eq!(finished, 1);
+        assert_eq!(session.words_practiced, 1);
+        assert_eq!(session.wrong_answers, 1);
+        assert_eq!(session.attempts_on_current, MAX_ATTEMPTS_PER_QUESTION);
+        assert_eq!(get_translation("Haus").unwrap().unwrap().wrong_answers, 1);
+    }
+}
@@ -0,0 +1,39 @@
+use std::future::Future;
+
+use teloxide::{
+    requests::{Output, Request},
+    RequestError,
+};
+
+/// Extra attempts after Telegram asks us to slow down.
+const MAX_FLOOD_RETRIES: u32 = 2;
+
+/// Sends a request, waiting out Telegram's flood control (429 with a
+/// retry-after) instead of failing.
+pub trait SendRetrying: Request<Err = RequestError> + Send + Sync + Sized
+where
+    Output<Self>: Send,
+{
+    fn send_retrying(self) -> impl Future<Output = Result<Output<Self>, RequestError>> + Send {
+        async move {
+            let mut retries = 0;
+            loop {
+                match self.send_ref().await {
+                    Err(RequestError::RetryAfter(wait)) if retries < MAX_FLOOD_RETRIES => {
+                        log::warn!("Hit Telegram flood control, retrying in {:?}", wait);
+                        tokio::time::sleep(wait).await;
+                        retries += 1;
+                    }
+                    result => return result,
+                }
+            }
+        }
+    }
+}
+
+impl<R> SendRetrying for R
+where
+    R: Request<Err = RequestError> + Send + Sync,
+    Output<R>: Send,
+{
+}
@@ -0,0 +1,71 @@
+use std::{env, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const MAX_LOGGED_SENTENCES: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggedSentence {
+    pub original: String,
+    pub translation: String,
+}
+
+/// Sentence logging is opt-in via LOG_SENTENCES=true.
+pub fn is_sentence_log_enabled() -> bool {
+    env::var("LOG_SENTENCES")
+        .map(|value| matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Defaults to sentences_log.json next to the translations storage file.
+pub fn get_sentence_log_path() -> String {
+    env::var("SENTENCES_LOG_FILE").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("sentences_log.json")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+pub fn read_logged_sentences() -> Result<Vec<LoggedSentence>> {
+    let path = get_sentence_log_path();
+    if !Path::new(&path).exists() {
+        return Ok(Vec::new());
+    }
+    let data = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+pub fn log_sentence(original: &str, translation: &str) -> Result<()> {
+    let mut sentences = read_logged_sentences()?;
+    sentences.retain(|s| s.original != original);
+    sentences.push(LoggedSentence {
+        original: original.to_string(),
+        translation: translation.to_string(),
+    });
+
+    if sentences.len() > MAX_LOGGED_SENTENCES {
+        let overflow = sentences.len() - MAX_LOGGED_SENTENCES;
+        sentences.drain(..overflow);
+    }
+
+    let path = get_sentence_log_path();
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string(&sentences)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+pub fn search_logged_sentences(query: &str) -> Result<Vec<LoggedSentence>> {
+    let query = query.to_lowercase();
+    Ok(read_logged_sentences()?
+        .into_iter()
+        .filter(|s| {
+            s.original.to_lowercase().contains(&query)
+                || s.translation.to_lowercase().contains(&query)
+        })
+        .collect())
+}
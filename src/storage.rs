@@ -0,0 +1,408 @@
+use std::{
+    env, fs,
+    path::Path,
+    sync::{Mutex, OnceLock, PoisonError},
+    time::SystemTime,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::translation::{
+    canonical_key, find_by_original, find_by_translation, get_storage_path, insert_deduplicated,
+    Translation,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Where translations are persisted. Callers hold `lock_storage()` around
+/// read-modify-write cycles; the default methods are built on `read`/`write`
+/// and backends override them where they can do better.
+pub trait Storage: Send + Sync {
+    fn read(&self) -> Result<Vec<Translation>>;
+
+    fn write(&self, translations: &[Translation]) -> Result<()>;
+
+    /// When the backing file was last written, if it can be told.
+    fn last_modified(&self) -> Option<SystemTime>;
+
+    fn find(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(self.read()?.into_iter().find(|t| t.matches(word)))
+    }
+
+    fn find_by_original(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(find_by_original(word, &self.read()?).cloned())
+    }
+
+    fn find_by_translation(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(find_by_translation(word, &self.read()?).cloned())
+    }
+
+    /// Saves the entry, replacing any with the same original.
+    fn upsert(&self, translation: Translation) -> Result<()> {
+        let mut translations = self.read()?;
+        insert_deduplicated(&mut translations, translation);
+        self.write(&translations)
+    }
+
+    fn delete(&self, word: &str) -> Result<bool> {
+        let mut translations = self.read()?;
+        let initial_len = translations.len();
+        translations.retain(|t| !t.matches(word));
+        self.write(&translations)?;
+        Ok(initial_len != translations.len())
+    }
+
+    fn update_stats(&self, word: &str, correct: bool) -> Result<()> {
+        let mut translations = self.read()?;
+        if let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) {
+            translation.record_result(correct);
+            self.write(&translations)?;
+        }
+        Ok(())
+    }
+}
+
+static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+/// Opens the backend selected by STORAGE_BACKEND ("json" by default, or
+/// "sqlite"). Call once at startup so a broken database fails early.
+pub fn init_storage() -> Result<()> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or_default();
+    let (name, storage): (&str, Box<dyn Storage>) = match backend.trim().to_lowercase().as_str() {
+        "" | "json" => ("JSON", Box::new(JsonStorage)),
+        "sqlite" => ("SQLite", Box::new(SqliteStorage::open(&get_sqlite_path())?)),
+        other => return Err(format!("Unknown STORAGE_BACKEND '{}'", other).into()),
+    };
+    log::info!("Using {} storage backend", name);
+    STORAGE
+        .set(storage)
+        .map_err(|_| "Storage is already initialized")?;
+    Ok(())
+}
+
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.get_or_init(|| Box::new(JsonStorage)).as_ref()
+}
+
+/// The whole vocabulary in one JSON file (STORAGE_FILE), rewritten on save.
+pub struct JsonStorage;
+
+fn backup_path(path: &str) -> String {
+    format!("{}.bak", path)
+}
+
+/// Any failure counts as corruption, including a file cut in the middle of a
+/// multi-byte character, which isn't valid UTF-8.
+fn read_storage_file(path: &str) -> Result<Vec<Translation>> {
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn file_modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Storage for JsonStorage {
+    fn last_modified(&self) -> Option<SystemTime> {
+        file_modified(&get_storage_path())
+    }
+
+    fn read(&self) -> Result<Vec<Translation>> {
+        let path = get_storage_path();
+        if !Path::new(&path).exists() {
+            fs::write(&path, "[]")?;
+        }
+        match read_storage_file(&path) {
+            Ok(translations) => Ok(translations),
+            Err(e) => {
+                // The main file is unreadable or corrupt, fall back to the copy
+                // from the last successful save
+                match read_storage_file(&backup_path(&path)) {
+                    Ok(translations) => {
+                        log::warn!(
+                            "Storage file {} is corrupt ({}), using backup with {} entries",
+                            path,
+                            e,
+                            translations.len()
+                        );
+                        Ok(translations)
+                    }
+                    Err(_) => Err(e),
+                }
+            }
+        }
+    }
+
+    fn write(&self, translations: &[Translation]) -> Result<()> {
+        let path = get_storage_path();
+        let tmp_path = format!("{}.tmp", path);
+        let data = serde_json::to_string(translations)?;
+
+        // Write to a temporary file and rename it into place so a crash mid-write
+        // never leaves a truncated database behind
+        fs::write(&tmp_path, &data)?;
+
+        // Keep the previous save as the backup before replacing it, unless
+        // it's corrupt and would overwrite a good backup
+        if Path::new(&path).exists() {
+            match read_storage_file(&path) {
+                Ok(_) => {
+                    if let Err(e) = fs::copy(&path, backup_path(&path)) {
+                        log::warn!("Failed to write storage backup: {}", e);
+                    }
+                }
+                Err(e) => log::warn!("Not backing up corrupt storage file {}: {}", path, e),
+            }
+        }
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// Defaults to translations_storage.db next to the JSON storage file.
+fn get_sqlite_path() -> String {
+    env::var("SQLITE_FILE").unwrap_or_else(|_| {
+        Path::new(&get_storage_path())
+            .with_extension("db")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+/// One row per word in a `translations` table, so single-word changes don't
+/// rewrite the whole vocabulary. Entries are stored as JSON alongside the
+/// canonical keys used for lookups.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+const SCHEMA_VERSION: i32 = 1;
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS translations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT NOT NULL UNIQUE,
+                translation_key TEXT NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS translations_translation_key
+                ON translations (translation_key);",
+        )?;
+
+        let storage = Self {
+            conn: Mutex::new(conn),
+        };
+        storage.migrate_from_json()?;
+        Ok(storage)
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// On first start, imports the existing JSON storage file into the database.
+    fn migrate_from_json(&self) -> Result<()> {
+        let version: i32 = self
+            .conn()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        if Path::new(&get_storage_path()).exists() {
+            let translations = JsonStorage.read()?;
+            if !translations.is_empty() {
+                log::info!(
+                    "Migrating {} translations from {} to SQLite",
+                    translations.len(),
+                    get_storage_path()
+                );
+                self.write(&translations)?;
+            }
+        }
+
+        self.conn()
+            .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+        Ok(())
+    }
+
+    fn find_row(conn: &Connection, word: &str) -> Result<Option<(i64, Translation)>> {
+        Self::find_row_where(conn, "key = ?1 OR translation_key = ?1", word)
+    }
+
+    fn find_row_where(
+        conn: &Connection,
+        condition: &str,
+        word: &str,
+    ) -> Result<Option<(i64, Translation)>> {
+        let key = canonical_key(word);
+        let row = conn
+            .query_row(
+                &format!(
+                    "SELECT id, data FROM translations WHERE {} ORDER BY id LIMIT 1",
+                    condition
+                ),
+                params![key],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        match row {
+            Some((id, data)) => Ok(Some((id, serde_json::from_str(&data)?))),
+            None => Ok(None),
+        }
+    }
+}
+
+fn insert_row(conn: &Connection, translation: &Translation) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO translations (key, translation_key, data) VALUES (?1, ?2, ?3)",
+        params![
+            translation.key(),
+            canonical_key(&translation.translation),
+            serde_json::to_string(translation)?
+        ],
+    )?;
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn last_modified(&self) -> Option<SystemTime> {
+        file_modified(&get_sqlite_path())
+    }
+
+    fn read(&self) -> Result<Vec<Translation>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT data FROM translations ORDER BY id")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut translations = Vec::new();
+        for data in rows {
+            translations.push(serde_json::from_str(&data?)?);
+        }
+        Ok(translations)
+    }
+
+    fn write(&self, translations: &[Translation]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM translations", [])?;
+        for translation in translations {
+            insert_row(&tx, translation)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn find(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(Self::find_row(&self.conn(), word)?.map(|(_, translation)| translation))
+    }
+
+    fn find_by_original(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(Self::find_row_where(&self.conn(), "key = ?1", word)?.map(|(_, t)| t))
+    }
+
+    fn find_by_translation(&self, word: &str) -> Result<Option<Translation>> {
+        Ok(Self::find_row_where(&self.conn(), "translation_key = ?1", word)?.map(|(_, t)| t))
+    }
+
+    fn upsert(&self, translation: Translation) -> Result<()> {
+        // The key column is unique, so this replaces an entry with the same original
+        insert_row(&self.conn(), &translation)
+    }
+
+    fn delete(&self, word: &str) -> Result<bool> {
+        let deleted = self.conn().execute(
+            "DELETE FROM translations WHERE key = ?1 OR translation_key = ?1",
+            params![canonical_key(word)],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn update_stats(&self, word: &str, correct: bool) -> Result<()> {
+        let conn = self.conn();
+        if let Some((id, mut translation)) = Self::find_row(&conn, word)? {
+            translation.record_result(correct);
+            conn.execute(
+                "UPDATE translations SET data = ?1 WHERE id = ?2",
+                params![serde_json::to_string(&translation)?, id],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestStorage;
+
+    fn word(original: &str, translation: &str) -> Translation {
+        Translation {
+            original: original.to_string(),
+            translation: translation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn json_storage_roundtrip() {
+        let _storage = TestStorage::new("json-roundtrip");
+        let translations = vec![word("Haus", "дом"), word("Baum", "дерево")];
+        JsonStorage.write(&translations).unwrap();
+
+        let read = JsonStorage.read().unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].original, "Haus");
+        assert_eq!(read[1].translation, "дерево");
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_previous_save() {
+        let storage = TestStorage::new("json-corrupt");
+        JsonStorage.write(&[word("Haus", "дом")]).unwrap();
+        JsonStorage
+            .write(&[word("Haus", "дом"), word("Baum", "дерево")])
+            .unwrap();
+
+        // A crash mid-write leaves a truncated file behind
+        let data = fs::read(&storage.path).unwrap();
+        fs::write(&storage.path, &data[..data.len() / 2]).unwrap();
+
+        let read = JsonStorage.read().unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].original, "Haus");
+    }
+
+    #[test]
+    fn file_cut_inside_a_character_falls_back_to_backup() {
+        let storage = TestStorage::new("json-cut-utf8");
+        JsonStorage.write(&[word("Haus", "дом")]).unwrap();
+        JsonStorage
+            .write(&[word("Haus", "дом"), word("Baum", "дерево")])
+            .unwrap();
+
+        // Cut after the first byte of the two-byte "д" in "дерево"
+        let data = fs::read(&storage.path).unwrap();
+        let cut = data
+            .windows(2)
+            .rposition(|pair| pair == "д".as_bytes())
+            .unwrap()
+            + 1;
+        fs::write(&storage.path, &data[..cut]).unwrap();
+
+        let read = JsonStorage.read().unwrap();
+        assert_eq!(read.len(), 1);
+
+        // Saving over the corrupt file keeps the good backup
+        JsonStorage
+            .write(&[word("Haus", "дом"), word("Katze", "кошка")])
+            .unwrap();
+        let backup = read_storage_file(&backup_path(&storage.path.to_string_lossy())).unwrap();
+        assert_eq!(backup.len(), 1);
+        assert_eq!(backup[0].original, "Haus");
+    }
+}
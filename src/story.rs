@@ -1,10 +1,18 @@
+use std::collections::HashSet;
+
 use crate::{
     ai::STORY_PROMPT,
-    translation::{read_translations, translate_text},
+    translation::{canonical_key, practice_weight, read_translations, translate_text, Translation},
 };
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+const STORY_WORD_COUNT: usize = 100;
+/// With fewer saved words than this, weighting changes little and stories
+/// draw uniformly from all known words.
+const MIN_WEIGHTED_WORDS: usize = 10;
+const GLOSSARY_SIZE: usize = 10;
+
 pub fn select_random_words(words: &[String], count: usize) -> Vec<String> {
     use rand::seq::IteratorRandom;
     let mut rng = rand::thread_rng();
@@ -16,23 +24,30 @@ pub fn select_random_words(words: &[String], count: usize) -> Vec<String> {
         .collect()
 }
 
-pub fn get_german_words() -> Result<Vec<String>> {
-    let translations = read_translations()?;
+fn headword(translation: &Translation) -> Option<String> {
+    if !translation.original.contains(' ') {
+        Some(translation.original.clone())
+    } else {
+        translation
+            .original
+            .split_whitespace()
+            .last()
+            .map(|word| word.to_string())
+    }
+}
+
+fn collect_german_words(translations: &[Translation]) -> Vec<String> {
     let mut words = Vec::new();
 
     for translation in translations {
-        if !translation.original.contains(' ') {
-            words.push(translation.original);
-        } else if let Some(word) = translation.original.split_whitespace().last() {
-            words.push(word.to_string());
-        }
+        words.extend(headword(translation));
 
-        for example in translation.examples {
+        for example in &translation.examples {
             words.extend(
                 example
                     .german
                     .split_whitespace()
-                    .filter(|w| w.chars().next().map_or(false, |c| c.is_uppercase()))
+                    .filter(|w| w.chars().next().is_some_and(|c| c.is_uppercase()))
                     .map(|w| w.trim_matches(|c: char| !c.is_alphabetic()).to_string()),
             );
         }
@@ -40,17 +55,97 @@ pub fn get_german_words() -> Result<Vec<String>> {
 
     words.sort();
     words.dedup();
+    words
+}
+
+fn story_translations() -> Result<Vec<Translation>> {
+    Ok(read_translations()?
+        .into_iter()
+        .filter(|t| !t.archived)
+        .collect())
+}
+
+pub fn get_german_words() -> Result<Vec<String>> {
+    Ok(collect_german_words(&story_translations()?))
+}
+
+/// Saved words are picked with the practice weights, so words with many
+/// mistakes show up in stories more often. Remaining slots are filled
+/// uniformly with other known words.
+fn select_story_words(translations: &[Translation], count: usize) -> Vec<String> {
+    use rand::seq::SliceRandom;
+
+    let words = collect_german_words(translations);
+    if translations.len() < MIN_WEIGHTED_WORDS {
+        return select_random_words(&words, count);
+    }
+
+    let mut selected: Vec<String> = translations
+        .choose_multiple_weighted(&mut rand::thread_rng(), count, practice_weight)
+        .map(|chosen| chosen.filter_map(headword).collect())
+        .unwrap_or_default();
+    selected.sort();
+    selected.dedup();
+
+    let remaining: Vec<String> = words
+        .into_iter()
+        .filter(|word| !selected.contains(word))
+        .collect();
+    let missing = count.saturating_sub(selected.len());
+    selected.extend(select_random_words(&remaining, missing));
+    selected
+}
+
+/// Saved words that occur in the story, hardest first. Verbs also count in
+/// their conjugated forms.
+fn glossary_words<'a>(story: &str, translations: &'a [Translation]) -> Vec<&'a Translation> {
+    let tokens: HashSet<String> = story
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|token| !token.is_empty())
+        .map(canonical_key)
+        .collect();
 
-    Ok(words)
+    let mut found: Vec<&Translation> = translations
+        .iter()
+        .filter(|t| {
+            headword(t).is_some_and(|word| tokens.contains(&canonical_key(&word)))
+                || t.conjugations.iter().flatten().any(|conjugation| {
+                    conjugation
+                        .split_whitespace()
+                        .last()
+                        .is_some_and(|form| tokens.contains(&canonical_key(form)))
+                })
+        })
+        .collect();
+    found.sort_by(|a, b| practice_weight(b).total_cmp(&practice_weight(a)));
+    found.truncate(GLOSSARY_SIZE);
+    found
+}
+
+fn with_glossary(story: String, translations: &[Translation]) -> String {
+    let words = glossary_words(&story, translations);
+    if words.is_empty() {
+        return story;
+    }
+    let entries: Vec<String> = words
+        .iter()
+        .map(|t| format!("• {} — {}", t.with_article(), t.translation))
+        .collect();
+    format!(
+        "{}\n\n📖 Словарь:\n{}",
+        story.trim_end(),
+        entries.join("\n")
+    )
 }
 
 pub async fn generate_story(use_chatgpt: bool, use_deepseek: bool) -> Result<String> {
-    let words = get_german_words()?;
-    let selected_words = select_random_words(&words, 100);
+    let translations = story_translations()?;
+    let selected_words = select_story_words(&translations, STORY_WORD_COUNT);
 
     let prompt = format!(
         "STORY_GENERATION:{}",
         STORY_PROMPT.replace("{word list}", &selected_words.join(", "))
     );
-    translate_text(&prompt, use_chatgpt, use_deepseek).await
+    let story = translate_text(&prompt, None, use_chatgpt, use_deepseek).await?;
+    Ok(with_glossary(story, &translations))
 }
@@ -0,0 +1,47 @@
+use std::env;
+
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+
+use crate::ai::http_client;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_STT_API_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+const DEFAULT_STT_MODEL: &str = "whisper-1";
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+/// Sends an OGG/Opus voice message to a Whisper-compatible transcription
+/// endpoint and returns the recognized text.
+pub async fn transcribe_voice(audio: Vec<u8>) -> Result<String> {
+    let api_key = env::var("STT_API_KEY")
+        .or_else(|_| env::var("OPENAI_API_KEY"))
+        .map_err(|_| "STT_API_KEY environment variable not set")?;
+    let api_url = env::var("STT_API_URL").unwrap_or_else(|_| DEFAULT_STT_API_URL.to_string());
+    let model = env::var("STT_MODEL").unwrap_or_else(|_| DEFAULT_STT_MODEL.to_string());
+
+    let file = Part::bytes(audio)
+        .file_name("voice.ogg")
+        .mime_str("audio/ogg")?;
+    let form = Form::new().part("file", file).text("model", model);
+
+    let client = http_client();
+    let response = client
+        .post(&api_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Transcription request failed with status: {}", status).into());
+    }
+
+    let transcription = response.json::<TranscriptionResponse>().await?;
+    Ok(transcription.text.trim().to_string())
+}
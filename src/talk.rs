@@ -1,13 +1,22 @@
 use rand::seq::SliceRandom;
+use serde::Deserialize;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::fs;
+use std::sync::{Arc, OnceLock};
 use teloxide::{prelude::Requester, types::Message, Bot};
 use tokio::sync::Mutex;
 
 use crate::ai::{
-    ChatGPTMessage, ChatGPTRequest, ChatGPTResponse, ClaudeMessage, ClaudeRequest, ClaudeResponse,
-    CHATGPT_API_URL, CHATGPT_MODEL, TALK_MODE_PROMPT,
+    first_choice, first_text, http_client, make_claude_request, ChatGPTMessage, ChatGPTRequest,
+    ChatGPTResponse, ClaudeMessage, ClaudeRequest, ServiceUnavailable, CHATGPT_API_URL,
+    SERVICE_UNAVAILABLE_MESSAGE, TALK_MODE_PROMPT, TALK_QUIZ_INSTRUCTION,
 };
+use crate::language::TargetLanguage;
+use crate::new_words::{collect_candidates, offer_new_words};
+use crate::retry::SendRetrying;
+use crate::translation::{practice_weight, read_translations};
+use crate::typing::TypingIndicator;
+use crate::user_config::chat_config;
 use std::env;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -33,18 +42,144 @@ const QUESTIONS: [&str; 8] = [
     "Was machst du gerne in deiner Freizeit?",
 ];
 
+const TALK_PROMPTS_FILE: &str = "talk_prompts.json";
+
+/// Conversation openers, optionally overridden from talk_prompts.json.
+struct TalkPrompts {
+    greetings: Vec<String>,
+    introductions: Vec<String>,
+    questions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TalkPromptsFile {
+    greetings: Option<Vec<String>>,
+    introductions: Option<Vec<String>>,
+    questions: Option<Vec<String>>,
+}
+
+static TALK_PROMPTS: OnceLock<TalkPrompts> = OnceLock::new();
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+impl TalkPrompts {
+    fn defaults() -> Self {
+        Self {
+            greetings: to_strings(&GREETINGS),
+            introductions: to_strings(&INTRODUCTIONS),
+            questions: to_strings(&QUESTIONS),
+        }
+    }
+}
+
+/// Replaces a built-in pool with the configured one unless it's missing or empty.
+fn override_pool(pool: &mut Vec<String>, configured: Option<Vec<String>>, name: &str) {
+    match configured {
+        Some(values) if values.iter().any(|v| !v.trim().is_empty()) => {
+            *pool = values
+                .into_iter()
+                .filter(|v| !v.trim().is_empty())
+                .collect();
+        }
+        Some(_) => log::warn!(
+            "{} has an empty '{}' list, using the built-in one",
+            TALK_PROMPTS_FILE,
+            name
+        ),
+        None => {}
+    }
+}
+
+/// Reads talk_prompts.json from the working directory if it exists. Call once
+/// at startup; talk mode falls back to the built-in pools otherwise.
+pub fn load_talk_prompts() -> Result<()> {
+    let file_path = std::env::current_dir()?.join(TALK_PROMPTS_FILE);
+    let mut prompts = TalkPrompts::defaults();
+
+    if file_path.exists() {
+        let data = fs::read_to_string(&file_path)?;
+        let file: TalkPromptsFile = serde_json::from_str(&data)
+            .map_err(|e| format!("{} is malformed: {}", TALK_PROMPTS_FILE, e))?;
+        override_pool(&mut prompts.greetings, file.greetings, "greetings");
+        override_pool(
+            &mut prompts.introductions,
+            file.introductions,
+            "introductions",
+        );
+        override_pool(&mut prompts.questions, file.questions, "questions");
+        log::info!("Loaded talk prompts from {}", TALK_PROMPTS_FILE);
+    }
+
+    TALK_PROMPTS
+        .set(prompts)
+        .map_err(|_| "Talk prompts are already loaded")?;
+    Ok(())
+}
+
+fn talk_prompts() -> &'static TalkPrompts {
+    TALK_PROMPTS.get_or_init(TalkPrompts::defaults)
+}
+
+const CORRECTION_MARKER: &str = "Kleine Korrektur:";
+/// In quiz mode every this many user messages the partner works in a weak word.
+const QUIZ_EVERY_TURNS: u32 = 3;
+const QUIZ_WORD_POOL: usize = 10;
+
 #[derive(Clone)]
 pub struct TalkSession {
     context: Vec<String>,
+    user_turns: u32,
+    words_written: usize,
+    corrections: u32,
+    word_candidates: Vec<String>,
+    /// Weak words to weave into the conversation, empty outside `/talk quiz`.
+    quiz_words: Vec<String>,
+    quizzed: usize,
 }
 
 impl TalkSession {
-    fn new() -> Self {
+    fn new(quiz_words: Vec<String>) -> Self {
         Self {
             context: Vec::new(),
+            user_turns: 0,
+            words_written: 0,
+            corrections: 0,
+            word_candidates: Vec::new(),
+            quiz_words,
+            quizzed: 0,
         }
     }
 
+    fn record_turn(&mut self, message: &str, response: &str) {
+        self.user_turns += 1;
+        self.words_written += message.split_whitespace().count();
+        collect_candidates(message, &mut self.word_candidates);
+        self.corrections += response
+            .lines()
+            .filter(|line| line.trim_start().starts_with(CORRECTION_MARKER))
+            .count() as u32;
+    }
+
+    fn format_report(&self) -> String {
+        format!(
+            "📊 Dein Gespräch:\nNachrichten: {}\nGeschriebene Wörter: {}\nKorrekturen: {}",
+            self.user_turns, self.words_written, self.corrections
+        )
+    }
+
+    /// The weak word to bring up in the reply to the current message, taking
+    /// turns through the pool.
+    fn next_quiz_word(&mut self) -> Option<String> {
+        if self.quiz_words.is_empty() || !(self.user_turns + 1).is_multiple_of(QUIZ_EVERY_TURNS) {
+            return None;
+        }
+        let word = self.quiz_words[self.quizzed % self.quiz_words.len()].clone();
+        self.quizzed += 1;
+        Some(word)
+    }
+
     fn add_message(&mut self, message: &str) {
         self.context.push(message.to_string());
         // Keep only the last 5 messages for context
@@ -60,29 +195,29 @@ impl TalkSession {
 
 pub type TalkSessions = Arc<Mutex<HashMap<i64, TalkSession>>>;
 
-async fn make_claude_request(request: &ClaudeRequest) -> Result<ClaudeResponse> {
-    let api_key =
-        env::var("ANTHROPIC_API_KEY").expect("ANTHROPIC_API_KEY environment variable not set");
-    let client = reqwest::Client::new();
-
-    let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("content-type", "application/json")
-        .json(request)
-        .send()
-        .await?
-        .json::<ClaudeResponse>()
-        .await?;
-
-    Ok(response)
+fn build_talk_prompt(
+    context: &str,
+    message: &str,
+    quiz_word: Option<&str>,
+    language: TargetLanguage,
+) -> String {
+    let quiz = quiz_word
+        .map(|word| TALK_QUIZ_INSTRUCTION.replace("{word}", word))
+        .unwrap_or_default();
+    TALK_MODE_PROMPT
+        .replace("{quiz}", &quiz)
+        .replace("{context}", context)
+        .replace("{message}", message)
+        .replace("{language}", language.name())
 }
 
-async fn talk_with_claude(context: &str, message: &str) -> Result<String> {
-    let prompt = TALK_MODE_PROMPT
-        .replace("{context}", context)
-        .replace("{message}", message);
+async fn talk_with_claude(
+    context: &str,
+    message: &str,
+    quiz_word: Option<&str>,
+    language: TargetLanguage,
+) -> Result<String> {
+    let prompt = build_talk_prompt(context, message, quiz_word, language);
 
     let messages = vec![ClaudeMessage {
         role: "user".to_string(),
@@ -96,26 +231,26 @@ async fn talk_with_claude(context: &str, message: &str) -> Result<String> {
     };
 
     let response = make_claude_request(&request).await?;
-    Ok(response.content[0].text.clone())
+    first_text(&response)
 }
 
-async fn talk_with_chatgpt(context: &str, message: &str) -> Result<String> {
+async fn talk_with_chatgpt(
+    context: &str,
+    message: &str,
+    quiz_word: Option<&str>,
+    language: TargetLanguage,
+) -> Result<String> {
     let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY environment variable not set");
-    let client = reqwest::Client::new();
+    let client = http_client();
 
-    let prompt = TALK_MODE_PROMPT
-        .replace("{context}", context)
-        .replace("{message}", message);
+    let prompt = build_talk_prompt(context, message, quiz_word, language);
 
     let messages = vec![ChatGPTMessage {
         role: "user".to_string(),
         content: prompt,
     }];
 
-    let request = ChatGPTRequest {
-        model: CHATGPT_MODEL.to_string(),
-        messages,
-    };
+    let request = ChatGPTRequest::openai(messages);
 
     let response = client
         .post(CHATGPT_API_URL)
@@ -127,33 +262,66 @@ async fn talk_with_chatgpt(context: &str, message: &str) -> Result<String> {
         .json::<ChatGPTResponse>()
         .await?;
 
-    Ok(response.choices[0].message.content.clone())
+    first_choice(&response)
 }
 
 fn generate_initial_prompt() -> String {
+    let prompts = talk_prompts();
     let mut rng = rand::thread_rng();
     format!(
         "{} {} {}",
-        GREETINGS.choose(&mut rng).unwrap(),
-        INTRODUCTIONS.choose(&mut rng).unwrap(),
-        QUESTIONS.choose(&mut rng).unwrap()
+        prompts.greetings.choose(&mut rng).unwrap(),
+        prompts.introductions.choose(&mut rng).unwrap(),
+        prompts.questions.choose(&mut rng).unwrap()
     )
 }
 
-pub async fn start_talk_session(bot: &Bot, msg: &Message, sessions: &TalkSessions) -> Result<()> {
+/// Words with the highest practice weight, i.e. the ones answered wrong most.
+fn weak_words() -> Result<Vec<String>> {
+    let mut translations: Vec<_> = read_translations()?
+        .into_iter()
+        .filter(|t| !t.archived && t.wrong_answers > 0)
+        .collect();
+    translations.sort_by(|a, b| practice_weight(b).total_cmp(&practice_weight(a)));
+    Ok(translations
+        .iter()
+        .take(QUIZ_WORD_POOL)
+        .map(|t| t.with_article())
+        .collect())
+}
+
+pub async fn start_talk_session(
+    bot: &Bot,
+    msg: &Message,
+    sessions: &TalkSessions,
+    quiz: bool,
+) -> Result<()> {
     let mut sessions = sessions.lock().await;
 
     if sessions.contains_key(&msg.chat.id.0) {
         bot.send_message(msg.chat.id, "Du bist bereits im Gesprächsmodus!")
+            .send_retrying()
             .await?;
         return Ok(());
     }
 
+    let quiz_words = if quiz { weak_words()? } else { Vec::new() };
+    if quiz && quiz_words.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Du hast noch keine schwachen Wörter, wir sprechen ohne Quiz.",
+        )
+        .send_retrying()
+        .await?;
+    }
+
     let initial_prompt = generate_initial_prompt();
-    let mut session = TalkSession::new();
+    let mut session = TalkSession::new(quiz_words);
     session.add_message(&initial_prompt);
     sessions.insert(msg.chat.id.0, session);
-    bot.send_message(msg.chat.id, initial_prompt).await?;
+    bot.send_message(msg.chat.id, initial_prompt)
+        .send_retrying()
+        .await?;
 
     Ok(())
 }
@@ -161,14 +329,20 @@ pub async fn start_talk_session(bot: &Bot, msg: &Message, sessions: &TalkSession
 pub async fn stop_talk_session(bot: &Bot, msg: &Message, sessions: &TalkSessions) -> Result<()> {
     let mut sessions = sessions.lock().await;
 
-    if sessions.remove(&msg.chat.id.0).is_some() {
+    if let Some(session) = sessions.remove(&msg.chat.id.0) {
         bot.send_message(
             msg.chat.id,
-            "Danke für das Gespräch! Bis zum nächsten Mal! 👋",
+            format!(
+                "Danke für das Gespräch! Bis zum nächsten Mal! 👋\n\n{}",
+                session.format_report()
+            ),
         )
+        .send_retrying()
         .await?;
+        offer_new_words(bot, msg.chat.id, &session.word_candidates).await?;
     } else {
         bot.send_message(msg.chat.id, "Du bist nicht im Gesprächsmodus!")
+            .send_retrying()
             .await?;
     }
 
@@ -178,25 +352,40 @@ pub async fn stop_talk_session(bot: &Bot, msg: &Message, sessions: &TalkSessions
 pub async fn handle_talk_message(
     bot: &Bot,
     msg: &Message,
+    text: &str,
     sessions: &TalkSessions,
     use_chatgpt: &Arc<Mutex<bool>>,
 ) -> Result<()> {
     let mut sessions = sessions.lock().await;
 
     if let Some(session) = sessions.get_mut(&msg.chat.id.0) {
-        if let Some(text) = msg.text() {
-            session.add_message(text);
-
-            let use_chatgpt = *use_chatgpt.lock().await;
-            let response = if use_chatgpt {
-                talk_with_chatgpt(&session.get_context(), text).await?
-            } else {
-                talk_with_claude(&session.get_context(), text).await?
-            };
-
-            session.add_message(&response);
-            bot.send_message(msg.chat.id, response).await?;
-        }
+        session.add_message(text);
+        let quiz_word = session.next_quiz_word();
+
+        let use_chatgpt = *use_chatgpt.lock().await;
+        let language = chat_config(Some(msg.chat.id.0)).target_language();
+        let typing = TypingIndicator::start(bot, msg.chat.id);
+        let response = if use_chatgpt {
+            talk_with_chatgpt(&session.get_context(), text, quiz_word.as_deref(), language).await
+        } else {
+            talk_with_claude(&session.get_context(), text, quiz_word.as_deref(), language).await
+        };
+        drop(typing);
+        let response = match response {
+            Err(e) if e.is::<ServiceUnavailable>() => {
+                bot.send_message(msg.chat.id, SERVICE_UNAVAILABLE_MESSAGE)
+                    .send_retrying()
+                    .await?;
+                return Ok(());
+            }
+            result => result?,
+        };
+
+        session.add_message(&response);
+        session.record_turn(text, &response);
+        bot.send_message(msg.chat.id, response)
+            .send_retrying()
+            .await?;
     }
 
     Ok(())
@@ -0,0 +1,60 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Environment variables are process-wide, so tests that set them or touch
+/// the storage file take this lock for their whole run.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// A fresh storage file for one test. STORAGE_FILE points at it until the
+/// value is dropped, which also removes the file and unsets anything set
+/// through `env`.
+pub struct TestStorage {
+    pub path: PathBuf,
+    vars: Vec<&'static str>,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestStorage {
+    pub fn new(name: &str) -> Self {
+        let guard = ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let path = env::temp_dir().join(format!("zungenrede-{}-{}.json", name, process::id()));
+        let storage = Self {
+            path,
+            vars: vec!["STORAGE_FILE"],
+            _guard: guard,
+        };
+        storage.remove_files();
+        env::set_var("STORAGE_FILE", &storage.path);
+        storage
+    }
+
+    /// Sets an environment variable until the end of the test.
+    pub fn env(&mut self, key: &'static str, value: &str) {
+        env::set_var(key, value);
+        self.vars.push(key);
+    }
+
+    /// A path next to the storage file, e.g. for the `.bak` copy.
+    pub fn sibling(&self, suffix: &str) -> PathBuf {
+        PathBuf::from(format!("{}{}", self.path.display(), suffix))
+    }
+
+    fn remove_files(&self) {
+        for suffix in ["", ".bak", ".tmp"] {
+            let _ = fs::remove_file(self.sibling(suffix));
+        }
+    }
+}
+
+impl Drop for TestStorage {
+    fn drop(&mut self) {
+        self.remove_files();
+        for key in &self.vars {
+            env::remove_var(key);
+        }
+    }
+}
@@ -1,20 +1,125 @@
-use std::{env, fs};
+use std::{
+    collections::BTreeMap,
+    env, fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
+use strsim::jaro_winkler;
+use teloxide::utils::markdown;
 
 use crate::{
     ai::{
-        make_claude_request, ChatGPTMessage, ChatGPTRequest, ChatGPTResponse, ClaudeMessage,
-        ClaudeRequest, CHATGPT_API_URL, CHATGPT_MODEL, CONTEXT_PROMPT, DEEPSEEK_API_URL,
-        DEEPSEEK_MODEL, EXPLANATION_PROMPT, FREEFORM_PROMPT, GERMAN_SENTENCE_PROMPT,
-        GERMAN_WORD_PROMPT, GRAMMAR_CHECK_PROMPT, RUSSIAN_TO_GERMAN_PROMPT, RUSSIAN_WORD_PROMPT,
-        SIMPLIFY_PROMPT,
+        first_choice, first_text, http_client, make_claude_request, ChatGPTMessage, ChatGPTRequest,
+        ChatGPTResponse, ClaudeMessage, ClaudeRequest, CHATGPT_API_URL, CONTEXT_PROMPT,
+        DEEPSEEK_API_URL, DEEPSEEK_MODEL, EXAMPLES_PROMPT, EXPLANATION_PROMPT, FREEFORM_PROMPT,
+        GERMAN_SENTENCE_PROMPT, GERMAN_WORD_PROMPT, GRAMMAR_CHECK_PROMPT, MONOLINGUAL_PROMPT,
+        SENTENCE_ALIGNMENT_PROMPT, SIMPLIFY_PROMPT, STRICT_FORMAT_PROMPT, TARGET_TO_GERMAN_PROMPT,
+        TARGET_WORD_PROMPT,
     },
-    input::{analyze_input, InputType},
+    input::{analyze_input, split_sentences, InputType},
+    language::{contains_cyrillic, is_target_language, TargetLanguage},
+    storage::{storage, Storage},
+    user_config::{chat_config, PromptKind, UserConfig},
 };
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+pub const MIN_EXAMPLE_COUNT: usize = 1;
+pub const MAX_EXAMPLE_COUNT: usize = 5;
+const DEFAULT_EXAMPLE_COUNT: usize = 2;
+const ARTICLES: [&str; 3] = ["der", "die", "das"];
+const MAX_RECENT_RESULTS: usize = 10;
+const RECENT_MISTAKE_BOOST: f64 = 1.5;
+/// Wrong answers after which a word that's mostly answered wrong becomes a
+/// leech, as in Anki.
+const LEECH_WRONG_ANSWERS: u32 = 8;
+/// A leech that was put back into practice is flagged again after this many
+/// further mistakes.
+const LEECH_REPEAT_INTERVAL: u32 = 4;
+const SYLLABLES_PREFIX: &str = "Silben:";
+const MAX_ORIGINAL_CHARS: usize = 60;
+const MAX_TRANSLATION_CHARS: usize = 150;
+/// Phrases that mean the model answered about the request instead of with
+/// a word card.
+const REFUSAL_MARKERS: [&str; 10] = [
+    "i can't",
+    "i cannot",
+    "i'm sorry",
+    "i am sorry",
+    "i apologize",
+    "as an ai",
+    "извините",
+    "к сожалению",
+    "я не могу",
+    "не могу перевести",
+];
+/// Weight above which a practiced word is shown as coming up more often
+/// because of mistakes. New words (2.0) don't count, they have no answers yet.
+const ELEVATED_WEIGHT: f64 = 1.5;
+
+static STORAGE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Guards read-modify-write cycles on the storage file. Hold the returned
+/// guard from `read_translations` until the matching `write_translations`.
+pub fn lock_storage() -> MutexGuard<'static, ()> {
+    STORAGE_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Bumped whenever words are added, changed or removed, so cached copies of the
+// vocabulary know to reload. Practice statistics don't count as a change.
+static VOCABULARY_VERSION: AtomicU64 = AtomicU64::new(0);
+
+pub fn vocabulary_version() -> u64 {
+    VOCABULARY_VERSION.load(Ordering::Relaxed)
+}
+
+fn vocabulary_changed() {
+    VOCABULARY_VERSION.fetch_add(1, Ordering::Relaxed);
+}
+
+// 0 means the count hasn't been set via /examples yet and EXAMPLE_COUNT is used
+static EXAMPLE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn get_example_count() -> usize {
+    match EXAMPLE_COUNT.load(Ordering::Relaxed) {
+        0 => env::var("EXAMPLE_COUNT")
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|count| (MIN_EXAMPLE_COUNT..=MAX_EXAMPLE_COUNT).contains(count))
+            .unwrap_or(DEFAULT_EXAMPLE_COUNT),
+        count => count,
+    }
+}
+
+pub fn set_example_count(count: usize) -> Result<()> {
+    if !(MIN_EXAMPLE_COUNT..=MAX_EXAMPLE_COUNT).contains(&count) {
+        return Err(format!(
+            "Example count must be between {} and {}",
+            MIN_EXAMPLE_COUNT, MAX_EXAMPLE_COUNT
+        )
+        .into());
+    }
+    EXAMPLE_COUNT.store(count, Ordering::Relaxed);
+    Ok(())
+}
+
+fn template_examples(prompt: &str, example_line: &str) -> String {
+    let count = get_example_count();
+    let example_lines = (1..=count)
+        .map(|i| format!("{}. {}", i, example_line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    prompt
+        .replace("{example_count}", &count.to_string())
+        .replace("{example_lines}", &example_lines)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Translation {
     pub original: String,
@@ -26,68 +131,393 @@ pub struct Translation {
     pub correct_answers: u32,
     #[serde(default)]
     pub wrong_answers: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Outcomes of the most recent practice answers, oldest first.
+    #[serde(default)]
+    pub recent_results: Vec<bool>,
+    /// Set by /forget: left out of practice and stories but kept with its stats.
+    #[serde(default)]
+    pub archived: bool,
+    /// The German word split into syllables, e.g. "Ver·ant·wor·tung".
+    #[serde(default)]
+    pub syllables: Option<String>,
+    /// Unix time the word was first saved. Missing for words saved before
+    /// this was recorded, which count as old.
+    #[serde(default)]
+    pub added_at: Option<i64>,
+    /// Explanation saved by replying to the word's card with `?:`.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Answered wrong so often that practice skips it until it's put back.
+    #[serde(default)]
+    pub leech: bool,
+    /// Unix time of the last practice answer.
+    #[serde(default)]
+    pub last_practiced: Option<i64>,
+}
+
+/// Normalized form used to compare words: lowercase, `ß` folded to `ss` and
+/// whitespace collapsed, so "Straße" and "strasse" are the same entry.
+pub fn canonical_key(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .replace('ß', "ss")
 }
 
 impl Translation {
+    /// German original prefixed with its article for nouns, e.g. "der Wald".
+    pub fn with_article(&self) -> String {
+        let article = self
+            .grammar_forms
+            .first()
+            .map(|form| form.trim())
+            .filter(|form| ARTICLES.contains(form));
+        let has_article = self
+            .original
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| ARTICLES.contains(&word));
+
+        match article {
+            Some(article) if !has_article => format!("{} {}", article, self.original),
+            _ => self.original.clone(),
+        }
+    }
+
+    /// Counts a practice answer and remembers it in the recent results.
+    pub fn record_result(&mut self, correct: bool) {
+        if correct {
+            self.correct_answers += 1;
+        } else {
+            self.wrong_answers += 1;
+            let past_threshold = self.wrong_answers >= LEECH_WRONG_ANSWERS
+                && (self.wrong_answers - LEECH_WRONG_ANSWERS).is_multiple_of(LEECH_REPEAT_INTERVAL);
+            if past_threshold && self.wrong_answers > self.correct_answers {
+                self.leech = true;
+            }
+        }
+        self.last_practiced = Some(unix_now());
+        self.recent_results.push(correct);
+        if self.recent_results.len() > MAX_RECENT_RESULTS {
+            let overflow = self.recent_results.len() - MAX_RECENT_RESULTS;
+            self.recent_results.drain(..overflow);
+        }
+    }
+
+    /// Matching key for the German original.
+    pub fn key(&self) -> String {
+        canonical_key(&self.original)
+    }
+
+    /// Whether the word matches either side of this entry.
+    pub fn matches(&self, word: &str) -> bool {
+        self.matches_original(word) || self.matches_translation(word)
+    }
+
+    pub fn matches_original(&self, word: &str) -> bool {
+        self.key() == canonical_key(word)
+    }
+
+    pub fn matches_translation(&self, word: &str) -> bool {
+        canonical_key(&self.translation) == canonical_key(word)
+    }
+
+    pub fn is_noun(&self) -> bool {
+        self.grammar_forms
+            .first()
+            .is_some_and(|form| ARTICLES.contains(&form.trim()))
+    }
+
     fn is_valid(&self) -> bool {
-        !self.original.trim().is_empty()
-            && !self.translation.trim().is_empty()
-            && self
-                .examples
-                .iter()
-                .all(|e| !e.german.trim().is_empty() && !e.russian.trim().is_empty())
+        self.validate().is_ok()
+    }
+
+    /// Rejects a parsed model reply that isn't a word card: empty or overly
+    /// long headwords, or a refusal/meta comment in place of the translation.
+    pub fn check_model_output(&self) -> std::result::Result<(), String> {
+        let original = self.original.trim();
+        let translation = self.translation.trim();
+        if original.is_empty() || translation.is_empty() {
+            return Err("the reply has no word or translation".to_string());
+        }
+        if original.chars().count() > MAX_ORIGINAL_CHARS
+            || translation.chars().count() > MAX_TRANSLATION_CHARS
+        {
+            return Err("the word or translation is suspiciously long".to_string());
+        }
+        let lowercase = format!("{}\n{}", original, translation).to_lowercase();
+        if let Some(marker) = REFUSAL_MARKERS
+            .iter()
+            .find(|marker| lowercase.contains(*marker))
+        {
+            return Err(format!("the reply looks like a refusal (\"{}\")", marker));
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the first empty field, if any.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.original.trim().is_empty() {
+            return Err("`original` is empty".to_string());
+        }
+        if self.translation.trim().is_empty() {
+            return Err("`translation` is empty".to_string());
+        }
+        for (i, example) in self.examples.iter().enumerate() {
+            if example.german.trim().is_empty() {
+                return Err(format!("`examples[{}].german` is empty", i));
+            }
+            if example.russian.trim().is_empty() {
+                return Err(format!("`examples[{}].russian` is empty", i));
+            }
+        }
+        Ok(())
     }
 }
 
+/// An example sentence. Stored German first whichever language the word was
+/// looked up in, and shown that way on cards.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Example {
     pub german: String,
+    /// Translation into the target language, Russian by default.
     pub russian: String,
 }
 
 pub fn update_translation_stats(word: &str, correct: bool) -> Result<()> {
+    let _guard = lock_storage();
+    storage().update_stats(word, correct)
+}
+
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// Adds a tag to the word, returning `false` if the word isn't in the database.
+pub fn tag_translation(word: &str, tag: &str) -> Result<bool> {
+    let _guard = lock_storage();
     let mut translations = read_translations()?;
+    let tag = normalize_tag(tag);
 
-    if let Some(translation) = translations.iter_mut().find(|t| {
-        t.original.to_lowercase() == word.to_lowercase()
-            || t.translation.to_lowercase() == word.to_lowercase()
-    }) {
-        if correct {
-            translation.correct_answers += 1;
-        } else {
-            translation.wrong_answers += 1;
-        }
+    let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) else {
+        return Ok(false);
+    };
 
+    if !translation.tags.contains(&tag) {
+        translation.tags.push(tag);
         write_translations(&translations)?;
     }
+    Ok(true)
+}
 
-    Ok(())
+/// Archives or restores the word, returning `false` if it isn't in the database.
+pub fn set_archived(word: &str, archived: bool) -> Result<bool> {
+    let _guard = lock_storage();
+    let mut translations = read_translations()?;
+
+    let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) else {
+        return Ok(false);
+    };
+
+    if translation.archived != archived {
+        translation.archived = archived;
+        write_translations(&translations)?;
+    }
+    Ok(true)
 }
 
-pub fn get_weighted_translation(translations: &[Translation]) -> Option<Translation> {
-    use rand::Rng;
+/// Flags the word as a leech or puts it back into practice, returning `false`
+/// if it isn't in the database.
+pub fn set_leech(word: &str, leech: bool) -> Result<bool> {
+    let _guard = lock_storage();
+    let mut translations = read_translations()?;
+
+    let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) else {
+        return Ok(false);
+    };
 
-    if translations.is_empty() {
-        return None;
+    if translation.leech != leech {
+        translation.leech = leech;
+        write_translations(&translations)?;
     }
+    Ok(true)
+}
 
-    // Calculate weights based on error rate
-    let weights: Vec<f64> = translations
-        .iter()
-        .map(|t| {
-            let total = t.correct_answers + t.wrong_answers;
-            if total == 0 {
-                // New words get higher weight to ensure they're practiced
-                2.0
-            } else {
-                // Calculate error rate and add 1 to ensure all words have a chance
-                1.0 + (t.wrong_answers as f64 / total as f64)
-            }
+/// Attaches a note to the word, returning `false` if it isn't in the database.
+pub fn set_note(word: &str, note: &str) -> Result<bool> {
+    let _guard = lock_storage();
+    let mut translations = read_translations()?;
+
+    let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) else {
+        return Ok(false);
+    };
+
+    translation.note = Some(note.to_string());
+    write_translations(&translations)?;
+    Ok(true)
+}
+
+/// Replaces the word's examples, returning `false` if it isn't in the database.
+pub fn set_examples(word: &str, examples: Vec<Example>) -> Result<bool> {
+    let _guard = lock_storage();
+    let mut translations = read_translations()?;
+
+    let Some(translation) = translations.iter_mut().find(|t| t.matches(word)) else {
+        return Ok(false);
+    };
+
+    translation.examples = examples;
+    *translation = prepare_translation(translation.clone())?;
+    write_translations(&translations)?;
+    Ok(true)
+}
+
+/// Asks the model for a fresh set of example sentences for a saved word and
+/// stores them in place of the old ones. Returns the word with its new
+/// examples.
+pub async fn regenerate_examples(
+    translation: &Translation,
+    language: TargetLanguage,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<Translation> {
+    let prompt = template_examples(
+        EXAMPLES_PROMPT,
+        &format!("German sentence - {} translation", language.name()),
+    )
+    .replace("{word}", &translation.with_article())
+    .replace("{translation}", &translation.translation);
+    let response = send_prompt(prompt, "", language, use_chatgpt, use_deepseek).await?;
+
+    let examples: Vec<Example> = clean_model_output(&response)
+        .lines()
+        .filter_map(strip_example_number)
+        .map(split_example)
+        .filter(|(german, russian)| !german.is_empty() && !russian.is_empty())
+        .take(get_example_count())
+        .map(|(german, russian)| Example {
+            german: german.to_string(),
+            russian: russian.to_string(),
         })
         .collect();
+    if examples.is_empty() {
+        return Err("The model returned no usable examples".into());
+    }
+
+    set_examples(&translation.original, examples.clone())?;
+    Ok(Translation {
+        examples,
+        ..translation.clone()
+    })
+}
+
+/// Selection weight for practice. New words get 2.0, others 1.0 plus their
+/// lifetime error rate. A run of correct answers halves the weight per answer,
+/// so a word that's been mastered recently rarely comes up even if it used to
+/// be hard, while a recent mistake makes it more likely again.
+pub fn practice_weight(translation: &Translation) -> f64 {
+    let total = translation.correct_answers + translation.wrong_answers;
+    if total == 0 {
+        return 2.0;
+    }
+
+    let base = 1.0 + (translation.wrong_answers as f64 / total as f64);
+    let streak = translation
+        .recent_results
+        .iter()
+        .rev()
+        .take_while(|correct| **correct)
+        .count();
+    let recent_mistake = translation.recent_results.last() == Some(&false);
+
+    let weight = base * 0.5f64.powi(streak as i32);
+    if recent_mistake {
+        weight * RECENT_MISTAKE_BOOST
+    } else {
+        weight
+    }
+}
+
+/// Share of correct answers from which a word with enough answers counts as
+/// mastered.
+const MASTERY_ACCURACY: f64 = 0.8;
+
+pub enum Mastery {
+    /// Fewer answers than the configured minimum, the accuracy says little yet.
+    NotEnoughData,
+    Learning,
+    Mastered,
+}
+
+pub fn mastery(translation: &Translation, min_attempts: u32) -> Mastery {
+    let total = translation.correct_answers + translation.wrong_answers;
+    if total < min_attempts.max(1) {
+        Mastery::NotEnoughData
+    } else if translation.correct_answers as f64 / total as f64 >= MASTERY_ACCURACY {
+        Mastery::Mastered
+    } else {
+        Mastery::Learning
+    }
+}
+
+/// Whether mistakes have made the word noticeably more likely to come up,
+/// using the same weight `get_weighted_translation` picks by.
+pub fn has_elevated_weight(translation: &Translation) -> bool {
+    translation.wrong_answers > 0 && practice_weight(translation) >= ELEVATED_WEIGHT
+}
+
+/// Picks a word for practice, never repeating `exclude` (the word just asked)
+/// unless it's the only one.
+pub fn get_weighted_translation(
+    translations: &[Translation],
+    exclude: Option<&str>,
+    skip_leeches: bool,
+) -> Option<Translation> {
+    pick_weighted_translation(translations, exclude, skip_leeches, &mut rand::thread_rng())
+}
+
+/// `get_weighted_translation` with the random source passed in, so a seeded
+/// generator gives a reproducible pick.
+pub fn pick_weighted_translation(
+    translations: &[Translation],
+    exclude: Option<&str>,
+    skip_leeches: bool,
+    rng: &mut impl rand::Rng,
+) -> Option<Translation> {
+    // Leeches are only asked when nothing else is left
+    let active: Vec<&Translation> = translations
+        .iter()
+        .filter(|t| !skip_leeches || !t.leech)
+        .collect();
+    let translations: Vec<&Translation> = if active.is_empty() {
+        translations.iter().collect()
+    } else {
+        active
+    };
+
+    let exclude_key = exclude.map(canonical_key);
+    let candidates: Vec<&Translation> = translations
+        .iter()
+        .copied()
+        .filter(|t| exclude_key.as_ref() != Some(&t.key()))
+        .collect();
+    let translations: Vec<&Translation> = if candidates.is_empty() {
+        translations
+    } else {
+        candidates
+    };
+
+    if translations.is_empty() {
+        return None;
+    }
+
+    // Calculate weights based on error rate and the current streak
+    let weights: Vec<f64> = translations.iter().map(|t| practice_weight(t)).collect();
 
     let total_weight: f64 = weights.iter().sum();
-    let mut rng = rand::thread_rng();
     let mut random_value = rng.gen::<f64>() * total_weight;
 
     for (i, weight) in weights.iter().enumerate() {
@@ -101,19 +531,84 @@ pub fn get_weighted_translation(translations: &[Translation]) -> Option<Translat
     Some(translations[0].clone())
 }
 
-pub async fn translate_text(text: &str, use_chatgpt: bool, use_deepseek: bool) -> Result<String> {
+pub fn get_random_translation(translations: &[Translation]) -> Option<&Translation> {
+    use rand::seq::SliceRandom;
+    translations.choose(&mut rand::thread_rng())
+}
+
+/// Sends the query to the selected model. With a `chat_id`, that chat's
+/// /setprompt overrides are used in place of the built-in prompts.
+pub async fn translate_text(
+    text: &str,
+    chat_id: Option<i64>,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<String> {
+    let config = chat_config(chat_id);
+    let language = config.target_language();
+    let (system_prompt, processed_text) = prepare_prompt(text, &config.prompts, language);
+    send_prompt(
+        system_prompt,
+        processed_text,
+        language,
+        use_chatgpt,
+        use_deepseek,
+    )
+    .await
+}
+
+pub(crate) async fn send_prompt(
+    system_prompt: String,
+    processed_text: &str,
+    language: TargetLanguage,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<String> {
+    let system_prompt = system_prompt.replace("{language}", language.name());
+
     if use_chatgpt {
-        translate_with_chatgpt(text).await
+        translate_with_chatgpt(system_prompt, processed_text).await
     } else if use_deepseek {
-        translate_with_deepseek(text).await
+        translate_with_deepseek(system_prompt, processed_text).await
     } else {
-        translate_with_claude(text).await
+        translate_with_claude(system_prompt, processed_text).await
     }
 }
 
-async fn translate_with_claude(text: &str) -> Result<String> {
-    let (system_prompt, processed_text) = prepare_prompt(text);
+/// Parses a word card reply and, if it isn't usable, asks the model once more
+/// with a stricter format reminder. The second reply is not retried again.
+/// The inner error says why the card was rejected.
+pub async fn parse_word_reply(
+    word: &str,
+    response: &str,
+    chat_id: Option<i64>,
+    use_chatgpt: bool,
+    use_deepseek: bool,
+) -> Result<std::result::Result<Translation, String>> {
+    let config = chat_config(chat_id);
+    let language = config.target_language();
+    let translation = parse_translation_response(word, response, language);
+    let Err(e) = translation.check_model_output() else {
+        return Ok(Ok(translation));
+    };
+    log::info!("Reply for '{}' is off-format ({}), retrying", word, e);
+
+    let (system_prompt, processed_text) = prepare_prompt(word, &config.prompts, language);
+    let system_prompt = format!("{}\n\n{}", system_prompt, STRICT_FORMAT_PROMPT);
+    let response = send_prompt(
+        system_prompt,
+        processed_text,
+        language,
+        use_chatgpt,
+        use_deepseek,
+    )
+    .await?;
+
+    let translation = parse_translation_response(word, &response, language);
+    Ok(translation.check_model_output().map(|()| translation))
+}
 
+async fn translate_with_claude(system_prompt: String, processed_text: &str) -> Result<String> {
     let messages = vec![ClaudeMessage {
         role: "user".to_string(),
         content: if processed_text.is_empty() {
@@ -130,15 +625,13 @@ async fn translate_with_claude(text: &str) -> Result<String> {
     };
 
     let response = make_claude_request(&request).await?;
-    Ok(response.content[0].text.clone())
+    first_text(&response)
 }
 
-async fn translate_with_chatgpt(text: &str) -> Result<String> {
+async fn translate_with_chatgpt(system_prompt: String, processed_text: &str) -> Result<String> {
     let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY environment variable not set");
 
-    let client = reqwest::Client::new();
-
-    let (system_prompt, processed_text) = prepare_prompt(text);
+    let client = http_client();
 
     let messages = vec![ChatGPTMessage {
         role: "user".to_string(),
@@ -149,10 +642,7 @@ async fn translate_with_chatgpt(text: &str) -> Result<String> {
         },
     }];
 
-    let request = ChatGPTRequest {
-        model: CHATGPT_MODEL.to_string(),
-        messages,
-    };
+    let request = ChatGPTRequest::openai(messages);
 
     let response = client
         .post(CHATGPT_API_URL)
@@ -164,16 +654,14 @@ async fn translate_with_chatgpt(text: &str) -> Result<String> {
         .json::<ChatGPTResponse>()
         .await?;
 
-    Ok(response.choices[0].message.content.clone())
+    first_choice(&response)
 }
 
-async fn translate_with_deepseek(text: &str) -> Result<String> {
+async fn translate_with_deepseek(system_prompt: String, processed_text: &str) -> Result<String> {
     let api_key =
         env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY environment variable not set");
 
-    let client = reqwest::Client::new();
-
-    let (system_prompt, processed_text) = prepare_prompt(text);
+    let client = http_client();
 
     let messages = vec![ChatGPTMessage {
         role: "user".to_string(),
@@ -184,10 +672,7 @@ async fn translate_with_deepseek(text: &str) -> Result<String> {
         },
     }];
 
-    let request = ChatGPTRequest {
-        model: DEEPSEEK_MODEL.to_string(),
-        messages,
-    };
+    let request = ChatGPTRequest::new(DEEPSEEK_MODEL, messages);
 
     let response = client
         .post(DEEPSEEK_API_URL)
@@ -199,10 +684,14 @@ async fn translate_with_deepseek(text: &str) -> Result<String> {
         .json::<ChatGPTResponse>()
         .await?;
 
-    Ok(response.choices[0].message.content.clone())
+    first_choice(&response)
 }
 
-fn prepare_prompt(text: &str) -> (String, &str) {
+fn prepare_prompt<'a>(
+    text: &'a str,
+    overrides: &BTreeMap<PromptKind, String>,
+    language: TargetLanguage,
+) -> (String, &'a str) {
     if text.starts_with("STORY_GENERATION:") {
         (text.trim_start_matches("STORY_GENERATION:").to_string(), "")
     } else if text.starts_with("Context: ") {
@@ -211,152 +700,604 @@ fn prepare_prompt(text: &str) -> (String, &str) {
         let query = parts.get(1).unwrap_or(&"").trim();
         (CONTEXT_PROMPT.replace("{context}", context), query)
     } else {
-        match analyze_input(text) {
+        let input_type = analyze_input(text, language);
+        let custom = PromptKind::for_input(&input_type).and_then(|kind| overrides.get(&kind));
+        let prompt = |built_in: &str| {
+            custom
+                .map_or(built_in, |custom| custom.as_str())
+                .to_string()
+        };
+        match input_type {
             InputType::Explanation => {
                 let clean_text = text.trim_start_matches("?:").trim();
-                (EXPLANATION_PROMPT.to_string(), clean_text)
+                (prompt(EXPLANATION_PROMPT), clean_text)
             }
             InputType::GrammarCheck => {
                 let clean_text = text.trim_start_matches("!:").trim();
-                (GRAMMAR_CHECK_PROMPT.to_string(), clean_text)
+                (prompt(GRAMMAR_CHECK_PROMPT), clean_text)
             }
             InputType::Freeform => {
                 let clean_text = text.trim_start_matches("??:").trim();
-                (FREEFORM_PROMPT.to_string(), clean_text)
+                (prompt(FREEFORM_PROMPT), clean_text)
             }
             InputType::Simplify => {
                 let clean_text = text.trim_start_matches("-:").trim();
-                (SIMPLIFY_PROMPT.to_string(), clean_text)
+                (prompt(SIMPLIFY_PROMPT), clean_text)
+            }
+            InputType::Preview => {
+                prepare_prompt(text.trim_start_matches("~:").trim(), overrides, language)
+            }
+            InputType::Monolingual => {
+                let clean_text = text.trim_start_matches("=:").trim();
+                (MONOLINGUAL_PROMPT.to_string(), clean_text)
             }
             _ => {
-                let prompt = match analyze_input(text) {
-                    InputType::RussianWord => RUSSIAN_WORD_PROMPT,
-                    InputType::RussianSentence => RUSSIAN_TO_GERMAN_PROMPT,
-                    InputType::GermanWord => GERMAN_WORD_PROMPT,
-                    InputType::GermanSentence => GERMAN_SENTENCE_PROMPT,
+                let prompt = match input_type {
+                    InputType::TargetWord => template_examples(
+                        &prompt(TARGET_WORD_PROMPT),
+                        &format!("{} sentence - German translation", language.name()),
+                    ),
+                    InputType::TargetSentence => prompt(TARGET_TO_GERMAN_PROMPT),
+                    InputType::GermanWord => template_examples(
+                        &prompt(GERMAN_WORD_PROMPT),
+                        &format!("German sentence - {} translation", language.name()),
+                    ),
+                    InputType::GermanSentence => prompt(GERMAN_SENTENCE_PROMPT),
                     InputType::Explanation
                     | InputType::GrammarCheck
                     | InputType::Freeform
-                    | InputType::Simplify => {
+                    | InputType::Simplify
+                    | InputType::Preview
+                    | InputType::Monolingual => {
                         unreachable!()
                     }
                 };
-                (prompt.to_string(), text)
+                let sentences = split_sentences(text);
+                if matches!(
+                    input_type,
+                    InputType::TargetSentence | InputType::GermanSentence
+                ) && sentences.len() > 1
+                {
+                    let numbered = sentences
+                        .iter()
+                        .enumerate()
+                        .map(|(i, sentence)| format!("{}. {}", i + 1, sentence))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let alignment = SENTENCE_ALIGNMENT_PROMPT.replace("{sentences}", &numbered);
+                    return (format!("{}\n\n{}", prompt, alignment), "");
+                }
+                (prompt, text)
             }
         }
     }
 }
 
-pub fn add_translation(translation: Translation) -> Result<()> {
+/// Validates the entry and brings it into its stored shape. Every save goes
+/// through here, so this is where STORE_EXAMPLES=false drops the examples.
+fn prepare_translation(mut translation: Translation) -> Result<Translation> {
     if !translation.is_valid() {
         return Err("Invalid translation data".into());
     }
+    if !store_examples() {
+        translation.examples.clear();
+    }
 
-    let mut translations = read_translations()?;
-
-    // Remove existing translations with the same original or translation text
-    translations.retain(|t| {
-        t.original.to_lowercase() != translation.original.to_lowercase()
-            && t.translation.to_lowercase() != translation.translation.to_lowercase()
-    });
+    // German nouns are always displayed capitalized, whatever the input casing
+    translation.original = translation.original.trim().to_string();
+    if translation.is_noun() {
+        let mut chars = translation.original.chars();
+        if let Some(first) = chars.next() {
+            translation.original = first.to_uppercase().chain(chars).collect();
+        }
+    }
+    Ok(translation)
+}
 
+/// Replaces the entry with the same German original. Entries that only share
+/// the translation are different words and stay.
+pub fn insert_deduplicated(translations: &mut Vec<Translation>, translation: Translation) {
+    translations.retain(|t| t.key() != translation.key());
     translations.push(translation);
+}
 
-    write_translations(&translations)?;
+/// Examples are saved unless STORE_EXAMPLES=false, which keeps the storage
+/// small; replies still show the examples of a fresh lookup.
+fn store_examples() -> bool {
+    env::var("STORE_EXAMPLES")
+        .map(|value| {
+            !matches!(
+                value.trim().to_lowercase().as_str(),
+                "false" | "0" | "no" | "off"
+            )
+        })
+        .unwrap_or(true)
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Returned when a new word would go over MAX_WORDS and the policy is to
+/// refuse it, or no stored word is mastered and can be evicted.
+#[derive(Debug)]
+pub struct WordLimitReached(pub usize);
+
+impl fmt::Display for WordLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The dictionary is full ({} words)", self.0)
+    }
+}
+
+impl std::error::Error for WordLimitReached {}
+
+/// What happens to a new word once MAX_WORDS words are stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WordCapPolicy {
+    Refuse,
+    /// Drop the best learned of the mastered words to make room. The
+    /// vocabulary is shared by all chats, so mastery is judged with the
+    /// operator's EVICT_MIN_ATTEMPTS (default: the per-chat default of
+    /// /settings) rather than any one chat's setting. With no mastered word
+    /// the new one is refused.
+    EvictMastered {
+        min_attempts: u32,
+    },
+}
+
+impl WordCapPolicy {
+    fn from_env() -> Self {
+        match env::var("WORD_CAP_POLICY") {
+            Ok(value) => match value.trim().to_lowercase().as_str() {
+                "refuse" => Self::Refuse,
+                "evict" => Self::EvictMastered {
+                    min_attempts: get_evict_min_attempts(),
+                },
+                _ => {
+                    log::warn!(
+                        "Invalid WORD_CAP_POLICY '{}', expected refuse or evict. Using refuse.",
+                        value
+                    );
+                    Self::Refuse
+                }
+            },
+            Err(_) => Self::Refuse,
+        }
+    }
+}
+
+fn get_evict_min_attempts() -> u32 {
+    env::var("EVICT_MIN_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .unwrap_or_else(|| UserConfig::default().min_attempts_for_mastery)
+}
+
+fn get_max_words() -> Option<usize> {
+    env::var("MAX_WORDS")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|max| *max > 0)
+}
+
+/// Makes room for a new word when the storage is at MAX_WORDS, or refuses
+/// it. Updates of stored words always go through.
+fn enforce_word_cap(translation: &Translation) -> Result<()> {
+    let Some(max_words) = get_max_words() else {
+        return Ok(());
+    };
+    let mut translations = read_translations()?;
+    if make_room(
+        &mut translations,
+        translation,
+        max_words,
+        WordCapPolicy::from_env(),
+    )? {
+        write_translations(&translations)?;
+    }
+    Ok(())
+}
+
+/// Evicts words from `translations` until the new one fits under
+/// `max_words`, and returns whether any were evicted.
+fn make_room(
+    translations: &mut Vec<Translation>,
+    translation: &Translation,
+    max_words: usize,
+    policy: WordCapPolicy,
+) -> Result<bool> {
+    if translations.len() < max_words || translations.iter().any(|t| t.key() == translation.key()) {
+        return Ok(false);
+    }
+    let WordCapPolicy::EvictMastered { min_attempts } = policy else {
+        return Err(WordLimitReached(max_words).into());
+    };
 
+    // Highest accuracy first, then the most answers behind it
+    let value = |t: &Translation| {
+        let total = t.correct_answers + t.wrong_answers;
+        (t.correct_answers as f64 / total as f64, total)
+    };
+    while translations.len() >= max_words {
+        let Some(evicted) = translations
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches!(mastery(t, min_attempts), Mastery::Mastered))
+            .max_by(|(_, a), (_, b)| {
+                let (a, b) = (value(a), value(b));
+                a.0.total_cmp(&b.0).then(a.1.cmp(&b.1))
+            })
+            .map(|(i, _)| i)
+        else {
+            return Err(WordLimitReached(max_words).into());
+        };
+        let evicted = translations.remove(evicted);
+        log::info!("Word limit reached, evicting '{}'", evicted.original);
+    }
+    Ok(true)
+}
+
+pub fn add_translation(translation: Translation) -> Result<()> {
+    let mut translation = prepare_translation(translation)?;
+    translation.added_at.get_or_insert_with(unix_now);
+    let _guard = lock_storage();
+    enforce_word_cap(&translation)?;
+    storage().upsert(translation)?;
+    vocabulary_changed();
     Ok(())
 }
 
+/// Adds many entries with a single storage write instead of one per word.
+fn add_translations_batch(storage: &dyn Storage, batch: Vec<Translation>) -> Result<usize> {
+    let batch = batch
+        .into_iter()
+        .map(prepare_translation)
+        .collect::<Result<Vec<_>>>()?;
+
+    let _guard = lock_storage();
+    let mut translations = storage.read()?;
+    let count = batch.len();
+    let max_words = get_max_words();
+    let policy = WordCapPolicy::from_env();
+    for translation in batch {
+        if let Some(max_words) = max_words {
+            make_room(&mut translations, &translation, max_words, policy)?;
+        }
+        insert_deduplicated(&mut translations, translation);
+    }
+    storage.write(&translations)?;
+    vocabulary_changed();
+    Ok(count)
+}
+
 pub fn get_storage_path() -> String {
     std::env::var("STORAGE_FILE").unwrap_or_else(|_| "translations_storage.json".to_string())
 }
 
+pub fn storage_last_modified() -> Option<SystemTime> {
+    storage().last_modified()
+}
+
 pub fn read_translations() -> Result<Vec<Translation>> {
-    let path = get_storage_path();
-    if !std::path::Path::new(&path).exists() {
-        fs::write(&path, "[]")?;
-    }
-    if let Ok(data) = fs::read_to_string(&path) {
-        let translations: Vec<Translation> = serde_json::from_str(&data)?;
-        Ok(translations)
-    } else {
-        Ok(Vec::new())
-    }
+    storage().read()
 }
 
 fn write_translations(translations: &[Translation]) -> Result<()> {
-    let path = get_storage_path();
-    let data = serde_json::to_string(translations)?;
-    fs::write(&path, data)?;
+    storage().write(translations)?;
+    vocabulary_changed();
     Ok(())
 }
 
+/// Looks a word up on either side without loading the whole vocabulary where
+/// the storage backend supports it.
+pub fn get_translation(word: &str) -> Result<Option<Translation>> {
+    storage().find(word)
+}
+
+/// Looks a German word up by its original only.
+pub fn get_by_original(word: &str) -> Result<Option<Translation>> {
+    storage().find_by_original(word)
+}
+
+/// Looks a word in the target language up by its translation only.
+pub fn get_by_translation(word: &str) -> Result<Option<Translation>> {
+    storage().find_by_translation(word)
+}
+
 pub fn find_translation<'a>(
     word: &str,
     translations: &'a [Translation],
 ) -> Option<&'a Translation> {
-    translations.iter().find(|t| {
-        t.original.to_lowercase() == word.to_lowercase()
-            || t.translation.to_lowercase() == word.to_lowercase()
-    })
+    translations.iter().find(|t| t.matches(word))
+}
+
+pub fn find_by_original<'a>(
+    word: &str,
+    translations: &'a [Translation],
+) -> Option<&'a Translation> {
+    translations.iter().find(|t| t.matches_original(word))
+}
+
+pub fn find_by_translation<'a>(
+    word: &str,
+    translations: &'a [Translation],
+) -> Option<&'a Translation> {
+    translations.iter().find(|t| t.matches_translation(word))
+}
+
+pub fn search_translations<'a>(
+    query: &str,
+    translations: &'a [Translation],
+) -> Vec<&'a Translation> {
+    let query = query.to_lowercase();
+    translations
+        .iter()
+        .filter(|t| {
+            t.original.to_lowercase().contains(&query)
+                || t.translation.to_lowercase().contains(&query)
+        })
+        .collect()
+}
+
+/// Below this similarity stored words are too unrelated to suggest.
+const MIN_SIMILAR_WORD_SCORE: f64 = 0.75;
+
+/// Stored words spelled most like `original`, best first, leaving out the
+/// word itself.
+pub fn similar_words<'a>(
+    original: &str,
+    translations: &'a [Translation],
+    limit: usize,
+) -> Vec<&'a Translation> {
+    let key = canonical_key(original);
+    let mut scored: Vec<(f64, &Translation)> = translations
+        .iter()
+        .filter(|t| t.key() != key && !t.archived)
+        .map(|t| (jaro_winkler(&key, &t.key()), t))
+        .filter(|(score, _)| *score >= MIN_SIMILAR_WORD_SCORE)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(limit).map(|(_, t)| t).collect()
+}
+
+/// Merges grammar forms, conjugations and examples from a fresh AI response
+/// into an existing entry without touching its statistics. Returns a
+/// description of everything that was added.
+pub fn merge_translation(existing: &mut Translation, fresh: Translation) -> Vec<String> {
+    let mut added = Vec::new();
+
+    for form in fresh.grammar_forms {
+        if !existing
+            .grammar_forms
+            .iter()
+            .any(|f| f.to_lowercase() == form.to_lowercase())
+        {
+            added.push(format!("grammar form: {}", form));
+            existing.grammar_forms.push(form);
+        }
+    }
+
+    if let Some(conjugations) = fresh.conjugations {
+        let existing_conjugations = existing.conjugations.get_or_insert_with(Vec::new);
+        for conj in conjugations {
+            if !existing_conjugations.contains(&conj) {
+                added.push(format!("conjugation: {}", conj));
+                existing_conjugations.push(conj);
+            }
+        }
+    }
+
+    if existing.syllables.is_none() {
+        if let Some(syllables) = fresh.syllables {
+            added.push(format!("syllables: {}", syllables));
+            existing.syllables = Some(syllables);
+        }
+    }
+
+    for example in fresh.examples {
+        if !existing
+            .examples
+            .iter()
+            .any(|e| e.german.to_lowercase() == example.german.to_lowercase())
+        {
+            added.push(format!("example: {}", example.german));
+            existing.examples.push(example);
+        }
+    }
+
+    added
 }
 
 pub fn clear_translations() -> Result<()> {
-    let path = get_storage_path();
-    fs::write(&path, "[]")?;
-    Ok(())
+    let _guard = lock_storage();
+    write_translations(&[])
 }
 
-pub fn import_translations(json_data: &str) -> Result<usize> {
-    let translations: Vec<Translation> = serde_json::from_str(json_data)?;
+/// Version of the export envelope. Version 1 was a bare array of entries.
+const EXPORT_VERSION: u32 = 2;
 
-    if !translations.iter().all(|t| t.is_valid()) {
-        return Err("Invalid translation data in import file".into());
+#[derive(Serialize)]
+struct ExportEnvelope {
+    version: u32,
+    translations: Vec<Translation>,
+}
+
+/// Serializes the vocabulary for /export, wrapped with its format version.
+pub fn export_translations(translations: Vec<Translation>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&ExportEnvelope {
+        version: EXPORT_VERSION,
+        translations,
+    })?)
+}
+
+/// Shape of an import before its entries are migrated and parsed.
+#[derive(Deserialize)]
+struct ImportEnvelope {
+    version: u32,
+    translations: Vec<serde_json::Value>,
+}
+
+/// Accepts both a versioned envelope and a bare array (version 1), migrating
+/// older entries to the current format.
+fn parse_import(json_data: &str) -> Result<Vec<Translation>> {
+    let value: serde_json::Value = serde_json::from_str(json_data)?;
+    let envelope = match value {
+        serde_json::Value::Array(translations) => ImportEnvelope {
+            version: 1,
+            translations,
+        },
+        value => serde_json::from_value(value)?,
+    };
+    if envelope.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this bot supports ({})",
+            envelope.version, EXPORT_VERSION
+        )
+        .into());
     }
+    migrate_import(envelope.version, envelope.translations)
+}
 
-    write_translations(&translations)?;
-    Ok(translations.len())
+/// Brings entries of an older export version up to the current format.
+/// Fields added since then are filled with defaults by serde.
+fn migrate_import(version: u32, mut entries: Vec<serde_json::Value>) -> Result<Vec<Translation>> {
+    if version < 2 {
+        // Version 1 files were often edited by hand and leave out empty lists
+        for entry in entries.iter_mut().filter_map(|e| e.as_object_mut()) {
+            for key in ["grammar_forms", "examples"] {
+                entry
+                    .entry(key)
+                    .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+            }
+        }
+    }
+
+    entries
+        .into_iter()
+        .map(|entry| Ok(serde_json::from_value(entry)?))
+        .collect()
+}
+
+/// Replaces the database with the imported entries, or adds them to it when
+/// `merge` is set.
+pub fn import_translations(json_data: &str, merge: bool) -> Result<usize> {
+    import_entries(parse_import(json_data)?, merge)
+}
+
+/// Validates parsed entries and stores them, shared by the JSON and CSV imports.
+pub fn import_entries(translations: Vec<Translation>, merge: bool) -> Result<usize> {
+    import_into(storage(), translations, merge)
+}
+
+fn import_into(
+    storage: &dyn Storage,
+    translations: Vec<Translation>,
+    merge: bool,
+) -> Result<usize> {
+    // An empty import would silently wipe the database
+    if translations.is_empty() {
+        return Err("Import file contains no translations".into());
+    }
+
+    let mut failures = translations
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| t.validate().err().map(|e| (i, t, e)));
+    if let Some((i, translation, e)) = failures.next() {
+        let failed = 1 + failures.count();
+        return Err(format!(
+            "Invalid entry #{} ('{}'): {}. {} of {} entries failed validation",
+            i,
+            translation.original,
+            e,
+            failed,
+            translations.len()
+        )
+        .into());
+    }
+
+    if merge {
+        return add_translations_batch(storage, translations);
+    }
+
+    // A replacing import applies the batch to an empty list
+    let mut imported = Vec::with_capacity(translations.len());
+    for translation in translations {
+        insert_deduplicated(&mut imported, prepare_translation(translation)?);
+    }
+
+    let _guard = lock_storage();
+    storage.write(&imported)?;
+    vocabulary_changed();
+    Ok(imported.len())
 }
 
 pub fn delete_translation(word: &str) -> Result<bool> {
-    let mut translations = read_translations()?;
-    let initial_len = translations.len();
+    let _guard = lock_storage();
+    let deleted = storage().delete(word)?;
+    if deleted {
+        vocabulary_changed();
+    }
+    Ok(deleted)
+}
 
-    translations.retain(|t| {
-        t.original.to_lowercase() != word.to_lowercase()
-            && t.translation.to_lowercase() != word.to_lowercase()
-    });
+const BULLET_PREFIXES: [&str; 4] = ["- ", "• ", "* ", "– "];
+
+/// Strips markdown code fences, any preamble before the first content line
+/// (e.g. "Here is the translation:") and bullet prefixes, so the positional
+/// parsing in `parse_translation_response` sees one entry per line.
+pub fn clean_model_output(response: &str) -> String {
+    let lines: Vec<&str> = response
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("```"))
+        .map(|line| {
+            BULLET_PREFIXES
+                .iter()
+                .find_map(|prefix| line.strip_prefix(prefix))
+                .unwrap_or(line)
+                .trim()
+        })
+        .collect();
 
-    write_translations(&translations)?;
-    Ok(initial_len != translations.len())
+    let first_content_line = lines
+        .iter()
+        .position(|line| !line.is_empty() && !line.ends_with(':'))
+        .unwrap_or(lines.len());
+
+    lines[first_content_line..]
+        .join("\n")
+        .trim_end()
+        .to_string()
 }
 
-pub fn parse_translation_response(original: &str, response: &str) -> Translation {
+pub fn parse_translation_response(
+    original: &str,
+    response: &str,
+    language: TargetLanguage,
+) -> Translation {
+    let response = clean_model_output(response);
     let lines: Vec<&str> = response.lines().collect();
-    let is_russian_input = original
-        .chars()
-        .any(|c| matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}'));
-
-    let mut translation = if is_russian_input {
-        Translation {
-            original: lines.get(1).unwrap_or(&"").trim().to_string(),
-            translation: lines.first().unwrap_or(&original).trim().to_string(),
-            grammar_forms: Vec::new(),
-            conjugations: None,
-            examples: Vec::new(),
-            correct_answers: 0,
-            wrong_answers: 0,
-        }
+    let is_target_input = is_target_language(original, language);
+
+    let (headword, other) = (
+        lines.first().unwrap_or(&original).trim().to_string(),
+        lines.get(1).unwrap_or(&"").trim().to_string(),
+    );
+    // The reply starts with the input word, so target-language input has the
+    // German word second
+    let (original, translation) = if is_target_input {
+        (other, headword)
     } else {
-        Translation {
-            original: lines.first().unwrap_or(&original).trim().to_string(),
-            translation: lines.get(1).unwrap_or(&"").trim().to_string(),
-            grammar_forms: Vec::new(),
-            conjugations: None,
-            examples: Vec::new(),
-            correct_answers: 0,
-            wrong_answers: 0,
-        }
+        (headword, other)
+    };
+    let mut translation = Translation {
+        original,
+        translation,
+        ..Default::default()
     };
 
     if lines.len() > 2 {
@@ -364,10 +1305,15 @@ pub fn parse_translation_response(original: &str, response: &str) -> Translation
         let mut conjugations = Vec::new();
         let mut in_conjugation_section = false;
 
-        while current_line < lines.len() && !lines[current_line].trim().starts_with('1') {
+        while current_line < lines.len() && strip_example_number(lines[current_line]).is_none() {
             let line = lines[current_line].trim();
 
-            if !line.is_empty() {
+            if let Some(syllables) = line.strip_prefix(SYLLABLES_PREFIX) {
+                let syllables = syllables.trim();
+                if !syllables.is_empty() {
+                    translation.syllables = Some(syllables.to_string());
+                }
+            } else if !line.is_empty() {
                 if line.contains("ich ")
                     || line.contains("du ")
                     || line.contains("er/")
@@ -390,33 +1336,27 @@ pub fn parse_translation_response(original: &str, response: &str) -> Translation
             translation.conjugations = Some(conjugations);
         }
 
-        while current_line < lines.len() {
-            let line = lines[current_line].trim();
-            if line.starts_with('1') || line.starts_with('2') {
-                let parts: Vec<&str> = line.split('-').map(|s| s.trim()).collect();
-                if let Some((german_part, russian_parts)) = parts.split_first() {
-                    let german = german_part
-                        .trim_start_matches('1')
-                        .trim_start_matches('2')
-                        .trim()
-                        .to_string();
-                    let russian = russian_parts.join("-").trim().to_string();
-
-                    if is_russian_input {
-                        translation.examples.push(Example {
-                            german: russian,
-                            russian: german,
-                        });
-                    } else {
-                        translation.examples.push(Example { german, russian });
-                    }
-                }
+        let max_examples = get_example_count();
+        while current_line < lines.len() && translation.examples.len() < max_examples {
+            if let Some(line) = strip_example_number(lines[current_line]) {
+                // The prompt puts the input language's sentence first, so for
+                // target-language input the German sentence is the second half
+                let (first, second) = split_example(line);
+                let (german, russian) = if is_target_input {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                translation.examples.push(Example {
+                    german: german.to_string(),
+                    russian: russian.to_string(),
+                });
             }
             current_line += 1;
         }
     }
 
-    if !is_russian_input {
+    if !is_target_input {
         let words: Vec<&str> = translation.original.split_whitespace().collect();
         if words.len() == 2 && ["der", "die", "das"].contains(&words[0]) {
             translation.grammar_forms.push(words[0].to_string());
@@ -427,6 +1367,124 @@ pub fn parse_translation_response(original: &str, response: &str) -> Translation
     translation
 }
 
+const EXAMPLE_DELIMITERS: [&str; 3] = [" - ", " – ", " — "];
+
+/// Splits "Ich schreibe eine E-Mail - Я пишу письмо" into its two sides. The
+/// prompts ask for " - ", other dashes are accepted too. Without a spaced
+/// dash, the first hyphen that isn't inside a word ("E-Mail", "20-jährig")
+/// is used.
+pub fn split_example(line: &str) -> (&str, &str) {
+    let spaced = EXAMPLE_DELIMITERS
+        .iter()
+        .filter_map(|delimiter| line.find(delimiter).map(|i| (i, delimiter.len())))
+        .min();
+    let split = spaced.or_else(|| {
+        line.char_indices()
+            .filter(|(_, c)| *c == '-')
+            .find(|(i, _)| {
+                let before = line[..*i].chars().next_back();
+                let after = line[i + 1..].chars().next();
+                !(before.is_some_and(char::is_alphanumeric)
+                    && after.is_some_and(char::is_alphanumeric))
+            })
+            .map(|(i, _)| (i, 1))
+    });
+
+    match split {
+        Some((i, len)) => (line[..i].trim(), line[i + len..].trim()),
+        None => (line.trim(), ""),
+    }
+}
+
+/// Returns the line without its leading "1." / "2)" style number, or `None`
+/// if the line isn't a numbered example.
+fn strip_example_number(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == line.len() {
+        return None;
+    }
+    Some(rest.trim_start_matches(['.', ')']).trim())
+}
+
+/// Pairs each sentence of a multi-sentence text with its line of a reply to
+/// `SENTENCE_ALIGNMENT_PROMPT`. `None` if the text is a single sentence or
+/// the reply doesn't have one numbered line per sentence.
+pub fn format_aligned_sentences(text: &str, response: &str) -> Option<String> {
+    let sentences = split_sentences(text);
+    if sentences.len() < 2 {
+        return None;
+    }
+    let translations: Vec<&str> = response
+        .lines()
+        .filter_map(strip_example_number)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if translations.len() != sentences.len() {
+        return None;
+    }
+
+    Some(
+        sentences
+            .iter()
+            .zip(translations)
+            .map(|(sentence, translation)| format!("{} ➜ {}", sentence, translation))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    )
+}
+
+/// Splits a translation like "дом, здание" or "дом; здание / строение" into
+/// its individual meanings.
+pub fn split_meanings(text: &str) -> Vec<String> {
+    text.split([',', ';', '/'])
+        .map(|meaning| meaning.trim().to_string())
+        .filter(|meaning| !meaning.is_empty())
+        .collect()
+}
+
+/// Formats a translation with several meanings as a numbered list on the
+/// following lines, and a single meaning inline.
+pub fn format_meanings(text: &str) -> String {
+    let meanings = split_meanings(text);
+    if meanings.len() <= 1 {
+        return text.trim().to_string();
+    }
+    meanings
+        .iter()
+        .enumerate()
+        .map(|(i, meaning)| format!("\n{}. {}", i + 1, meaning))
+        .collect()
+}
+
+/// Turns the grammar checker's `*word*` mistake markers into MarkdownV2 bold,
+/// escaping everything else. A stray unpaired `*` is shown literally.
+pub fn format_grammar_check(response: &str) -> String {
+    let response = response.replace("**", "*");
+    let parts: Vec<&str> = response.split('*').collect();
+    let last = parts.len() - 1;
+
+    let mut formatted = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        let escaped = markdown::escape(part);
+        let is_marked = i % 2 == 1;
+        if is_marked && i == last {
+            formatted.push_str("\\*");
+            formatted.push_str(&escaped);
+        } else if is_marked && !part.trim().is_empty() {
+            formatted.push_str(&format!("*{}*", escaped));
+        } else {
+            formatted.push_str(&escaped);
+        }
+    }
+    formatted
+}
+
+/// Cards stay below Telegram's 4096 character limit with room for footers
+/// like the similar words.
+const MAX_CARD_CHARS: usize = 3500;
+const EXAMPLES_TRIMMED_NOTE: &str = "(примеры сокращены)\n";
+
 pub fn format_translation_response(translation: &Translation) -> String {
     let mut response = String::new();
 
@@ -438,10 +1496,10 @@ pub fn format_translation_response(translation: &Translation) -> String {
         })
         .unwrap_or(false);
 
-    let is_russian = translation
-        .original
-        .chars()
-        .any(|c| matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}'));
+    // Only legacy entries, saved before parsing always put German in
+    // `original`, can have a swapped Cyrillic headword. New entries never do,
+    // whatever the target language, so this doesn't depend on it.
+    let is_target = contains_cyrillic(&translation.original);
 
     let already_has_article = translation
         .original
@@ -452,7 +1510,7 @@ pub fn format_translation_response(translation: &Translation) -> String {
 
     if is_noun {
         if let Some(article) = translation.grammar_forms.first() {
-            if is_russian {
+            if is_target {
                 response.push_str(&format!("➡️ {}\n", translation.original));
                 response.push_str(&format!("⬅️ {} {}\n", article, translation.translation));
             } else {
@@ -461,12 +1519,24 @@ pub fn format_translation_response(translation: &Translation) -> String {
                 } else {
                     response.push_str(&format!("➡️ {} {}\n", article, translation.original));
                 }
-                response.push_str(&format!("⬅️ {}\n", translation.translation));
+                response.push_str(&format!(
+                    "⬅️ {}\n",
+                    format_meanings(&translation.translation)
+                ));
             }
         }
     } else {
         response.push_str(&format!("➡️ {}\n", translation.original));
-        response.push_str(&format!("⬅️ {}\n", translation.translation));
+        response.push_str(&format!(
+            "⬅️ {}\n",
+            format_meanings(&translation.translation)
+        ));
+    }
+
+    if let Some(syllables) = &translation.syllables {
+        // Under the headword, the first line
+        let headword_end = response.find('\n').map_or(response.len(), |i| i + 1);
+        response.insert_str(headword_end, &format!("🗣 {}\n", syllables));
     }
 
     if !translation.grammar_forms.is_empty() {
@@ -483,17 +1553,401 @@ pub fn format_translation_response(translation: &Translation) -> String {
         }
     }
 
+    let mut tail = String::new();
+    if let Some(note) = &translation.note {
+        tail.push_str(&format!("\n📝 Заметка:\n{}\n", note));
+    }
+
+    if !translation.tags.is_empty() {
+        let tags: Vec<String> = translation.tags.iter().map(|t| format!("#{}", t)).collect();
+        tail.push_str(&format!("\n🏷 {}\n", tags.join(" ")));
+    }
+
+    // Examples are what gets dropped when the card would be too long to send
     if !translation.examples.is_empty() {
-        response.push_str("\n📚 Примеры:\n");
+        let mut budget = MAX_CARD_CHARS
+            .saturating_sub(response.chars().count() + tail.chars().count())
+            .saturating_sub(EXAMPLES_TRIMMED_NOTE.chars().count());
+        let mut examples = String::from("\n📚 Примеры:\n");
+        let mut trimmed = false;
         for (i, example) in translation.examples.iter().enumerate() {
-            response.push_str(&format!(
-                "{} {} — {}\n",
-                i + 1,
-                example.german,
-                example.russian
-            ));
+            let line = format!("{} {} — {}\n", i + 1, example.german, example.russian);
+            let len = line.chars().count();
+            if len > budget {
+                trimmed = true;
+                break;
+            }
+            budget -= len;
+            examples.push_str(&line);
+        }
+        if trimmed {
+            examples.push_str(EXAMPLES_TRIMMED_NOTE);
         }
+        response.push_str(&examples);
     }
 
+    response.push_str(&tail);
+    if response.chars().count() > MAX_CARD_CHARS {
+        response = response.chars().take(MAX_CARD_CHARS).collect();
+    }
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestStorage;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashMap;
+
+    fn practiced(original: &str, correct: u32, wrong: u32, recent: &[bool]) -> Translation {
+        Translation {
+            original: original.to_string(),
+            translation: format!("{} (ru)", original),
+            correct_answers: correct,
+            wrong_answers: wrong,
+            recent_results: recent.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// How often each word is picked over `draws` seeded draws.
+    fn pick_counts(translations: &[Translation], draws: usize) -> HashMap<String, usize> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = HashMap::new();
+        for _ in 0..draws {
+            let picked = pick_weighted_translation(translations, None, false, &mut rng).unwrap();
+            *counts.entry(picked.original).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn recent_results_shift_selection_frequency() {
+        // Same lifetime error rate, different recent answers
+        let translations = vec![
+            practiced("Streak", 6, 4, &[true, true, true]),
+            practiced("Plain", 6, 4, &[]),
+            practiced("Missed", 6, 4, &[true, false]),
+        ];
+        let counts = pick_counts(&translations, 10_000);
+
+        // A streak of three halves the weight three times
+        assert!(counts["Streak"] * 6 < counts["Plain"]);
+        assert!(counts["Plain"] < counts["Missed"]);
+    }
+
+    #[test]
+    fn pick_share_follows_practice_weight() {
+        let translations = vec![
+            practiced("Hard", 1, 9, &[false]),
+            practiced("Easy", 9, 1, &[true]),
+            practiced("Fine", 8, 2, &[]),
+            practiced("New", 0, 0, &[]),
+        ];
+        let draws = 20_000;
+        let counts = pick_counts(&translations, draws);
+        let total_weight: f64 = translations.iter().map(practice_weight).sum();
+
+        for translation in &translations {
+            let expected = practice_weight(translation) / total_weight;
+            let share =
+                counts.get(&translation.original).copied().unwrap_or(0) as f64 / draws as f64;
+            assert!(
+                (share - expected).abs() < 0.02,
+                "{}: picked {:.3}, expected {:.3}",
+                translation.original,
+                share,
+                expected
+            );
+        }
+        assert!(counts["Hard"] > counts["Easy"] * 3);
+    }
+
+    /// Keeps the vocabulary in memory and counts full rewrites.
+    #[derive(Default)]
+    struct CountingStorage {
+        translations: std::sync::Mutex<Vec<Translation>>,
+        writes: AtomicUsize,
+    }
+
+    impl Storage for CountingStorage {
+        fn read(&self) -> Result<Vec<Translation>> {
+            Ok(self.translations.lock().unwrap().clone())
+        }
+
+        fn write(&self, translations: &[Translation]) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            *self.translations.lock().unwrap() = translations.to_vec();
+            Ok(())
+        }
+
+        fn last_modified(&self) -> Option<SystemTime> {
+            None
+        }
+    }
+
+    fn import_fixture(count: usize) -> Vec<Translation> {
+        (0..count)
+            .map(|i| Translation {
+                original: format!("Wort{}", i),
+                translation: format!("слово{}", i),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn import_does_one_write() {
+        for merge in [true, false] {
+            let storage = CountingStorage::default();
+            let imported = import_into(&storage, import_fixture(500), merge).unwrap();
+
+            assert_eq!(imported, 500);
+            assert_eq!(storage.writes.load(Ordering::Relaxed), 1);
+            assert_eq!(storage.read().unwrap().len(), 500);
+        }
+    }
+
+    const EVICT: WordCapPolicy = WordCapPolicy::EvictMastered { min_attempts: 5 };
+
+    #[test]
+    fn eviction_skips_words_without_enough_answers() {
+        let stored = vec![practiced("Known", 9, 1, &[]), practiced("Lucky", 1, 0, &[])];
+
+        let mut translations = stored.clone();
+        let new = practiced("Neu", 0, 0, &[]);
+        assert!(make_room(&mut translations, &new, 2, EVICT).unwrap());
+        assert_eq!(translations.len(), 1);
+        assert_eq!(translations[0].original, "Lucky");
+
+        let mut translations = vec![stored[1].clone(), new.clone()];
+        let error =
+            make_room(&mut translations, &practiced("Anders", 0, 0, &[]), 2, EVICT).unwrap_err();
+        assert!(error.is::<WordLimitReached>());
+
+        let mut translations = stored.clone();
+        assert!(make_room(&mut translations, &new, 2, WordCapPolicy::Refuse).is_err());
+        assert!(!make_room(&mut translations, &stored[0], 2, WordCapPolicy::Refuse).unwrap());
+
+        // Words still being learned are never evicted, whatever their accuracy
+        let mut translations = vec![practiced("Hard", 4, 6, &[]), practiced("Okay", 7, 3, &[])];
+        assert!(make_room(&mut translations, &new, 2, EVICT).is_err());
+        assert_eq!(translations.len(), 2);
+    }
+
+    #[test]
+    fn batch_adds_respect_the_word_cap() {
+        let mut env = TestStorage::new("batch-word-cap");
+        env.env("MAX_WORDS", "2");
+        env.env("WORD_CAP_POLICY", "evict");
+        let storage = CountingStorage::default();
+        storage
+            .write(&[practiced("Known", 9, 1, &[]), practiced("Lucky", 1, 0, &[])])
+            .unwrap();
+
+        add_translations_batch(&storage, vec![practiced("Neu", 0, 0, &[])]).unwrap();
+        let originals: Vec<String> = storage
+            .read()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.original)
+            .collect();
+        assert_eq!(originals, ["Lucky", "Neu"]);
+
+        assert!(add_translations_batch(&storage, vec![practiced("Anders", 0, 0, &[])]).is_err());
+        assert_eq!(storage.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn grammar_check_markers_become_bold() {
+        assert_eq!(
+            format_grammar_check("Ich *habe* gestern Fußball gespielt."),
+            "Ich *habe* gestern Fußball gespielt\\."
+        );
+        assert_eq!(
+            format_grammar_check("Das ist **mein** Haus (nicht dein)!"),
+            "Das ist *mein* Haus \\(nicht dein\\)\\!"
+        );
+    }
+
+    #[test]
+    fn grammar_check_escapes_unpaired_markers() {
+        assert_eq!(format_grammar_check("2 * 3 = 6."), "2 \\* 3 \\= 6\\.");
+    }
+
+    #[test]
+    fn examples_are_dropped_on_every_save_path() {
+        let mut env = TestStorage::new("store-examples");
+        env.env("STORE_EXAMPLES", "false");
+        let with_examples = |original: &str| Translation {
+            original: original.to_string(),
+            translation: "дом".to_string(),
+            examples: vec![Example {
+                german: "Das Haus ist alt.".to_string(),
+                russian: "Дом старый.".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        add_translation(with_examples("Haus")).unwrap();
+        let storage = CountingStorage::default();
+        import_into(&storage, vec![with_examples("Gebäude")], true).unwrap();
+
+        assert!(read_translations().unwrap()[0].examples.is_empty());
+        assert!(storage.read().unwrap()[0].examples.is_empty());
+    }
+
+    #[test]
+    fn unversioned_export_is_migrated() {
+        let json = r#"[
+            {"original": "Haus", "translation": "дом", "correct_answers": 3},
+            {"original": "Baum", "translation": "дерево", "grammar_forms": ["der"],
+             "conjugations": null, "examples": []}
+        ]"#;
+        let translations = parse_import(json).unwrap();
+
+        assert_eq!(translations.len(), 2);
+        assert_eq!(translations[0].correct_answers, 3);
+        assert!(translations[0].grammar_forms.is_empty());
+        assert!(translations[0].examples.is_empty());
+        assert_eq!(translations[1].grammar_forms, ["der"]);
+    }
+
+    #[test]
+    fn versioned_export_roundtrips() {
+        let exported = export_translations(import_fixture(3)).unwrap();
+        let translations = parse_import(std::str::from_utf8(&exported).unwrap()).unwrap();
+        assert_eq!(translations.len(), 3);
+        assert_eq!(translations[2].original, "Wort2");
+
+        let newer = r#"{"version": 99, "translations": []}"#;
+        assert!(parse_import(newer).is_err());
+    }
+
+    #[test]
+    fn examples_split_on_the_spaced_delimiter() {
+        assert_eq!(
+            split_example("Ich schreibe eine E-Mail. - Я пишу электронное письмо."),
+            ("Ich schreibe eine E-Mail.", "Я пишу электронное письмо.")
+        );
+        assert_eq!(
+            split_example("Er ist 20-jährig. - Ему 20 лет, он кое-что знает."),
+            ("Er ist 20-jährig.", "Ему 20 лет, он кое-что знает.")
+        );
+    }
+
+    #[test]
+    fn examples_fall_back_to_a_bare_dash() {
+        assert_eq!(
+            split_example("Die E-Mail ist da.-Письмо по e-mail пришло."),
+            ("Die E-Mail ist da.", "Письмо по e-mail пришло.")
+        );
+        assert_eq!(split_example("Nur ein Satz."), ("Nur ein Satz.", ""));
+    }
+
+    #[test]
+    fn refusal_reply_is_rejected() {
+        let response = "I'm sorry, but I can't help with that request.\nPlease try another word.";
+        let translation = parse_translation_response("Haus", response, TargetLanguage::Russian);
+        assert!(translation.check_model_output().is_err());
+    }
+
+    #[test]
+    fn well_formed_reply_is_accepted() {
+        let response = "das Haus\nдом\ndie Häuser\n1. Das Haus ist alt. - Дом старый.";
+        let translation = parse_translation_response("Haus", response, TargetLanguage::Russian);
+        assert_eq!(translation.check_model_output(), Ok(()));
+        assert_eq!(translation.original, "Haus");
+        assert_eq!(translation.translation, "дом");
+    }
+
+    #[test]
+    fn russian_input_examples_show_german_first() {
+        let response = "дом\nHaus\ndas\n1. Дом старый. - Das Haus ist alt.";
+        let translation = parse_translation_response("дом", response, TargetLanguage::Russian);
+        assert_eq!(translation.examples[0].german, "Das Haus ist alt.");
+        assert_eq!(translation.examples[0].russian, "Дом старый.");
+
+        let card = format_translation_response(&translation);
+        assert!(
+            card.contains("1 Das Haus ist alt. — Дом старый."),
+            "{}",
+            card
+        );
+    }
+
+    #[test]
+    fn clean_model_output_strips_code_fences() {
+        let response = "```\nHaus\nдом\nder Haus, die Häuser\n```";
+        assert_eq!(
+            clean_model_output(response),
+            "Haus\nдом\nder Haus, die Häuser"
+        );
+    }
+
+    #[test]
+    fn concurrent_adds_are_not_lost() {
+        let _storage = TestStorage::new("concurrent-adds");
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    add_translation(Translation {
+                        original: format!("Wort{}", i),
+                        translation: format!("слово{}", i),
+                        ..Default::default()
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let translations = read_translations().unwrap();
+        assert_eq!(translations.len(), 16);
+        for i in 0..16 {
+            assert!(find_by_original(&format!("Wort{}", i), &translations).is_some());
+        }
+    }
+
+    #[test]
+    fn clean_model_output_strips_preamble() {
+        let response = "Here is the translation:\n\n- Haus\n- дом";
+        assert_eq!(clean_model_output(response), "Haus\nдом");
+    }
+
+    #[test]
+    fn entries_sharing_only_the_translation_stay_separate() {
+        let entry = |original: &str, translation: &str| Translation {
+            original: original.to_string(),
+            translation: translation.to_string(),
+            ..Default::default()
+        };
+        let mut translations = Vec::new();
+        insert_deduplicated(&mut translations, entry("дом", "Haus"));
+        insert_deduplicated(&mut translations, entry("Haus", "дом"));
+        assert_eq!(translations.len(), 2);
+
+        assert_eq!(
+            find_by_original("дом", &translations).unwrap().translation,
+            "Haus"
+        );
+        assert_eq!(
+            find_by_translation("дом", &translations).unwrap().original,
+            "Haus"
+        );
+
+        insert_deduplicated(&mut translations, entry("haus", "жилище"));
+        assert_eq!(translations.len(), 2);
+        assert_eq!(
+            find_by_original("Haus", &translations).unwrap().translation,
+            "жилище"
+        );
+        assert_eq!(
+            find_by_original("дом", &translations).unwrap().translation,
+            "Haus"
+        );
+    }
+}
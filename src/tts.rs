@@ -0,0 +1,56 @@
+use std::env;
+
+use serde::Serialize;
+
+use crate::ai::http_client;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_TTS_API_URL: &str = "https://api.openai.com/v1/audio/speech";
+const DEFAULT_TTS_MODEL: &str = "tts-1";
+const DEFAULT_TTS_VOICE: &str = "alloy";
+
+#[derive(Serialize)]
+struct SpeechRequest<'a> {
+    model: String,
+    voice: String,
+    input: &'a str,
+    response_format: &'static str,
+}
+
+/// Whether a key for the speech endpoint is set, so optional audio can be
+/// skipped quietly instead of failing.
+pub fn is_tts_configured() -> bool {
+    env::var("TTS_API_KEY").is_ok() || env::var("OPENAI_API_KEY").is_ok()
+}
+
+/// Synthesizes German speech via an OpenAI-compatible speech endpoint and
+/// returns OGG/Opus audio suitable for a Telegram voice message.
+pub async fn synthesize_speech(text: &str) -> Result<Vec<u8>> {
+    let api_key = env::var("TTS_API_KEY")
+        .or_else(|_| env::var("OPENAI_API_KEY"))
+        .map_err(|_| "TTS_API_KEY environment variable not set")?;
+    let api_url = env::var("TTS_API_URL").unwrap_or_else(|_| DEFAULT_TTS_API_URL.to_string());
+
+    let request = SpeechRequest {
+        model: env::var("TTS_MODEL").unwrap_or_else(|_| DEFAULT_TTS_MODEL.to_string()),
+        voice: env::var("TTS_VOICE").unwrap_or_else(|_| DEFAULT_TTS_VOICE.to_string()),
+        input: text,
+        response_format: "opus",
+    };
+
+    let client = http_client();
+    let response = client
+        .post(&api_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Speech request failed with status: {}", status).into());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
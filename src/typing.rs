@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use teloxide::{prelude::*, types::ChatAction};
+use tokio::task::JoinHandle;
+
+/// Telegram clears a chat action after about five seconds.
+const TYPING_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Shows "typing…" in the chat until dropped, so slow AI replies don't look
+/// like the bot went silent.
+pub struct TypingIndicator(JoinHandle<()>);
+
+impl TypingIndicator {
+    pub fn start(bot: &Bot, chat_id: ChatId) -> Self {
+        let bot = bot.clone();
+        Self(tokio::spawn(async move {
+            loop {
+                if let Err(e) = bot.send_chat_action(chat_id, ChatAction::Typing).await {
+                    log::warn!("Failed to send typing action: {}", e);
+                    return;
+                }
+                tokio::time::sleep(TYPING_INTERVAL).await;
+            }
+        }))
+    }
+}
+
+impl Drop for TypingIndicator {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,429 @@
+use std::{collections::BTreeMap, env, fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use strsim::{jaro_winkler, normalized_levenshtein};
+
+use crate::{
+    input::InputType,
+    language::{default_target_language, TargetLanguage},
+    practice::PracticeDirection,
+    translation::Translation,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.85;
+const DEFAULT_STATS_INTERVAL: u32 = 10;
+const DEFAULT_MIN_ATTEMPTS_FOR_MASTERY: u32 = 5;
+const MIN_SIMILARITY_THRESHOLD: f64 = 0.5;
+
+/// Which words a practice session draws from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WordKind {
+    Noun,
+    Verb,
+    Other,
+}
+
+impl WordKind {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "noun" => Some(Self::Noun),
+            "verb" => Some(Self::Verb),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, translation: &Translation) -> bool {
+        let is_verb = translation
+            .conjugations
+            .as_ref()
+            .is_some_and(|conjugations| !conjugations.is_empty());
+        match self {
+            Self::Noun => translation.is_noun(),
+            Self::Verb => is_verb,
+            Self::Other => !translation.is_noun() && !is_verb,
+        }
+    }
+}
+
+impl fmt::Display for WordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Noun => write!(f, "noun"),
+            Self::Verb => write!(f, "verb"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// How practice answers are compared with the expected ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Similarity {
+    /// Forgiving with typos in long words, but lenient with short ones.
+    #[default]
+    #[serde(rename = "jaro")]
+    JaroWinkler,
+    /// Edit distance relative to the longer word.
+    Levenshtein,
+    /// Only an exact match counts.
+    Exact,
+}
+
+impl Similarity {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jaro" => Some(Self::JaroWinkler),
+            "levenshtein" => Some(Self::Levenshtein),
+            "exact" => Some(Self::Exact),
+            _ => None,
+        }
+    }
+
+    /// Score from 0.0 (unrelated) to 1.0 (identical).
+    pub fn score(&self, a: &str, b: &str) -> f64 {
+        match self {
+            Self::JaroWinkler => jaro_winkler(a, b),
+            Self::Levenshtein => normalized_levenshtein(a, b),
+            Self::Exact if a == b => 1.0,
+            Self::Exact => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Similarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::JaroWinkler => write!(f, "jaro"),
+            Self::Levenshtein => write!(f, "levenshtein"),
+            Self::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+/// Which built-in prompt a `/setprompt` override replaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptKind {
+    Word,
+    Sentence,
+    Explain,
+    Grammar,
+    Freeform,
+    Simplify,
+}
+
+impl PromptKind {
+    pub const NAMES: &'static str = "word, sentence, explain, grammar, freeform, simplify";
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "word" => Some(Self::Word),
+            "sentence" => Some(Self::Sentence),
+            "explain" => Some(Self::Explain),
+            "grammar" => Some(Self::Grammar),
+            "freeform" => Some(Self::Freeform),
+            "simplify" => Some(Self::Simplify),
+            _ => None,
+        }
+    }
+
+    pub fn for_input(input_type: &InputType) -> Option<Self> {
+        match input_type {
+            InputType::TargetWord | InputType::GermanWord => Some(Self::Word),
+            InputType::TargetSentence | InputType::GermanSentence => Some(Self::Sentence),
+            InputType::Explanation => Some(Self::Explain),
+            InputType::GrammarCheck => Some(Self::Grammar),
+            InputType::Freeform => Some(Self::Freeform),
+            InputType::Simplify => Some(Self::Simplify),
+            InputType::Preview | InputType::Monolingual => None,
+        }
+    }
+}
+
+impl fmt::Display for PromptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Word => "word",
+            Self::Sentence => "sentence",
+            Self::Explain => "explain",
+            Self::Grammar => "grammar",
+            Self::Freeform => "freeform",
+            Self::Simplify => "simplify",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Per-chat practice settings, changed with `/settings key=value`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserConfig {
+    /// Default direction when `/practice` is started without ru|de.
+    pub direction: Option<PracticeDirection>,
+    /// How close a misspelled answer must be to still get credit.
+    pub similarity_threshold: f64,
+    pub similarity: Similarity,
+    /// Show session statistics every N answers.
+    pub stats_interval: u32,
+    pub word_kind: Option<WordKind>,
+    /// Count a correct noun typed without its article as right, with a reminder.
+    pub article_partial_credit: bool,
+    /// Read ae/oe/ue/ss in German answers and lookups as ä/ö/ü/ß.
+    pub ascii_digraphs: bool,
+    /// Ask the AI to explain wrong practice answers. Off by default since
+    /// each explanation is an API call.
+    pub explain_mistakes: bool,
+    /// Answers a word needs before its accuracy counts, so a 1/1 word isn't
+    /// called mastered.
+    pub min_attempts_for_mastery: u32,
+    /// Leave leeches (words answered wrong very often) out of practice until
+    /// they're put back.
+    pub suspend_leeches: bool,
+    /// Save looked-up words right away. When off, the card comes with a
+    /// save button instead.
+    pub auto_save: bool,
+    /// Replacements for the built-in AI prompts, set with /setprompt.
+    pub prompts: BTreeMap<PromptKind, String>,
+    /// Set with /targetlang; TARGET_LANG applies until then.
+    pub target_language: Option<TargetLanguage>,
+}
+
+impl Default for UserConfig {
+    fn default() -> Self {
+        Self {
+            direction: None,
+            similarity_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+            similarity: Similarity::default(),
+            stats_interval: DEFAULT_STATS_INTERVAL,
+            word_kind: None,
+            article_partial_credit: false,
+            ascii_digraphs: false,
+            explain_mistakes: false,
+            min_attempts_for_mastery: DEFAULT_MIN_ATTEMPTS_FOR_MASTERY,
+            suspend_leeches: true,
+            auto_save: true,
+            prompts: BTreeMap::new(),
+            target_language: None,
+        }
+    }
+}
+
+impl UserConfig {
+    pub fn target_language(&self) -> TargetLanguage {
+        self.target_language.unwrap_or_else(default_target_language)
+    }
+
+    /// Applies one `key=value` update.
+    pub fn set(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        let value = value.trim().to_lowercase();
+        match key.trim().to_lowercase().as_str() {
+            "direction" => {
+                self.direction = match value.as_str() {
+                    "any" => None,
+                    other => Some(
+                        PracticeDirection::parse(other).ok_or("direction must be ru, de or any")?,
+                    ),
+                };
+            }
+            "strictness" => {
+                let threshold = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|t| (MIN_SIMILARITY_THRESHOLD..=1.0).contains(t))
+                    .ok_or(format!(
+                        "strictness must be a number from {} to 1.0",
+                        MIN_SIMILARITY_THRESHOLD
+                    ))?;
+                self.similarity_threshold = threshold;
+            }
+            "matching" => {
+                self.similarity = Similarity::parse(&value)
+                    .ok_or("matching must be jaro, levenshtein or exact")?;
+            }
+            "stats" => {
+                self.stats_interval = value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or("stats must be a positive number")?;
+            }
+            "words" => {
+                self.word_kind = match value.as_str() {
+                    "any" => None,
+                    other => Some(
+                        WordKind::parse(other).ok_or("words must be noun, verb, other or any")?,
+                    ),
+                };
+            }
+            "article_credit" => {
+                self.article_partial_credit = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("article_credit must be on or off".to_string()),
+                };
+            }
+            "digraphs" => {
+                self.ascii_digraphs = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("digraphs must be on or off".to_string()),
+                };
+            }
+            "explain" => {
+                self.explain_mistakes = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("explain must be on or off".to_string()),
+                };
+            }
+            "mastery" => {
+                self.min_attempts_for_mastery = value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or("mastery must be a positive number")?;
+            }
+            "leeches" => {
+                self.suspend_leeches = match value.as_str() {
+                    "suspend" => true,
+                    "keep" => false,
+                    _ => return Err("leeches must be suspend or keep".to_string()),
+                };
+            }
+            "autosave" => {
+                self.auto_save = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("autosave must be on or off".to_string()),
+                };
+            }
+            other => return Err(format!("Unknown setting '{}'", other)),
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let direction = match self.direction {
+            Some(PracticeDirection::ToRussian) => "ru",
+            Some(PracticeDirection::ToGerman) => "de",
+            None => "any",
+        };
+        let word_kind = self
+            .word_kind
+            .map(|kind| kind.to_string())
+            .unwrap_or_else(|| "any".to_string());
+        let mut description = format!(
+            "⚙️ Settings\n\n\
+             direction={} (ru, de, any)\n\
+             strictness={} ({}-1.0)\n\
+             matching={} (answer comparison: jaro, levenshtein, exact)\n\
+             stats={} (answers between statistics)\n\
+             words={} (noun, verb, other, any)\n\
+             article_credit={} (accept nouns without article: on, off)\n\
+             digraphs={} (read ae/oe/ue/ss as ä/ö/ü/ß: on, off)\n\
+             explain={} (AI explanation of wrong answers: on, off)\n\
+             leeches={} (hard words in practice: suspend, keep)\n\
+             mastery={} (answers before a word's accuracy counts)\n\
+             autosave={} (save looked-up words without asking: on, off)\n\n\
+             Change with /settings key=value",
+            direction,
+            self.similarity_threshold,
+            MIN_SIMILARITY_THRESHOLD,
+            self.similarity,
+            self.stats_interval,
+            word_kind,
+            if self.article_partial_credit {
+                "on"
+            } else {
+                "off"
+            },
+            if self.ascii_digraphs { "on" } else { "off" },
+            if self.explain_mistakes { "on" } else { "off" },
+            if self.suspend_leeches {
+                "suspend"
+            } else {
+                "keep"
+            },
+            self.min_attempts_for_mastery,
+            if self.auto_save { "on" } else { "off" }
+        );
+        if !self.prompts.is_empty() {
+            let kinds: Vec<String> = self.prompts.keys().map(|kind| kind.to_string()).collect();
+            description.push_str(&format!(
+                "\n\nCustom prompts: {} (/resetprompt to restore)",
+                kinds.join(", ")
+            ));
+        }
+        description
+    }
+}
+
+/// Defaults to a config directory next to the translations storage file.
+fn get_config_dir() -> String {
+    env::var("CONFIG_DIR").unwrap_or_else(|_| {
+        let storage_path = crate::translation::get_storage_path();
+        Path::new(&storage_path)
+            .with_file_name("config")
+            .to_string_lossy()
+            .to_string()
+    })
+}
+
+fn get_config_path(chat_id: i64) -> String {
+    Path::new(&get_config_dir())
+        .join(format!("{}.json", chat_id))
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn read_user_config(chat_id: i64) -> Result<UserConfig> {
+    let path = get_config_path(chat_id);
+    if !Path::new(&path).exists() {
+        return Ok(UserConfig::default());
+    }
+    let data = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// The chat's settings, or the defaults when there's no chat or its settings
+/// can't be read.
+pub fn chat_config(chat_id: Option<i64>) -> UserConfig {
+    let Some(chat_id) = chat_id else {
+        return UserConfig::default();
+    };
+    read_user_config(chat_id).unwrap_or_else(|e| {
+        log::warn!("Failed to read settings for {}: {}", chat_id, e);
+        UserConfig::default()
+    })
+}
+
+pub fn write_user_config(chat_id: i64, config: &UserConfig) -> Result<()> {
+    fs::create_dir_all(get_config_dir())?;
+    let path = get_config_path(chat_id);
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string_pretty(config)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_language_is_saved_with_the_settings() {
+        let config = UserConfig {
+            target_language: Some(TargetLanguage::English),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains("\"target_language\":\"en\""));
+
+        let restored: UserConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.target_language(), TargetLanguage::English);
+    }
+}